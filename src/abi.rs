@@ -0,0 +1,393 @@
+//! The contract ABI: what a caller needs to know about a compiled module. It
+//! lists every exported function with its selector, parameters, return type
+//! and mutability, and every event and custom error a contract declares.
+//! `--emit=abi` writes it as `<name>.abi.json`, and each module carries its
+//! SHA-256 in the `mintora.abi` custom section so deployed bytecode can be
+//! matched to its ABI.
+//!
+//! # Calldata
+//!
+//! A module built with the dispatcher also exports `call()`, through which a
+//! chain calls any of its functions by selector. `call` reads its input with
+//! the `calldata_size` and `calldata_copy` host functions and hands back its
+//! output with `return_data`.
+//!
+//! The input is the function's selector as 4 big-endian bytes, followed by its
+//! arguments in order. The output is its return value, or nothing if it has
+//! none. Values are encoded back to back:
+//!
+//! - `i32`, `i64`, `bool` and `f64` as one little-endian 8-byte word, the same
+//!   as event fields: integers sign-extended, `bool` as 1 or 0 (any non-zero
+//!   word decodes as `true`) and `f64` as its IEEE 754 bits;
+//! - `string` as its byte length in one word, then its UTF-8 bytes, unpadded.
+//!
+//! Before calling anything, `call` reverts with `InvalidCalldata()` if the
+//! input is not exactly as long as the arguments need, with
+//! `UnknownSelector(i64)` if no function has the selector, and with
+//! `NotPayable(i64)`, passing the amount, if currency is sent to a function
+//! that is not `#[payable]`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::ast::{EventDecl, Expr, ExprKind, Param, Program, Statement, StmtKind};
+use crate::compiler::{function_items, FnItem};
+
+/// Name of the custom section holding the ABI's SHA-256.
+pub const SECTION: &str = "mintora.abi";
+
+/// The errors `call` itself reverts with, by canonical form.
+pub const INVALID_CALLDATA: &str = "InvalidCalldata()";
+pub const UNKNOWN_SELECTOR: &str = "UnknownSelector(i64)";
+pub const NOT_PAYABLE: &str = "NotPayable(i64)";
+pub const RESERVED_ERRORS: &[&str] = &[INVALID_CALLDATA, UNKNOWN_SELECTOR, NOT_PAYABLE];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+    pub events: Vec<Event>,
+    pub errors: Vec<Error>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Function {
+    pub name: String,
+    /// The contract declaring it; top-level functions have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<String>,
+    /// `0x`-prefixed hex of `selector`.
+    pub selector: String,
+    pub inputs: Vec<Field>,
+    /// The return type; functions without one return nothing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    pub mutability: Mutability,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub name: String,
+    pub contract: String,
+    /// `0x`-prefixed hex of `topic`, which the chain logs the event under.
+    pub topic: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Error {
+    pub name: String,
+    pub contract: String,
+    /// `0x`-prefixed hex of `selector`, which a revert reports.
+    pub selector: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mutability {
+    /// Neither writes storage nor emits events, nor calls anything that does.
+    View,
+    NonPayable,
+    /// Marked `#[payable]`: meant to be called with native currency.
+    Payable,
+}
+
+/// 64-bit FNV-1a, which storage keys, selectors and topics are derived from.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// `name(t1,t2)`, the canonical form selectors and topics are hashed from.
+/// Untyped parameters compile to `i32`, so that is their type here too.
+pub fn canonical(name: &str, params: &[Param]) -> String {
+    let types: Vec<&str> = params.iter().map(param_type).collect();
+    format!("{}({})", name, types.join(","))
+}
+
+fn param_type(p: &Param) -> &str {
+    p.ty.as_ref().map_or("i32", |t| t.as_str())
+}
+
+/// The 4-byte selector of a function or error: the top half of the FNV-1a of its canonical form.
+pub fn selector(canonical: &str) -> u32 {
+    (fnv1a(canonical.bytes()) >> 32) as u32
+}
+
+/// The topic an event is logged under: the FNV-1a of its canonical form.
+pub fn topic(canonical: &str) -> i64 {
+    fnv1a(canonical.bytes()) as i64
+}
+
+fn fields(params: &[Param]) -> Vec<Field> {
+    params.iter().map(|p| Field { name: p.name.to_string(), ty: param_type(p).to_string() }).collect()
+}
+
+/// The ABI of a type-checked program.
+pub fn abi(program: &Program) -> Abi {
+    let items = function_items(program).expect("the program was type-checked");
+    let items: Vec<&FnItem> = items.iter().filter(|f| !f.is_test()).collect();
+    let writers = writers(&items);
+    let functions = items.iter().map(|f| Function {
+        name: f.name.to_string(),
+        contract: f.contract.map(|c| c.name.to_string()),
+        selector: format!("0x{:08x}", selector(&canonical(f.name.as_str(), f.params))),
+        inputs: fields(f.params),
+        output: f.return_type.as_ref().map(|t| t.to_string()).filter(|t| t != "void"),
+        mutability: if f.attribute("payable").is_some() {
+            Mutability::Payable
+        } else if writers.contains(f.name.as_str()) {
+            Mutability::NonPayable
+        } else {
+            Mutability::View
+        },
+    }).collect();
+    let (mut events, mut errors) = (Vec::new(), Vec::new());
+    for stmt in &program.statements {
+        let StmtKind::Contract { name: contract, events: declared, errors: declared_errors, .. } = &stmt.kind else { continue };
+        let sig = |e: &EventDecl| canonical(e.name.as_str(), &e.fields);
+        events.extend(declared.iter().map(|e| Event {
+            name: e.name.to_string(), contract: contract.to_string(), topic: format!("0x{:016x}", topic(&sig(e))), fields: fields(&e.fields),
+        }));
+        errors.extend(declared_errors.iter().map(|e| Error {
+            name: e.name.to_string(), contract: contract.to_string(), selector: format!("0x{:08x}", selector(&sig(e))), fields: fields(&e.fields),
+        }));
+    }
+    Abi { functions, events, errors }
+}
+
+impl Abi {
+    /// The contents of `<name>.abi.json`.
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the ABI serializes") + "\n"
+    }
+
+    /// The SHA-256 of `json()`, as embedded in the `mintora.abi` section.
+    pub fn digest(&self) -> [u8; 32] {
+        sha256(self.json().as_bytes())
+    }
+}
+
+/// Functions that change state: ones that write storage or emit events, and
+/// every function that calls one of those.
+fn writers<'p>(items: &[&FnItem<'p>]) -> HashSet<&'p str> {
+    let mut calls: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut writers = HashSet::new();
+    for f in items {
+        let fields: Vec<&str> = f.contract.map_or(Vec::new(), |c| c.fields.iter().map(|s| s.name.as_str()).collect());
+        let mut walk = Effects { fields, locals: f.params.iter().map(|p| p.name.as_str()).collect(), writes: false, calls: Vec::new() };
+        walk.block(f.body);
+        if walk.writes { writers.insert(f.name.as_str()); }
+        calls.insert(f.name.as_str(), walk.calls);
+    }
+    loop {
+        let more: Vec<&str> = calls.iter()
+            .filter(|(f, callees)| !writers.contains(*f) && callees.iter().any(|c| writers.contains(c)))
+            .map(|(f, _)| *f).collect();
+        if more.is_empty() { return writers; }
+        writers.extend(more);
+    }
+}
+
+/// What one function body does directly. Locals shadow storage fields, so
+/// assigning to a shadowed name is not a write.
+struct Effects<'p> {
+    fields: Vec<&'p str>,
+    locals: Vec<&'p str>,
+    writes: bool,
+    calls: Vec<&'p str>,
+}
+
+impl<'p> Effects<'p> {
+    fn block(&mut self, body: &'p [Statement]) {
+        let depth = self.locals.len();
+        for stmt in body { self.stmt(stmt); }
+        self.locals.truncate(depth);
+    }
+
+    fn stmt(&mut self, stmt: &'p Statement) {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
+                self.expr(value);
+                self.locals.push(name.as_str());
+            }
+            StmtKind::Assign { name, value, .. } => {
+                self.writes |= !self.locals.contains(&name.as_str()) && self.fields.contains(&name.as_str());
+                self.expr(value);
+            }
+            StmtKind::Emit { args, .. } => {
+                self.writes = true;
+                args.iter().for_each(|a| self.expr(a));
+            }
+            StmtKind::Revert { args, .. } => args.iter().for_each(|a| self.expr(a)),
+            StmtKind::Block(inner) => self.block(inner),
+            StmtKind::If { cond, then_body, else_body } => {
+                self.expr(cond);
+                self.block(then_body);
+                if let Some(else_body) = else_body { self.block(else_body); }
+            }
+            StmtKind::While { cond, body } => {
+                self.expr(cond);
+                self.block(body);
+            }
+            StmtKind::Return(Some(e)) | StmtKind::Expr(e) => self.expr(e),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, e: &'p Expr) {
+        match &e.kind {
+            ExprKind::Call { callee, args } => {
+                self.calls.push(callee.as_str());
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } => self.expr(expr),
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A value as calldata carries it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    I32(i32),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Token {
+    /// Parse `text` as a value of the ABI type `ty`, e.g. a command-line argument.
+    pub fn parse(ty: &str, text: &str) -> Option<Token> {
+        match ty {
+            "i32" => text.parse().ok().map(Token::I32),
+            "i64" => text.parse().ok().map(Token::I64),
+            "f64" => text.parse().ok().map(Token::F64),
+            "bool" => text.parse().ok().map(Token::Bool),
+            "string" => Some(Token::String(text.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::I32(v) => write!(f, "{}", v),
+            Token::I64(v) => write!(f, "{}", v),
+            Token::F64(v) => write!(f, "{}", v),
+            Token::Bool(v) => write!(f, "{}", v),
+            Token::String(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Encode values back to back.
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for token in tokens {
+        let word = match token {
+            Token::I32(v) => *v as i64,
+            Token::I64(v) => *v,
+            Token::F64(v) => v.to_bits() as i64,
+            Token::Bool(v) => *v as i64,
+            Token::String(s) => {
+                out.extend_from_slice(&(s.len() as i64).to_le_bytes());
+                out.extend_from_slice(s.as_bytes());
+                continue;
+            }
+        };
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+/// The input to `call` that calls the function with `selector` on `args`.
+pub fn encode_call(selector: u32, args: &[Token]) -> Vec<u8> {
+    let mut out = selector.to_be_bytes().to_vec();
+    out.extend(encode(args));
+    out
+}
+
+/// Decode values of the given ABI types, which must take up all of `bytes`.
+pub fn decode(types: &[&str], bytes: &[u8]) -> Option<Vec<Token>> {
+    fn word(rest: &mut &[u8]) -> Option<i64> {
+        let (head, tail) = rest.split_first_chunk::<8>()?;
+        *rest = tail;
+        Some(i64::from_le_bytes(*head))
+    }
+    let mut rest = bytes;
+    let mut tokens = Vec::new();
+    for ty in types {
+        tokens.push(match *ty {
+            "i32" => Token::I32(word(&mut rest)? as i32),
+            "i64" => Token::I64(word(&mut rest)?),
+            "f64" => Token::F64(f64::from_bits(word(&mut rest)? as u64)),
+            "bool" => Token::Bool(word(&mut rest)? != 0),
+            "string" => {
+                let len = usize::try_from(word(&mut rest)?).ok()?;
+                if len > rest.len() { return None; }
+                let (text, tail) = rest.split_at(len);
+                rest = tail;
+                Token::String(String::from_utf8(text.to_vec()).ok()?)
+            }
+            _ => return None,
+        });
+    }
+    rest.is_empty().then_some(tokens)
+}
+
+/// SHA-256 (FIPS 180-4), so the embedded digest can be checked with standard tools.
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    // Pad with a 1 bit, zeros, and the message length in bits to a multiple of 64 bytes.
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 { message.push(0); }
+    message.extend_from_slice(&(bytes.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() { w[i] = u32::from_be_bytes(word.try_into().unwrap()); }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            (hh, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (x, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) { *x = x.wrapping_add(v); }
+    }
+    let mut out = [0; 32];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(h) { chunk.copy_from_slice(&word.to_be_bytes()); }
+    out
+}
@@ -0,0 +1,315 @@
+//! Name resolution and types for editor features: what each name refers to,
+//! the type of every expression, which names are in scope where, and the
+//! symbols a file declares. Unlike `type_check` it keeps going past errors, so
+//! it works on code that is still being written.
+
+use std::collections::HashMap;
+
+use crate::ast::{Expr, ExprKind, Ident, Param, Program, Span, Statement, StmtKind, StorageField};
+use crate::compiler::{self, infer_expr_type, type_name, ContractItem, Env, Local, Signature, Type};
+use crate::host;
+use crate::lexer::KEYWORDS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Contract,
+    Function,
+    Storage,
+    /// An event or custom error.
+    Event,
+    Param,
+    Local,
+    /// A built-in such as `len` or `msg.sender`.
+    Builtin,
+    Keyword,
+}
+
+/// A declaration, as an outline entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The whole declaration.
+    pub span: Span,
+    /// Just the name.
+    pub name_span: Span,
+    /// The declaration in one line, e.g. `fn add(v: i64) -> i64`.
+    pub detail: String,
+    pub children: Vec<Symbol>,
+}
+
+/// A name that can be completed at some point in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: SymbolKind,
+    pub detail: String,
+}
+
+/// A name that is in scope from `from` to `to`.
+#[derive(Debug)]
+struct Visible {
+    completion: Completion,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Top-level declarations, with contract members as children.
+    pub symbols: Vec<Symbol>,
+    /// Every use or declaration of a name and where it is declared.
+    references: Vec<(Span, Span)>,
+    /// How each declaration reads, keyed by the span of its name.
+    details: HashMap<Span, String>,
+    /// Every expression with its inferred type.
+    types: Vec<(Span, Type)>,
+    scopes: Vec<Visible>,
+    functions: Vec<Completion>,
+}
+
+/// The innermost of `spans` around `offset`.
+fn innermost<T>(items: &[(Span, T)], offset: usize) -> Option<&(Span, T)> {
+    items.iter().filter(|(s, _)| s.start <= offset && offset < s.end).min_by_key(|(s, _)| s.end - s.start)
+}
+
+impl Analysis {
+    /// Where the name at `offset` is declared.
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        innermost(&self.references, offset).map(|&(_, def)| def)
+    }
+
+    /// What is at `offset`: the declaration of a name, or else the type of
+    /// the innermost expression, with the span it covers.
+    pub fn hover(&self, offset: usize) -> Option<(Span, String)> {
+        if let Some((span, def)) = innermost(&self.references, offset) {
+            return self.details.get(def).map(|d| (*span, d.clone()));
+        }
+        innermost(&self.types, offset).map(|(span, ty)| (*span, type_name(ty).to_string()))
+    }
+
+    /// The names that make sense at `offset`: the members of `msg` or `block`
+    /// right after `msg.`, otherwise keywords, functions, built-ins and the
+    /// storage fields, parameters and locals in scope.
+    pub fn completions(&self, src: &str, offset: usize) -> Vec<Completion> {
+        let before = &src[..offset.min(src.len())];
+        let word = before.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+        if let Some(object) = word.strip_suffix('.') {
+            let ns = &object[object.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').len()..];
+            return host::BUILTINS.iter().filter(|b| b.namespace == ns).map(|b| Completion {
+                label: b.field.to_string(), kind: SymbolKind::Builtin, detail: format!("{}.{}: {}", b.namespace, b.field, b.ty),
+            }).collect();
+        }
+        let keywords = KEYWORDS.iter().map(|(k, _)| Completion { label: k.to_string(), kind: SymbolKind::Keyword, detail: String::new() });
+        let builtins = compiler::BUILTIN_FUNCTIONS.iter().chain(&["msg", "block"]).map(|name| Completion {
+            label: name.to_string(),
+            kind: if host::is_namespace(name) { SymbolKind::Builtin } else { SymbolKind::Function },
+            detail: builtin_detail(name).to_string(),
+        });
+        let in_scope = self.scopes.iter().filter(|v| v.from <= offset && offset <= v.to).map(|v| v.completion.clone());
+        let mut all: Vec<Completion> = Vec::new();
+        // Inner declarations come last, and shadow outer ones of the same name.
+        for c in keywords.chain(builtins).chain(self.functions.iter().cloned()).chain(in_scope) {
+            all.retain(|other| other.label != c.label);
+            all.push(c);
+        }
+        all
+    }
+}
+
+fn builtin_detail(name: &str) -> &'static str {
+    match name {
+        "len" => "fn len(s: string) -> i32",
+        "assert" => "fn assert(cond: bool)",
+        "assert_eq" => "fn assert_eq(left, right)",
+        "msg" => "the current call: `msg.sender`, `msg.value`",
+        _ => "the current block: `block.number`, `block.timestamp`",
+    }
+}
+
+fn typed(name: &Ident, ty: Option<&Ident>) -> String {
+    match ty {
+        Some(ty) => format!("{}: {}", name, ty),
+        None => name.to_string(),
+    }
+}
+
+/// `fn name(a: i64, b) -> ty`, as declared.
+pub fn signature(name: &Ident, params: &[Param], return_type: Option<&Ident>) -> String {
+    let params: Vec<String> = params.iter().map(|p| typed(&p.name, p.ty.as_ref())).collect();
+    let ret = return_type.map_or(String::new(), |t| format!(" -> {}", t));
+    format!("fn {}({}){}", name, params.join(", "), ret)
+}
+
+pub fn analyze(program: &Program) -> Analysis {
+    let mut analysis = Analysis::default();
+    let mut fns = HashMap::new();
+    let mut functions = Vec::new();
+    for stmt in &program.statements {
+        match &stmt.kind {
+            StmtKind::Contract { name, fields, events, errors, functions: members } => {
+                let mut children: Vec<Symbol> = fields.iter().map(|f| Symbol {
+                    name: f.name.to_string(), kind: SymbolKind::Storage, span: f.span, name_span: f.name.span,
+                    detail: format!("storage {}: {}", f.name, f.ty), children: Vec::new(),
+                }).collect();
+                for (keyword, decls) in [("event", events), ("error", errors)] {
+                    for e in decls {
+                        let fields: Vec<String> = e.fields.iter().map(|f| typed(&f.name, f.ty.as_ref())).collect();
+                        let detail = format!("{} {}({})", keyword, e.name, fields.join(", "));
+                        analysis.details.insert(e.name.span, detail.clone());
+                        analysis.references.push((e.name.span, e.name.span));
+                        children.push(Symbol {
+                            name: e.name.to_string(), kind: SymbolKind::Event, span: e.span, name_span: e.name.span, detail, children: Vec::new(),
+                        });
+                    }
+                }
+                children.extend(members.iter().filter_map(function_symbol));
+                analysis.symbols.push(Symbol {
+                    name: name.to_string(), kind: SymbolKind::Contract, span: stmt.span, name_span: name.span,
+                    detail: format!("contract {}", name), children,
+                });
+                functions.extend(members.iter().map(|f| (f, Some((stmt.span, ContractItem::of(stmt).expect("a contract"))))));
+            }
+            StmtKind::Function { .. } => {
+                analysis.symbols.extend(function_symbol(stmt));
+                functions.push((stmt, None));
+            }
+            _ => {}
+        }
+    }
+    for (stmt, _) in &functions {
+        if let StmtKind::Function { attributes, name, params, return_type, body } = &stmt.kind {
+            let item = compiler::FnItem { attributes, name, params, return_type, body, contract: None };
+            // The first of several functions with one name is the one calls resolve to.
+            fns.entry(name.to_string()).or_insert_with(|| Signature::of(&item));
+            let detail = signature(name, params, return_type.as_ref());
+            analysis.details.entry(name.span).or_insert_with(|| detail.clone());
+            analysis.references.push((name.span, name.span));
+            if !item.is_test() {
+                analysis.functions.push(Completion { label: name.to_string(), kind: SymbolKind::Function, detail });
+            }
+        }
+    }
+    for (stmt, contract) in functions {
+        let StmtKind::Function { params, body, .. } = &stmt.kind else { continue };
+        let fields = contract.map_or(&[][..], |(_, c)| c.fields);
+        let mut walker = Walker { analysis: &mut analysis, env: Env::new(fields, params, &fns), contract: contract.map(|(_, c)| c) };
+        let contract_span = contract.map_or(stmt.span, |(span, _)| span);
+        walker.declare_fields(fields, contract_span);
+        for p in params {
+            walker.declare(&p.name, SymbolKind::Param, typed(&p.name, p.ty.as_ref()), stmt.span.end);
+        }
+        walker.block(body, stmt.span.end);
+    }
+    analysis
+}
+
+fn function_symbol(stmt: &Statement) -> Option<Symbol> {
+    match &stmt.kind {
+        StmtKind::Function { name, params, return_type, .. } => Some(Symbol {
+            name: name.to_string(), kind: SymbolKind::Function, span: stmt.span, name_span: name.span,
+            detail: signature(name, params, return_type.as_ref()), children: Vec::new(),
+        }),
+        _ => None,
+    }
+}
+
+/// Walks one function body the way `type_check` does, recording instead of
+/// stopping at errors.
+struct Walker<'a, 'f> {
+    analysis: &'a mut Analysis,
+    env: Env<'f>,
+    contract: Option<ContractItem<'f>>,
+}
+
+impl Walker<'_, '_> {
+    fn declare_fields(&mut self, fields: &[StorageField], contract: Span) {
+        for f in fields {
+            self.declare(&f.name, SymbolKind::Storage, format!("storage {}: {}", f.name, f.ty), contract.end);
+        }
+    }
+
+    /// Record a declaration that stays in scope until `to`. The environment
+    /// already holds its type.
+    fn declare(&mut self, name: &Ident, kind: SymbolKind, detail: String, to: usize) {
+        self.analysis.references.push((name.span, name.span));
+        self.analysis.details.insert(name.span, detail.clone());
+        let completion = Completion { label: name.to_string(), kind, detail };
+        self.analysis.scopes.push(Visible { completion, from: name.span.end, to });
+    }
+
+    fn reference(&mut self, name: &Ident) {
+        if let Some(local) = self.env.lookup(name.as_str()) { self.analysis.references.push((name.span, local.span)); }
+    }
+
+    /// A block whose declarations go out of scope at `end`.
+    fn block(&mut self, body: &[Statement], end: usize) {
+        self.env.push();
+        for stmt in body { self.stmt(stmt, end); }
+        self.env.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Statement, end: usize) {
+        match &stmt.kind {
+            StmtKind::Let { name, mutable, ty, value } => {
+                self.expr(value);
+                let ty = ty.as_ref().map(|t| compiler::type_from_name(t.as_str()))
+                    .unwrap_or_else(|| infer_expr_type(value, &self.env).unwrap_or(Type::Unknown));
+                let detail = format!("let {}{}: {}", if *mutable { "mut " } else { "" }, name, type_name(&ty));
+                self.env.declare(name.as_str(), Local { ty, mutable: *mutable, span: name.span });
+                self.declare(name, SymbolKind::Local, detail, end);
+            }
+            StmtKind::Assign { name, value, .. } => {
+                self.reference(name);
+                self.expr(value);
+            }
+            StmtKind::Block(inner) => self.block(inner, stmt.span.end),
+            StmtKind::If { cond, then_body, else_body } => {
+                self.expr(cond);
+                let then_end = else_body.as_ref().and_then(|b| b.first()).map_or(stmt.span.end, |s| s.span.start);
+                self.block(then_body, then_end);
+                if let Some(else_body) = else_body { self.block(else_body, stmt.span.end); }
+            }
+            StmtKind::While { cond, body } => {
+                self.expr(cond);
+                self.block(body, stmt.span.end);
+            }
+            StmtKind::Emit { event: name, args } | StmtKind::Revert { error: name, args } => {
+                let error = matches!(stmt.kind, StmtKind::Revert { .. });
+                if let Some(decl) = self.contract.and_then(|c| c.event(name.as_str(), error)) {
+                    self.analysis.references.push((name.span, decl.name.span));
+                }
+                for arg in args { self.expr(arg); }
+            }
+            StmtKind::Return(Some(expr)) | StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue | StmtKind::Error
+            | StmtKind::Function { .. } | StmtKind::Contract { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        let ty = infer_expr_type(expr, &self.env).unwrap_or(Type::Unknown);
+        self.analysis.types.push((expr.span, ty));
+        match &expr.kind {
+            ExprKind::Ident(name) => {
+                if let Some(local) = self.env.lookup(name) { self.analysis.references.push((expr.span, local.span)); }
+            }
+            ExprKind::Call { callee, args } => {
+                if let Some(sig) = self.env.function(callee.as_str()) { self.analysis.references.push((callee.span, sig.span)); }
+                for arg in args { self.expr(arg); }
+            }
+            ExprKind::Member { object, field } => {
+                if let Ok(b) = compiler::resolve_builtin(object, field.as_str(), |n| self.env.lookup(n).is_some()) {
+                    self.analysis.details.insert(expr.span, format!("{}.{}: {}", b.namespace, b.field, b.ty));
+                    self.analysis.references.push((expr.span, expr.span));
+                }
+            }
+            ExprKind::Unary { expr: inner, .. } | ExprKind::Cast { expr: inner, .. } => self.expr(inner),
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::StringLiteral(_) => {}
+        }
+    }
+}
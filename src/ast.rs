@@ -1,35 +1,197 @@
-/// Abstract Syntax Tree for Mintora
-
-#[derive(Debug, Clone)]
-pub struct Param {
-    pub name: String,
-    pub ty: Option<String>, // e.g., "i32", "string"
-}
-
-#[derive(Debug)]
-pub enum Expr {
-    Number(f64),
-    StringLiteral(String),
-    Ident(String),
-    Binary { op: BinOp, left: Box<Expr>, right: Box<Expr> },
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum BinOp { Add }
-
-#[derive(Debug)]
-pub enum Statement {
-    Function {
-        name: String,
-        params: Vec<Param>,
-        return_type: Option<String>,
-        body: Vec<Statement>,
-    },
-    Return(Expr),
-    Expr(Expr),
-}
-
-#[derive(Debug)]
-pub struct Program {
-    pub statements: Vec<Statement>,
-}
+//! Abstract Syntax Tree for Mintora. Every node records the source span it was
+//! parsed from.
+
+use serde::Serialize;
+
+pub use crate::span::{FileId, Span};
+
+/// A name or type annotation as written in the source.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Ident {
+    pub fn as_str(&self) -> &str { &self.name }
+}
+
+impl PartialEq<str> for Ident {
+    fn eq(&self, other: &str) -> bool { self.name == other }
+}
+
+impl PartialEq<&str> for Ident {
+    fn eq(&self, other: &&str) -> bool { self.name == *other }
+}
+
+impl std::fmt::Display for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str(&self.name) }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Param {
+    pub name: Ident,
+    pub ty: Option<Ident>, // e.g., "i32", "string"
+    pub span: Span,
+}
+
+/// A persistent field declared inside a `contract` block: `storage name: ty;`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageField {
+    pub name: Ident,
+    pub ty: Ident,
+    pub span: Span,
+}
+
+/// An `event` or `error` declared inside a contract: a name and typed fields,
+/// e.g. `event Bumped(by: i64, total: i64);`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventDecl {
+    pub name: Ident,
+    pub fields: Vec<Param>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum ExprKind {
+    /// Integer literal; its value is range-checked against the type it ends up with.
+    Int(u64),
+    Float(f64),
+    Bool(bool),
+    StringLiteral(String),
+    Ident(String),
+    Call { callee: Ident, args: Vec<Expr> },
+    /// `object.field`; currently only built-ins such as `msg.sender` resolve.
+    Member { object: Box<Expr>, field: Ident },
+    Unary { op: UnaryOp, expr: Box<Expr> },
+    Binary { op: BinOp, left: Box<Expr>, right: Box<Expr> },
+    /// Explicit numeric conversion: `expr as ty`.
+    Cast { expr: Box<Expr>, ty: Ident },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum UnaryOp { Neg, Not }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BinOp {
+    Add, Sub, Mul, Div, Rem,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    And, Or,
+    BitAnd, BitOr, BitXor, Shl, Shr,
+}
+
+impl UnaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self { UnaryOp::Neg => "-", UnaryOp::Not => "!" }
+    }
+}
+
+impl BinOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+", BinOp::Sub => "-", BinOp::Mul => "*", BinOp::Div => "/", BinOp::Rem => "%",
+            BinOp::Eq => "==", BinOp::Ne => "!=", BinOp::Lt => "<", BinOp::Le => "<=", BinOp::Gt => ">", BinOp::Ge => ">=",
+            BinOp::And => "&&", BinOp::Or => "||",
+            BinOp::BitAnd => "&", BinOp::BitOr => "|", BinOp::BitXor => "^", BinOp::Shl => "<<", BinOp::Shr => ">>",
+        }
+    }
+
+    /// Binding strength, higher binds tighter. All binary operators are
+    /// left-associative; comparisons additionally refuse to chain.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 3,
+            BinOp::BitOr => 4,
+            BinOp::BitXor => 5,
+            BinOp::BitAnd => 6,
+            BinOp::Shl | BinOp::Shr => 7,
+            BinOp::Add | BinOp::Sub => 8,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 9,
+        }
+    }
+
+    pub fn is_comparison(self) -> bool { self.precedence() == 3 }
+    pub fn is_logical(self) -> bool { matches!(self, BinOp::And | BinOp::Or) }
+    pub fn is_bitwise(self) -> bool { matches!(self.precedence(), 4..=7) }
+}
+
+/// `#[name]` before a function, e.g. `#[test]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Attribute {
+    pub name: Ident,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum StmtKind {
+    Contract {
+        name: Ident,
+        fields: Vec<StorageField>,
+        events: Vec<EventDecl>,
+        errors: Vec<EventDecl>,
+        functions: Vec<Statement>,
+    },
+    Function {
+        attributes: Vec<Attribute>,
+        name: Ident,
+        params: Vec<Param>,
+        return_type: Option<Ident>,
+        body: Vec<Statement>,
+    },
+    Let {
+        name: Ident,
+        mutable: bool,
+        ty: Option<Ident>,
+        value: Expr,
+    },
+    /// `name = value;` or, with `op`, the compound form `name op= value;`.
+    Assign { name: Ident, op: Option<BinOp>, value: Expr },
+    Block(Vec<Statement>),
+    /// `else if` chains are represented as an `else` body holding a single `If`.
+    If {
+        cond: Expr,
+        then_body: Vec<Statement>,
+        else_body: Option<Vec<Statement>>,
+    },
+    While { cond: Expr, body: Vec<Statement> },
+    Break,
+    Continue,
+    Return(Option<Expr>),
+    /// `emit Event(args);` logs an event of the enclosing contract.
+    Emit { event: Ident, args: Vec<Expr> },
+    /// `revert Error(args);` aborts the call with an error of the enclosing contract.
+    Revert { error: Ident, args: Vec<Expr> },
+    Expr(Expr),
+    /// Source the parser could not make sense of; it reported a diagnostic and
+    /// skipped ahead to the next statement.
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+impl Program {
+    /// The function declared as `name`, at the top level or inside a contract.
+    pub fn function(&self, name: &str) -> Option<&Statement> {
+        self.statements.iter().flat_map(|s| match &s.kind {
+            StmtKind::Contract { functions, .. } => functions.iter().collect(),
+            _ => vec![s],
+        }).find(|s| matches!(&s.kind, StmtKind::Function { name: n, .. } if n == name))
+    }
+}
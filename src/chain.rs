@@ -0,0 +1,203 @@
+//! A simulated chain that runs compiled modules in-process: accounts with
+//! balances, deployed contracts with their own storage, the block context and
+//! an event log. It implements the host ABI in `host`, so `mintora run` and
+//! tests can call contract functions without a real node.
+
+use std::collections::HashMap;
+
+use crate::host;
+use crate::wasm::interp::{Host, Instance, Trap, Value};
+
+/// An account id, as the host ABI passes them.
+pub type Address = i64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub number: i64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: i64,
+}
+
+/// Who makes a call and what it sends, as `msg.sender` and `msg.value` see them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    pub sender: Address,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub contract: Address,
+    /// Identifies the event, see `abi::topic`.
+    pub topic: i64,
+    /// The event's fields, encoded as `host::words` describes.
+    pub data: Vec<i64>,
+}
+
+struct Contract {
+    /// The freshly instantiated module; each call runs on a copy of it.
+    module: Instance,
+    /// The copy the last successful call ran on, whose memory holds any string it returned.
+    last: Option<Instance>,
+    storage: HashMap<i64, i64>,
+}
+
+pub struct Chain {
+    pub block: Block,
+    balances: HashMap<Address, i64>,
+    contracts: HashMap<Address, Contract>,
+    events: Vec<Event>,
+    next_address: Address,
+}
+
+impl Default for Chain {
+    fn default() -> Self { Self::new() }
+}
+
+impl Chain {
+    pub fn new() -> Self {
+        Chain { block: Block { number: 1, timestamp: 1_700_000_000 }, balances: HashMap::new(), contracts: HashMap::new(), events: Vec::new(), next_address: 1 }
+    }
+
+    /// A new externally owned account holding `balance`.
+    pub fn create_account(&mut self, balance: i64) -> Address {
+        let address = self.next_address;
+        self.next_address += 1;
+        self.balances.insert(address, balance);
+        address
+    }
+
+    /// Instantiate a compiled module as a new contract with empty storage.
+    pub fn deploy(&mut self, wasm: &[u8]) -> Result<Address, String> {
+        let module = Instance::new(wasm)?;
+        let address = self.create_account(0);
+        self.contracts.insert(address, Contract { module, last: None, storage: HashMap::new() });
+        Ok(address)
+    }
+
+    pub fn balance(&self, account: Address) -> i64 {
+        self.balances.get(&account).copied().unwrap_or(0)
+    }
+
+    /// A storage cell of a contract; unset cells read as 0.
+    pub fn storage(&self, contract: Address, key: i64) -> i64 {
+        self.contracts.get(&contract).and_then(|c| c.storage.get(&key)).copied().unwrap_or(0)
+    }
+
+    /// A string returned by the last successful call to `contract`.
+    pub fn string(&self, contract: Address, packed: i64) -> Result<String, Trap> {
+        let last = self.contracts.get(&contract).and_then(|c| c.last.as_ref());
+        last.ok_or_else(|| Trap::Call(format!("contract {} has not been called", contract)))?.string(packed)
+    }
+
+    /// Every event emitted by a successful call, oldest first.
+    pub fn events(&self) -> &[Event] { &self.events }
+
+    /// Move to a later block, `seconds` after the current one.
+    pub fn advance(&mut self, blocks: i64, seconds: i64) {
+        self.block.number += blocks;
+        self.block.timestamp += seconds;
+    }
+
+    /// Call an exported function of `contract`, which receives `msg.value`
+    /// from the sender. If the call traps, none of its effects remain.
+    pub fn call(&mut self, contract: Address, msg: Message, name: &str, args: &[Value]) -> Result<Option<Value>, Trap> {
+        self.execute(contract, msg, name, args, Vec::new()).map(|(result, _)| result)
+    }
+
+    /// Call a contract built with the dispatcher through its `call` entry
+    /// point, returning its output. Both are encoded as `abi` describes.
+    pub fn transact(&mut self, contract: Address, msg: Message, calldata: &[u8]) -> Result<Vec<u8>, Trap> {
+        self.execute(contract, msg, "call", &[], calldata.to_vec()).map(|(_, output)| output)
+    }
+
+    fn execute(&mut self, contract: Address, msg: Message, name: &str, args: &[Value], calldata: Vec<u8>) -> Result<(Option<Value>, Vec<u8>), Trap> {
+        let Some(target) = self.contracts.get_mut(&contract) else {
+            return Err(Trap::Call(format!("there is no contract at address {}", contract)));
+        };
+        let available = self.balances.get(&msg.sender).copied().unwrap_or(0);
+        if msg.value < 0 || msg.value > available {
+            return Err(Trap::Call(format!("account {} cannot send {} with a balance of {}", msg.sender, msg.value, available)));
+        }
+        let held = self.balances.get(&contract).copied().unwrap_or(0);
+        if msg.sender != contract && held.checked_add(msg.value).is_none() {
+            return Err(Trap::Call(format!("contract {} cannot receive {} on top of a balance of {}", contract, msg.value, held)));
+        }
+        let mut env = Env {
+            contract,
+            msg,
+            block: self.block,
+            storage: target.storage.clone(),
+            events: Vec::new(),
+            calldata,
+            output: Vec::new(),
+        };
+        let mut instance = target.module.clone();
+        let result = instance.invoke(name, args, &mut env)?;
+        // Only a successful call commits its storage writes, events and transfer.
+        target.storage = env.storage;
+        target.last = Some(instance);
+        self.events.extend(env.events);
+        *self.balances.entry(msg.sender).or_default() -= msg.value;
+        *self.balances.entry(contract).or_default() += msg.value;
+        Ok((result, env.output))
+    }
+}
+
+/// The host side of one call. Writes go to a copy of the contract's storage
+/// that replaces the original only if the call succeeds.
+struct Env {
+    contract: Address,
+    msg: Message,
+    block: Block,
+    storage: HashMap<i64, i64>,
+    events: Vec<Event>,
+    calldata: Vec<u8>,
+    /// What the call passed to `return_data`.
+    output: Vec<u8>,
+}
+
+impl Host for Env {
+    fn call(&mut self, name: &str, args: &[Value], memory: &mut [u8]) -> Result<Option<Value>, Trap> {
+        let i64_arg = |i: usize| match args.get(i) {
+            Some(Value::I64(v)) => Ok(*v),
+            _ => Err(Trap::Host(format!("host function `{}` expects i64 arguments", name))),
+        };
+        let words = |i: usize| host::words(memory, i64_arg(i)?).ok_or(Trap::OutOfBounds);
+        let result = match name {
+            _ if name == host::STORAGE_READ.name => self.storage.get(&i64_arg(0)?).copied().unwrap_or(0),
+            _ if name == host::STORAGE_WRITE.name => {
+                self.storage.insert(i64_arg(0)?, i64_arg(1)?);
+                return Ok(None);
+            }
+            _ if name == host::CALLER.name => self.msg.sender,
+            _ if name == host::CALL_VALUE.name => self.msg.value,
+            _ if name == host::BLOCK_NUMBER.name => self.block.number,
+            _ if name == host::BLOCK_TIMESTAMP.name => self.block.timestamp,
+            _ if name == host::ASSERT_FAILED.name => {
+                let location = i64_arg(0)? as u64;
+                return Err(Trap::AssertionFailed { start: (location >> 32) as usize, end: location as u32 as usize });
+            }
+            _ if name == host::EMIT_EVENT.name => {
+                self.events.push(Event { contract: self.contract, topic: i64_arg(0)?, data: words(1)? });
+                return Ok(None);
+            }
+            _ if name == host::CALLDATA_SIZE.name => self.calldata.len() as i64,
+            _ if name == host::CALLDATA_COPY.name => {
+                let dest = usize::try_from(i64_arg(0)?).map_err(|_| Trap::OutOfBounds)?;
+                let end = dest.checked_add(self.calldata.len()).ok_or(Trap::OutOfBounds)?;
+                memory.get_mut(dest..end).ok_or(Trap::OutOfBounds)?.copy_from_slice(&self.calldata);
+                return Ok(None);
+            }
+            _ if name == host::RETURN_DATA.name => {
+                let packed = i64_arg(0)?;
+                let (ptr, len) = ((packed as u64 >> 32) as usize, packed as u32 as usize);
+                self.output = memory.get(ptr..ptr + len).ok_or(Trap::OutOfBounds)?.to_vec();
+                return Ok(None);
+            }
+            _ if name == host::REVERT.name => return Err(Trap::Revert { selector: i64_arg(0)? as u32, data: words(1)? }),
+            _ => return Err(Trap::Host(format!("unknown host function `{}`", name))),
+        };
+        Ok(Some(Value::I64(result)))
+    }
+}
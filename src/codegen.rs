@@ -0,0 +1,1060 @@
+//! Lowering of a type-checked program to a WASM module.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::abi;
+use crate::ast::{BinOp, EventDecl, Expr, ExprKind, Param, Program, Span, Statement, StmtKind, UnaryOp};
+use crate::compiler::{
+    assertion_condition, collect_signatures, common_type, function_items, infer_expr_type, int_literal, resolve_builtin,
+    type_from_name, type_name, ContractItem, Env, FnItem, Local, Signature, Type, ASSERTIONS,
+};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::host::{self, HostFn};
+use crate::runtime::{Helper, DATA_BASE, HELPERS};
+use crate::wasm::{write_sleb, write_uleb, FuncType, ModuleBuilder, ValType};
+
+/// Opcode of `op` applied to two operands of (lowered) type `ty`.
+fn binop_opcode(op: BinOp, ty: ValType) -> Option<u8> {
+    Some(match (ty, op) {
+        (ValType::I32, BinOp::Add) => 0x6A, // i32.add
+        (ValType::I32, BinOp::Sub) => 0x6B, // i32.sub
+        (ValType::I32, BinOp::Mul) => 0x6C, // i32.mul
+        (ValType::I32, BinOp::Div) => 0x6D, // i32.div_s
+        (ValType::I32, BinOp::Rem) => 0x6F, // i32.rem_s
+        (ValType::I32, BinOp::BitAnd) => 0x71, // i32.and
+        (ValType::I32, BinOp::BitOr) => 0x72, // i32.or
+        (ValType::I32, BinOp::BitXor) => 0x73, // i32.xor
+        (ValType::I32, BinOp::Shl) => 0x74, // i32.shl
+        (ValType::I32, BinOp::Shr) => 0x75, // i32.shr_s
+        (ValType::I32, BinOp::Eq) => 0x46, // i32.eq
+        (ValType::I32, BinOp::Ne) => 0x47, // i32.ne
+        (ValType::I32, BinOp::Lt) => 0x48, // i32.lt_s
+        (ValType::I32, BinOp::Gt) => 0x4A, // i32.gt_s
+        (ValType::I32, BinOp::Le) => 0x4C, // i32.le_s
+        (ValType::I32, BinOp::Ge) => 0x4E, // i32.ge_s
+        (ValType::I64, BinOp::Add) => 0x7C, // i64.add
+        (ValType::I64, BinOp::Sub) => 0x7D, // i64.sub
+        (ValType::I64, BinOp::Mul) => 0x7E, // i64.mul
+        (ValType::I64, BinOp::Div) => 0x7F, // i64.div_s
+        (ValType::I64, BinOp::Rem) => 0x81, // i64.rem_s
+        (ValType::I64, BinOp::BitAnd) => 0x83, // i64.and
+        (ValType::I64, BinOp::BitOr) => 0x84, // i64.or
+        (ValType::I64, BinOp::BitXor) => 0x85, // i64.xor
+        (ValType::I64, BinOp::Shl) => 0x86, // i64.shl
+        (ValType::I64, BinOp::Shr) => 0x87, // i64.shr_s
+        (ValType::I64, BinOp::Eq) => 0x51, // i64.eq
+        (ValType::I64, BinOp::Ne) => 0x52, // i64.ne
+        (ValType::I64, BinOp::Lt) => 0x53, // i64.lt_s
+        (ValType::I64, BinOp::Gt) => 0x55, // i64.gt_s
+        (ValType::I64, BinOp::Le) => 0x57, // i64.le_s
+        (ValType::I64, BinOp::Ge) => 0x59, // i64.ge_s
+        (ValType::F64, BinOp::Add) => 0xA0, // f64.add
+        (ValType::F64, BinOp::Sub) => 0xA1, // f64.sub
+        (ValType::F64, BinOp::Mul) => 0xA2, // f64.mul
+        (ValType::F64, BinOp::Div) => 0xA3, // f64.div
+        (ValType::F64, BinOp::Eq) => 0x61, // f64.eq
+        (ValType::F64, BinOp::Ne) => 0x62, // f64.ne
+        (ValType::F64, BinOp::Lt) => 0x63, // f64.lt
+        (ValType::F64, BinOp::Gt) => 0x64, // f64.gt
+        (ValType::F64, BinOp::Le) => 0x65, // f64.le
+        (ValType::F64, BinOp::Ge) => 0x66, // f64.ge
+        _ => return None,
+    })
+}
+
+/// Instructions converting a value of type `from` to `to`, as allowed by the
+/// type checker's implicit widenings and `as` casts. Float-to-int truncation
+/// saturates instead of trapping.
+fn conversion(from: &Type, to: &Type) -> Option<&'static [u8]> {
+    Some(match (from, to) {
+        _ if from == to => &[],
+        (Type::Bool, Type::I32) => &[],
+        (Type::I32, Type::I64) => &[0xAC], // i64.extend_i32_s
+        (Type::Bool, Type::I64) => &[0xAD], // i64.extend_i32_u
+        (Type::I32, Type::F64) => &[0xB7], // f64.convert_i32_s
+        (Type::Bool, Type::F64) => &[0xB8], // f64.convert_i32_u
+        (Type::I64, Type::F64) => &[0xB9], // f64.convert_i64_s
+        (Type::I64, Type::I32) => &[0xA7], // i32.wrap_i64
+        (Type::F64, Type::I32) => &[0xFC, 0x02], // i32.trunc_sat_f64_s
+        (Type::F64, Type::I64) => &[0xFC, 0x06], // i64.trunc_sat_f64_s
+        _ => return None,
+    })
+}
+
+/// Untyped params are treated as `i32` by codegen.
+fn concrete(ty: Type) -> Type {
+    if ty == Type::Unknown { Type::I32 } else { ty }
+}
+
+/// Map a Mintora type onto its WASM value type (`None` = no value).
+fn lower(ty: &Type) -> Option<ValType> {
+    match ty {
+        Type::I32 | Type::Bool | Type::Unknown => Some(ValType::I32),
+        Type::I64 => Some(ValType::I64),
+        Type::F64 => Some(ValType::F64),
+        // (ptr, len) packed into one i64, see `runtime`.
+        Type::String => Some(ValType::I64),
+        Type::Void => None,
+    }
+}
+
+/// Lower a type annotation; a missing annotation means an (implicitly i32) untyped param.
+fn lower_annotation(ty: Option<&str>) -> Result<Option<ValType>, String> {
+    match ty.map(type_from_name) {
+        None => Ok(Some(ValType::I32)),
+        Some(Type::Unknown) => Err(format!("unknown type `{}`", ty.unwrap_or_default())),
+        Some(t) => Ok(lower(&t)),
+    }
+}
+
+/// Where a name lives at runtime.
+#[derive(Clone, Copy)]
+enum Place {
+    Local(u32),
+    /// Slot key of a storage field.
+    Storage(i64),
+}
+
+/// String literals laid out back to back from `DATA_BASE`, deduplicated.
+#[derive(Default)]
+struct StringPool {
+    data: Vec<u8>,
+    offsets: HashMap<String, u32>,
+}
+
+impl StringPool {
+    /// Intern `s` and return its packed `(ptr << 32) | len` value.
+    fn intern(&mut self, s: &str) -> i64 {
+        let offset = match self.offsets.get(s) {
+            Some(&off) => off,
+            None => {
+                let off = DATA_BASE + self.data.len() as u32;
+                self.data.extend_from_slice(s.as_bytes());
+                self.offsets.insert(s.to_string(), off);
+                off
+            }
+        };
+        ((offset as i64) << 32) | s.len() as i64
+    }
+}
+
+/// Module-wide resources shared by every function emitter: imported host
+/// functions, runtime helpers and the string literal pool. Lowering runs twice:
+/// a probe pass records what the code actually uses, then `link` imports and
+/// declares exactly that and the real pass emits code against final indices.
+#[derive(Default)]
+struct Linker {
+    hosts: HashMap<&'static str, u32>,
+    helpers: HashMap<Helper, u32>,
+    used_hosts: RefCell<Vec<&'static str>>,
+    used_helpers: RefCell<Vec<Helper>>,
+    strings: RefCell<StringPool>,
+    /// Building a test module: `#[test]` functions are included and failed
+    /// assertions report where they are.
+    tests: bool,
+    /// Also export the `call` entry point, see `abi`.
+    dispatcher: bool,
+}
+
+impl Linker {
+    fn link(module: &mut ModuleBuilder, probe: &Linker) -> Self {
+        // Host imports come first in the function index space, in canonical ABI order.
+        let used = probe.used_hosts.borrow();
+        let mut hosts = HashMap::new();
+        for f in host::FUNCTIONS.iter().filter(|f| used.contains(&f.name)) {
+            hosts.insert(f.name, module.import_function(host::MODULE, f.name, f.func_type()));
+        }
+
+        // Runtime helpers follow, so their indices are known before user code is emitted.
+        let mut needed = probe.used_helpers.borrow().clone();
+        let mut i = 0;
+        while i < needed.len() {
+            for dep in needed[i].deps() {
+                if !needed.contains(dep) { needed.push(*dep); }
+            }
+            i += 1;
+        }
+        let mut helpers = HashMap::new();
+        for &h in HELPERS.iter().filter(|h| needed.contains(h)) {
+            helpers.insert(h, module.declare_function(h.func_type()));
+        }
+
+        let pool = probe.strings.borrow();
+        if !helpers.is_empty() || !pool.data.is_empty() {
+            let data_end = DATA_BASE + pool.data.len() as u32;
+            module.set_memory(data_end / 65536 + 1, "memory");
+            if !pool.data.is_empty() { module.add_data(DATA_BASE, pool.data.clone()); }
+            let heap = module.add_global_i32(((data_end + 7) & !7) as i32);
+            let alloc = helpers.get(&Helper::Alloc).copied().unwrap_or(0);
+            for (&h, &idx) in &helpers {
+                module.set_body(idx, h.body(heap, alloc));
+            }
+        }
+
+        Linker { hosts, helpers, tests: probe.tests, dispatcher: probe.dispatcher, ..Linker::default() }
+    }
+
+    fn host(&self, f: &HostFn) -> u32 {
+        let mut used = self.used_hosts.borrow_mut();
+        if !used.contains(&f.name) { used.push(f.name); }
+        self.hosts.get(f.name).copied().unwrap_or(0)
+    }
+
+    fn helper(&self, h: Helper) -> u32 {
+        let mut used = self.used_helpers.borrow_mut();
+        if !used.contains(&h) { used.push(h); }
+        self.helpers.get(&h).copied().unwrap_or(0)
+    }
+}
+
+/// Deterministic slot key of a storage field: FNV-1a over `Contract.field`, so
+/// keys stay stable when fields are added or reordered.
+fn storage_key(contract: &str, field: &str) -> i64 {
+    abi::fnv1a(contract.bytes().chain(std::iter::once(b'.')).chain(field.bytes())) as i64
+}
+
+/// Per-function code emitter. Params occupy the first local indices; each `let`
+/// allocates a fresh local so shadowed bindings never clobber each other. A
+/// type environment is kept in lockstep with the index scopes so expression
+/// types can be recovered exactly as the type checker saw them.
+struct FnEmitter<'a> {
+    env: Env<'a>,
+    funcs: &'a HashMap<String, FnInfo>,
+    linker: &'a Linker,
+    ret: Type,
+    /// Slot keys of the enclosing contract's storage fields.
+    storage: Option<&'a HashMap<String, i64>>,
+    /// The enclosing contract, whose events and errors `emit` and `revert` name.
+    contract: Option<ContractItem<'a>>,
+    n_params: u32,
+    locals: Vec<ValType>,
+    scopes: Vec<HashMap<String, u32>>,
+    code: Vec<u8>,
+    /// `(start, end, index)` of the most recent `local.set`, so an immediately
+    /// following `local.get` of the same slot can fold into `local.tee`.
+    last_set: Option<(usize, usize, u32)>,
+    /// Number of enclosing structured blocks (`block`/`loop`/`if`).
+    depth: u32,
+    /// `(break, continue)` label depths of the enclosing `while` loops.
+    loops: Vec<(u32, u32)>,
+}
+
+impl<'a> FnEmitter<'a> {
+    fn new(
+        env: Env<'a>,
+        params: &[Param],
+        funcs: &'a HashMap<String, FnInfo>,
+        linker: &'a Linker,
+        ret: Type,
+        storage: Option<&'a HashMap<String, i64>>,
+        contract: Option<ContractItem<'a>>,
+    ) -> Self {
+        let mut root = HashMap::new();
+        for (i, p) in params.iter().enumerate() { root.insert(p.name.to_string(), i as u32); }
+        FnEmitter {
+            env, funcs, linker, ret, storage, contract, n_params: params.len() as u32, locals: Vec::new(),
+            scopes: vec![root], code: Vec::new(), last_set: None, depth: 0, loops: Vec::new(),
+        }
+    }
+
+    fn type_of(&self, e: &Expr) -> Result<Type, String> {
+        infer_expr_type(e, &self.env).map(concrete).map_err(|d| d.message)
+    }
+
+    /// Locals shadow storage fields, mirroring the type checker's scoping.
+    fn lookup(&self, name: &str) -> Result<Place, String> {
+        if let Some(idx) = self.scopes.iter().rev().find_map(|s| s.get(name).copied()) {
+            return Ok(Place::Local(idx));
+        }
+        match self.storage.and_then(|slots| slots.get(name)) {
+            Some(&key) => Ok(Place::Storage(key)),
+            None => Err(format!("Unknown identifier `{}`", name)),
+        }
+    }
+
+    /// Push the value of `place`, whose Mintora type is `ty`. Storage cells hold
+    /// an i64, so narrower values are truncated and floats reinterpreted.
+    fn load(&mut self, place: Place, ty: &Type) {
+        match place {
+            Place::Local(idx) => self.local_get(idx),
+            Place::Storage(key) => {
+                self.i64_const(key);
+                self.call_host(&host::STORAGE_READ);
+                match ty {
+                    Type::F64 => self.code.push(0xBF), // f64.reinterpret_i64
+                    Type::I64 => {}
+                    _ => self.code.push(0xA7), // i32.wrap_i64
+                }
+            }
+        }
+    }
+
+    /// Convert the value on the stack from `from` to `to`.
+    fn convert(&mut self, from: &Type, to: &Type) -> Result<(), String> {
+        let to = concrete(to.clone());
+        let ops = conversion(from, &to)
+            .ok_or_else(|| format!("cannot convert `{}` to `{}`", type_name(from), type_name(&to)))?;
+        self.code.extend_from_slice(ops);
+        Ok(())
+    }
+
+    /// Emit `e` converted to `to`. Integer literals are emitted directly in the
+    /// target type instead of being widened at runtime.
+    fn emit_expr_as(&mut self, e: &Expr, to: &Type) -> Result<(), String> {
+        if let Some(v) = int_literal(e) {
+            if self.emit_int(v, &concrete(to.clone()))? { return Ok(()); }
+        }
+        let from = self.type_of(e)?;
+        self.emit_expr(e)?;
+        self.convert(&from, to)
+    }
+
+    /// Emit the integer literal `v` as a constant of type `ty`, if numeric.
+    fn emit_int(&mut self, v: i128, ty: &Type) -> Result<bool, String> {
+        let out_of_range = || format!("integer literal `{}` is out of range for `{}`", v, type_name(ty));
+        match ty {
+            Type::I32 => {
+                let v = i32::try_from(v).map_err(|_| out_of_range())?;
+                self.code.push(0x41); // i32.const
+                write_sleb(v as i64, &mut self.code);
+            }
+            Type::I64 => self.i64_const(i64::try_from(v).map_err(|_| out_of_range())?),
+            Type::F64 => self.f64_const(v as f64),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn i64_const(&mut self, v: i64) {
+        self.code.push(0x42); // i64.const
+        write_sleb(v, &mut self.code);
+    }
+
+    fn f64_const(&mut self, v: f64) {
+        self.code.push(0x44); // f64.const
+        self.code.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn call(&mut self, func: u32) {
+        self.code.push(0x10); // call
+        write_uleb(func, &mut self.code);
+    }
+
+    fn call_host(&mut self, f: &HostFn) {
+        let index = self.linker.host(f);
+        self.call(index);
+    }
+
+    fn call_helper(&mut self, h: Helper) {
+        let index = self.linker.helper(h);
+        self.call(index);
+    }
+
+    fn declare(&mut self, name: &str, ty: ValType) -> u32 {
+        let idx = self.n_params + self.locals.len() as u32;
+        self.locals.push(ty);
+        self.scopes.last_mut().expect("at least one scope").insert(name.to_string(), idx);
+        idx
+    }
+
+    fn local_get(&mut self, idx: u32) {
+        if let Some((start, end, set_idx)) = self.last_set.take() {
+            if end == self.code.len() && set_idx == idx {
+                self.code[start] = 0x22; // local.set -> local.tee
+                return;
+            }
+        }
+        self.code.push(0x20); // local.get
+        write_uleb(idx, &mut self.code);
+    }
+
+    fn local_set(&mut self, idx: u32) {
+        let start = self.code.len();
+        self.code.push(0x21); // local.set
+        write_uleb(idx, &mut self.code);
+        self.last_set = Some((start, self.code.len(), idx));
+    }
+
+    fn open(&mut self, header: &[u8]) {
+        self.code.extend_from_slice(header);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.code.push(0x0B); // end
+        self.depth -= 1;
+    }
+
+    /// Emit `br`/`br_if` to the block opened at label depth `target`.
+    fn branch(&mut self, opcode: u8, target: u32) {
+        self.code.push(opcode);
+        write_uleb(self.depth - target, &mut self.code);
+    }
+
+    fn emit_block(&mut self, body: &[Statement]) -> Result<(), String> {
+        self.scopes.push(HashMap::new());
+        self.env.push();
+        let res = body.iter().try_for_each(|s| self.emit_stmt(s));
+        self.env.pop();
+        self.scopes.pop();
+        res
+    }
+
+    fn emit_stmt(&mut self, stmt: &Statement) -> Result<(), String> {
+        match &stmt.kind {
+            StmtKind::Let { name, mutable, ty, value } => {
+                let ty = match ty {
+                    Some(t) => type_from_name(t.as_str()),
+                    None => self.type_of(value)?,
+                };
+                let ty = concrete(ty);
+                let vt = lower(&ty).ok_or_else(|| format!("`{}` cannot hold a `void` value", name))?;
+                // Evaluate before declaring so `let x = x + 1;` reads the outer `x`.
+                self.emit_expr_as(value, &ty)?;
+                let idx = self.declare(name.as_str(), vt);
+                self.env.declare(name.as_str(), Local { ty, mutable: *mutable, span: name.span });
+                self.local_set(idx);
+            }
+            StmtKind::Assign { name, op, value } => {
+                let place = self.lookup(name.as_str())?;
+                let ty = self.env.lookup(name.as_str()).map(|l| concrete(l.ty.clone()))
+                    .ok_or_else(|| format!("Unknown identifier `{}`", name))?;
+                if let Place::Storage(key) = place { self.i64_const(key); }
+                if let Some(op) = op {
+                    self.load(place, &ty);
+                    self.emit_binary(*op, &ty, value)?;
+                } else {
+                    self.emit_expr_as(value, &ty)?;
+                }
+                match place {
+                    Place::Local(idx) => self.local_set(idx),
+                    Place::Storage(_) => {
+                        match ty {
+                            Type::F64 => self.code.push(0xBD), // i64.reinterpret_f64
+                            Type::I64 => {}
+                            _ => self.code.push(0xAC), // i64.extend_i32_s
+                        }
+                        self.call_host(&host::STORAGE_WRITE);
+                    }
+                }
+            }
+            StmtKind::Block(inner) => self.emit_block(inner)?,
+            StmtKind::If { cond, then_body, else_body } => {
+                self.emit_expr(cond)?;
+                self.open(&[0x04, 0x40]); // if (no result)
+                self.emit_block(then_body)?;
+                if let Some(else_body) = else_body {
+                    self.code.push(0x05); // else
+                    self.emit_block(else_body)?;
+                }
+                self.close();
+            }
+            // block $break { loop $continue { br_if $break (!cond); body; br $continue } }
+            StmtKind::While { cond, body } => {
+                self.open(&[0x02, 0x40]); // block
+                let brk = self.depth;
+                self.open(&[0x03, 0x40]); // loop
+                self.loops.push((brk, self.depth));
+                self.emit_expr(cond)?;
+                self.code.push(0x45); // i32.eqz
+                self.branch(0x0D, brk); // br_if $break
+                self.emit_block(body)?;
+                self.branch(0x0C, brk + 1); // br $continue
+                self.loops.pop();
+                self.close();
+                self.close();
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let &(brk, cont) = self.loops.last().ok_or("`break`/`continue` outside of a loop")?;
+                self.branch(0x0C, if matches!(stmt.kind, StmtKind::Break) { brk } else { cont });
+            }
+            StmtKind::Return(expr) => {
+                if let Some(expr) = expr {
+                    let ret = self.ret.clone();
+                    self.emit_expr_as(expr, &ret)?;
+                }
+                self.code.push(0x0F); // return
+            }
+            StmtKind::Emit { event: name, args } | StmtKind::Revert { error: name, args } => {
+                let error = matches!(stmt.kind, StmtKind::Revert { .. });
+                let decl = self.contract.and_then(|c| c.event(name.as_str(), error))
+                    .ok_or_else(|| format!("`{}` is not declared in the enclosing contract", name))?;
+                let sig = abi::canonical(decl.name.as_str(), &decl.fields);
+                self.i64_const(if error { abi::selector(&sig) as i64 } else { abi::topic(&sig) });
+                self.emit_words(decl, args)?;
+                self.call_host(if error { &host::REVERT } else { &host::EMIT_EVENT });
+                if error { self.code.push(0x00); } // unreachable: `revert` never returns
+            }
+            StmtKind::Expr(expr) => {
+                let produces_value = self.type_of(expr)? != Type::Void;
+                self.emit_expr(expr)?;
+                if produces_value { self.code.push(0x1A); } // drop
+            }
+            StmtKind::Function { name, .. } | StmtKind::Contract { name, .. } =>
+                return Err(format!("Nested declaration `{}` is not supported", name)),
+            StmtKind::Error => return Err("the function contains syntax errors".into()),
+        }
+        Ok(())
+    }
+
+    /// Store the fields of an event or error in freshly allocated memory as
+    /// 64-bit words and push the packed `(ptr << 32) | len`, see `host::words`.
+    fn emit_words(&mut self, decl: &EventDecl, args: &[Expr]) -> Result<(), String> {
+        if args.is_empty() {
+            self.i64_const(0);
+            return Ok(());
+        }
+        let len = args.len() as u32 * 8;
+        self.code.push(0x41); // i32.const
+        write_sleb(len as i64, &mut self.code);
+        self.call_helper(Helper::Alloc);
+        // `$` cannot start an identifier, so the name never clashes with the program's.
+        let ptr = self.declare("$words", ValType::I32);
+        self.local_set(ptr);
+        for (i, (arg, field)) in args.iter().zip(&decl.fields).enumerate() {
+            let ty = type_from_name(field.ty.as_ref().map_or("", |t| t.as_str()));
+            self.local_get(ptr);
+            self.emit_expr_as(arg, &ty)?;
+            match ty {
+                Type::I64 => {}
+                Type::F64 => self.code.push(0xBD), // i64.reinterpret_f64
+                Type::Bool => self.code.push(0xAD), // i64.extend_i32_u
+                _ => self.code.push(0xAC), // i64.extend_i32_s
+            }
+            self.code.extend_from_slice(&[0x37, 0x03]); // i64.store align=8
+            write_uleb(i as u32 * 8, &mut self.code);
+        }
+        self.pack(ptr);
+        self.i64_const(len as i64);
+        self.code.push(0x84); // i64.or
+        Ok(())
+    }
+
+    /// Push the pointer in local `ptr` shifted into the high half of a packed
+    /// `(ptr << 32) | len`, for the length to be or-ed in.
+    fn pack(&mut self, ptr: u32) {
+        self.local_get(ptr);
+        self.code.push(0xAD); // i64.extend_i32_u
+        self.i64_const(32);
+        self.code.push(0x86); // i64.shl
+    }
+
+    fn i32_const(&mut self, v: i32) {
+        self.code.push(0x41); // i32.const
+        write_sleb(v as i64, &mut self.code);
+    }
+
+    /// Revert with one of the errors `call` reports itself, passing the i64
+    /// in local `word` if it takes one.
+    fn revert_with(&mut self, canonical: &str, word: Option<u32>) {
+        self.i64_const(abi::selector(canonical) as i64);
+        match word {
+            Some(word) => {
+                self.i32_const(8);
+                self.call_helper(Helper::Alloc);
+                let ptr = self.declare("$words", ValType::I32);
+                self.local_set(ptr);
+                self.local_get(ptr);
+                self.local_get(word);
+                self.code.extend_from_slice(&[0x37, 0x03, 0x00]); // i64.store align=8
+                self.pack(ptr);
+                self.i64_const(8);
+                self.code.push(0x84); // i64.or
+            }
+            None => self.i64_const(0),
+        }
+        self.call_host(&host::REVERT);
+        self.code.push(0x00); // unreachable
+    }
+
+    /// Revert with `InvalidCalldata()` unless at least `n` bytes of calldata
+    /// lie between locals `pos` and `end`.
+    fn require_calldata(&mut self, pos: u32, end: u32, n: i32) {
+        self.local_get(end);
+        self.local_get(pos);
+        self.code.push(0x6B); // i32.sub
+        self.i32_const(n);
+        self.code.push(0x49); // i32.lt_u
+        self.open(&[0x04, 0x40]); // if (no result)
+        self.revert_with(abi::INVALID_CALLDATA, None);
+        self.close();
+    }
+
+    /// Advance local `pos` by the i32 on the stack.
+    fn advance(&mut self, pos: u32) {
+        self.local_get(pos);
+        self.code.push(0x6A); // i32.add
+        self.local_set(pos);
+    }
+
+    /// The body of `call`: decode the selector and arguments from the
+    /// calldata, call the matching function and pass its encoded result to
+    /// `return_data`. Every failure reverts as `abi` describes.
+    fn emit_dispatcher(&mut self, routes: &[Route]) {
+        // buf = alloc(size); calldata_copy(buf); pos = buf + 4; end = buf + size
+        self.call_host(&host::CALLDATA_SIZE);
+        self.code.push(0xA7); // i32.wrap_i64
+        let size = self.declare("$size", ValType::I32);
+        self.local_set(size);
+        self.local_get(size);
+        self.call_helper(Helper::Alloc);
+        let buf = self.declare("$buf", ValType::I32);
+        self.local_set(buf);
+        self.local_get(buf);
+        self.code.push(0xAD); // i64.extend_i32_u
+        self.call_host(&host::CALLDATA_COPY);
+        let (pos, end) = (self.declare("$pos", ValType::I32), self.declare("$end", ValType::I32));
+        self.local_get(buf);
+        self.local_set(pos);
+        self.local_get(buf);
+        self.local_get(size);
+        self.code.push(0x6A); // i32.add
+        self.local_set(end);
+        self.require_calldata(pos, end, 4);
+
+        // The selector is big-endian.
+        for i in 0..4 {
+            self.local_get(buf);
+            self.code.extend_from_slice(&[0x2D, 0x00, i]); // i32.load8_u
+            if i > 0 {
+                self.code.push(0x72); // i32.or
+            }
+            if i < 3 {
+                self.i32_const(8);
+                self.code.push(0x74); // i32.shl
+            }
+        }
+        let sel = self.declare("$selector", ValType::I32);
+        self.local_set(sel);
+        self.i32_const(4);
+        self.advance(pos);
+
+        for route in routes {
+            self.local_get(sel);
+            self.i32_const(route.selector as i32);
+            self.code.push(0x46); // i32.eq
+            self.open(&[0x04, 0x40]); // if (no result)
+            if !route.payable {
+                self.call_host(&host::CALL_VALUE);
+                let value = self.declare("$value", ValType::I64);
+                self.local_set(value);
+                self.local_get(value);
+                self.code.push(0x50); // i64.eqz
+                self.code.push(0x45); // i32.eqz
+                self.open(&[0x04, 0x40]); // if (no result)
+                self.revert_with(abi::NOT_PAYABLE, Some(value));
+                self.close();
+            }
+            let mut args = Vec::new();
+            for ty in &route.params {
+                self.require_calldata(pos, end, 8);
+                self.local_get(pos);
+                self.code.extend_from_slice(&[0x29, 0x03, 0x00]); // i64.load align=8
+                let vt = lower(ty).expect("parameters hold values");
+                let arg = self.declare("$arg", vt);
+                match ty {
+                    Type::I64 => {}
+                    Type::F64 => self.code.push(0xBF), // f64.reinterpret_i64
+                    Type::Bool => {
+                        self.i64_const(0);
+                        self.code.push(0x52); // i64.ne
+                    }
+                    Type::String => {
+                        // The length word, then that many bytes: the argument points into the calldata.
+                        self.local_set(arg);
+                        self.i32_const(8);
+                        self.advance(pos);
+                        self.local_get(arg);
+                        self.local_get(end);
+                        self.local_get(pos);
+                        self.code.push(0x6B); // i32.sub
+                        self.code.push(0xAD); // i64.extend_i32_u
+                        self.code.push(0x56); // i64.gt_u
+                        self.open(&[0x04, 0x40]); // if (no result)
+                        self.revert_with(abi::INVALID_CALLDATA, None);
+                        self.close();
+                        self.pack(pos);
+                        self.local_get(arg);
+                        self.code.push(0x84); // i64.or
+                        self.local_set(arg);
+                        self.local_get(arg);
+                        self.code.push(0xA7); // i32.wrap_i64
+                        self.advance(pos);
+                        args.push(arg);
+                        continue;
+                    }
+                    _ => self.code.push(0xA7), // i32.wrap_i64
+                }
+                self.local_set(arg);
+                self.i32_const(8);
+                self.advance(pos);
+                args.push(arg);
+            }
+            self.local_get(pos);
+            self.local_get(end);
+            self.code.push(0x47); // i32.ne
+            self.open(&[0x04, 0x40]); // if (no result)
+            self.revert_with(abi::INVALID_CALLDATA, None);
+            self.close();
+
+            for &arg in &args { self.local_get(arg); }
+            self.call(route.index);
+            match &route.ret {
+                Type::Void => self.i64_const(0),
+                Type::String => {
+                    // out = alloc(8 + len); out[0..8] = len; copy the bytes after it.
+                    let result = self.declare("$result", ValType::I64);
+                    self.local_set(result);
+                    self.local_get(result);
+                    self.code.push(0xA7); // i32.wrap_i64
+                    self.i32_const(8);
+                    self.code.push(0x6A); // i32.add
+                    self.call_helper(Helper::Alloc);
+                    let out = self.declare("$out", ValType::I32);
+                    self.local_set(out);
+                    self.local_get(out);
+                    self.local_get(result);
+                    self.i64_const(0xFFFF_FFFF);
+                    self.code.push(0x83); // i64.and
+                    self.code.extend_from_slice(&[0x37, 0x03, 0x00]); // i64.store align=8
+                    self.local_get(out);
+                    self.i32_const(8);
+                    self.code.push(0x6A); // i32.add
+                    self.local_get(result);
+                    self.i64_const(32);
+                    self.code.push(0x88); // i64.shr_u
+                    self.code.push(0xA7); // i32.wrap_i64
+                    self.local_get(result);
+                    self.code.push(0xA7); // i32.wrap_i64
+                    self.code.extend_from_slice(&[0xFC, 0x0A, 0x00, 0x00]); // memory.copy
+                    self.pack(out);
+                    self.local_get(result);
+                    self.code.push(0xA7); // i32.wrap_i64
+                    self.i32_const(8);
+                    self.code.push(0x6A); // i32.add
+                    self.code.push(0xAD); // i64.extend_i32_u
+                    self.code.push(0x84); // i64.or
+                }
+                ty => {
+                    match ty {
+                        Type::I64 => {}
+                        Type::F64 => self.code.push(0xBD), // i64.reinterpret_f64
+                        Type::Bool => self.code.push(0xAD), // i64.extend_i32_u
+                        _ => self.code.push(0xAC), // i64.extend_i32_s
+                    }
+                    let result = self.declare("$result", ValType::I64);
+                    self.local_set(result);
+                    self.i32_const(8);
+                    self.call_helper(Helper::Alloc);
+                    let out = self.declare("$out", ValType::I32);
+                    self.local_set(out);
+                    self.local_get(out);
+                    self.local_get(result);
+                    self.code.extend_from_slice(&[0x37, 0x03, 0x00]); // i64.store align=8
+                    self.pack(out);
+                    self.i64_const(8);
+                    self.code.push(0x84); // i64.or
+                }
+            }
+            self.call_host(&host::RETURN_DATA);
+            self.code.push(0x0F); // return
+            self.close();
+        }
+        self.local_get(sel);
+        self.code.push(0xAD); // i64.extend_i32_u
+        let word = self.declare("$selector_word", ValType::I64);
+        self.local_set(word);
+        self.revert_with(abi::UNKNOWN_SELECTOR, Some(word));
+    }
+
+    fn emit_expr(&mut self, e: &Expr) -> Result<(), String> {
+        // Covers negated literals too, so `-2147483648` is a single `i32.const`.
+        if let Some(v) = int_literal(e) {
+            let ty = self.type_of(e)?;
+            self.emit_int(v, &ty)?;
+            return Ok(());
+        }
+        match &e.kind {
+            ExprKind::Int(_) => unreachable!("integer literals are handled above"),
+            ExprKind::Float(n) => self.f64_const(*n),
+            ExprKind::Bool(b) => self.code.extend_from_slice(&[0x41, *b as u8]), // i32.const 0|1
+            ExprKind::StringLiteral(s) => {
+                let packed = self.linker.strings.borrow_mut().intern(s);
+                self.i64_const(packed);
+            }
+            ExprKind::Ident(id) => {
+                let place = self.lookup(id)?;
+                let ty = self.type_of(e)?;
+                self.load(place, &ty);
+            }
+            ExprKind::Call { callee, args } if callee == "len" && !self.funcs.contains_key("len") => {
+                self.emit_expr(&args[0])?;
+                self.code.push(0xA7); // i32.wrap_i64: the low half is the length
+            }
+            ExprKind::Call { callee, args } if ASSERTIONS.contains(&callee.as_str()) && !self.funcs.contains_key(callee.as_str()) => {
+                self.emit_expr(&assertion_condition(callee, args, e.span)?)?;
+                self.code.push(0x45); // i32.eqz
+                self.open(&[0x04, 0x40]); // if (no result)
+                if self.linker.tests {
+                    self.i64_const(((e.span.start as i64) << 32) | e.span.end as i64);
+                    self.call_host(&host::ASSERT_FAILED);
+                }
+                self.code.push(0x00); // unreachable
+                self.close();
+            }
+            ExprKind::Call { callee, args } => {
+                let info = self.funcs.get(callee.as_str()).ok_or_else(|| format!("Unknown function `{}`", callee))?;
+                for (arg, ty) in args.iter().zip(&info.params) { self.emit_expr_as(arg, ty)?; }
+                self.call(info.index);
+            }
+            ExprKind::Member { object, field } => {
+                let builtin = resolve_builtin(object, field.as_str(), |n| self.scopes.iter().any(|s| s.contains_key(n)))?;
+                self.call_host(builtin.host);
+            }
+            ExprKind::Unary { op: UnaryOp::Neg, expr } => match self.type_of(expr)? {
+                Type::F64 => {
+                    self.emit_expr(expr)?;
+                    self.code.push(0x9A); // f64.neg
+                }
+                Type::I64 => {
+                    self.i64_const(0);
+                    self.emit_expr(expr)?;
+                    self.code.push(0x7D); // i64.sub
+                }
+                _ => {
+                    self.code.extend_from_slice(&[0x41, 0x00]); // i32.const 0
+                    self.emit_expr(expr)?;
+                    self.code.push(0x6B); // i32.sub
+                }
+            },
+            ExprKind::Unary { op: UnaryOp::Not, expr } => {
+                self.emit_expr(expr)?;
+                self.code.push(0x45); // i32.eqz
+            }
+            ExprKind::Binary { op, left, right } => {
+                let ty = self.type_of(left)?;
+                self.emit_expr(left)?;
+                self.emit_binary(*op, &ty, right)?;
+            }
+            ExprKind::Cast { expr, ty } => self.emit_expr_as(expr, &type_from_name(ty.as_str()))?,
+        }
+        Ok(())
+    }
+
+    /// Emit `<lhs of type lhs_ty already on stack> op right`.
+    fn emit_binary(&mut self, op: BinOp, lhs_ty: &Type, right: &Expr) -> Result<(), String> {
+        match op {
+            // Short-circuit: `a && b` is `if a { b } else { 0 }`, `a || b` is `if a { 1 } else { b }`.
+            BinOp::And => {
+                self.code.extend_from_slice(&[0x04, 0x7F]); // if (result i32)
+                self.emit_expr(right)?;
+                self.code.extend_from_slice(&[0x05, 0x41, 0x00, 0x0B]); // else i32.const 0 end
+            }
+            BinOp::Or => {
+                self.code.extend_from_slice(&[0x04, 0x7F, 0x41, 0x01, 0x05]); // if (result i32) i32.const 1 else
+                self.emit_expr(right)?;
+                self.code.push(0x0B); // end
+            }
+            BinOp::Add if *lhs_ty == Type::String => {
+                self.emit_expr(right)?;
+                self.call_helper(Helper::Concat);
+            }
+            BinOp::Eq | BinOp::Ne if *lhs_ty == Type::String => {
+                self.emit_expr(right)?;
+                self.call_helper(Helper::StrEq);
+                if op == BinOp::Ne { self.code.push(0x45); } // i32.eqz
+            }
+            _ => {
+                let rhs_ty = self.type_of(right)?;
+                let ty = common_type(lhs_ty, &rhs_ty).ok_or_else(|| format!(
+                    "operator `{}` cannot be applied to `{}` and `{}`",
+                    op.symbol(), type_name(lhs_ty), type_name(&rhs_ty)
+                ))?;
+                self.convert(lhs_ty, &ty)?;
+                self.emit_expr_as(right, &ty)?;
+                let opcode = lower(&ty).and_then(|vt| binop_opcode(op, vt))
+                    .ok_or_else(|| format!("operator `{}` is not supported on `{}`", op.symbol(), type_name(&ty)))?;
+                self.code.push(opcode);
+            }
+        }
+        Ok(())
+    }
+
+    /// Assemble the function body: local declarations followed by the code.
+    fn finish(mut self, returns_value: bool) -> Vec<u8> {
+        // Falling off the end of a value-returning function is rejected by the
+        // type checker; `unreachable` keeps the validator happy about the stack.
+        if returns_value { self.code.push(0x00); }
+        self.code.push(0x0B); // end
+
+        // Locals are declared as runs of the same type.
+        let mut groups: Vec<(u32, ValType)> = Vec::new();
+        for &ty in &self.locals {
+            match groups.last_mut() {
+                Some((n, last)) if *last == ty => *n += 1,
+                _ => groups.push((1, ty)),
+            }
+        }
+        let mut body = Vec::new();
+        write_uleb(groups.len() as u32, &mut body);
+        for (n, ty) in groups {
+            write_uleb(n, &mut body);
+            body.push(ty.byte());
+        }
+        body.extend_from_slice(&self.code);
+        body
+    }
+}
+
+/// What call sites need to know about a compiled function.
+struct FnInfo {
+    index: u32,
+    params: Vec<Type>,
+}
+
+/// A function `call` dispatches to.
+struct Route {
+    selector: u32,
+    index: u32,
+    params: Vec<Type>,
+    ret: Type,
+    payable: bool,
+}
+
+/// Compile every function in the program into one module, exporting each under
+/// its Mintora name. Functions may call each other (including recursively), and
+/// contract code reaches storage and chain built-ins through imported host
+/// functions; only the host functions and runtime helpers a module actually
+/// uses are linked in. Strings live in an exported linear memory. `#[test]`
+/// functions are left out, and the `mintora.abi` custom section holds the
+/// SHA-256 of the program's ABI.
+pub fn compile_to_wasm(program: &Program) -> Result<Vec<u8>, Diagnostic> {
+    compile(program, false, false)
+}
+
+/// Like `compile_to_wasm`, but also exporting the `call` entry point through
+/// which a chain calls the functions with ABI-encoded calldata, see `abi`.
+pub fn compile_with_dispatcher(program: &Program) -> Result<Vec<u8>, Diagnostic> {
+    compile(program, false, true)
+}
+
+/// Like `compile_to_wasm`, but exporting the `#[test]` functions too, for the test runner.
+pub fn compile_tests(program: &Program) -> Result<Vec<u8>, Diagnostic> {
+    compile(program, true, false)
+}
+
+fn compile(program: &Program, tests: bool, dispatcher: bool) -> Result<Vec<u8>, Diagnostic> {
+    let items = function_items(program)?;
+    if items.is_empty() {
+        return Err(Diagnostic::error(codes::CODEGEN, "No functions to compile. Expected e.g. `fn <name>(a: i32, b: i32) -> i32 { return a + b; }`"));
+    }
+    let sigs = collect_signatures(&items)?;
+
+    let mut storage = HashMap::new();
+    for stmt in &program.statements {
+        let StmtKind::Contract { name: contract, fields, .. } = &stmt.kind else { continue };
+        let mut slots = HashMap::new();
+        for f in fields {
+            if !matches!(type_from_name(f.ty.as_str()), Type::I32 | Type::I64 | Type::F64 | Type::Bool) {
+                return Err(cannot(f.span, format!(
+                    "Cannot compile contract `{}`: storage field `{}` of type `{}` is not supported by codegen yet",
+                    contract, f.name, f.ty
+                )));
+            }
+            slots.insert(f.name.to_string(), storage_key(contract.as_str(), f.name.as_str()));
+        }
+        storage.insert(contract.as_str(), slots);
+    }
+
+    // Probe pass: learn which host functions, helpers and literals are needed before fixing indices.
+    let probe = Linker { tests, dispatcher, ..Linker::default() };
+    lower_functions(&items, &sigs, &storage, &probe, &mut ModuleBuilder::new())?;
+
+    let mut module = ModuleBuilder::new();
+    let linker = Linker::link(&mut module, &probe);
+    lower_functions(&items, &sigs, &storage, &linker, &mut module)?;
+    if !tests { module.add_custom_section(abi::SECTION, abi::abi(program).digest().to_vec()); }
+    Ok(module.finish())
+}
+
+/// A codegen error located at `span`.
+fn cannot(span: Span, message: String) -> Diagnostic {
+    Diagnostic::error(codes::CODEGEN, message).with_primary(span, "")
+}
+
+fn lower_functions(
+    items: &[FnItem],
+    sigs: &HashMap<String, Signature>,
+    storage: &HashMap<&str, HashMap<String, i64>>,
+    linker: &Linker,
+    module: &mut ModuleBuilder,
+) -> Result<(), Diagnostic> {
+    let mut funcs = HashMap::new();
+    let mut defs = Vec::new();
+
+    // Pass 1: assign indices so bodies can call functions defined later.
+    for f in items.iter().filter(|f| linker.tests || !f.is_test()) {
+        let name = f.name.as_str();
+        let cannot_fn = |span, why: String| cannot(span, format!("Cannot compile function `{}`: {}", name, why));
+        let mut param_types = Vec::new();
+        for p in f.params {
+            let ty = lower_annotation(p.ty.as_ref().map(|t| t.as_str()))
+                .map_err(|e| cannot_fn(p.span, format!("parameter `{}`: {}", p.name, e)))?
+                .ok_or_else(|| cannot_fn(p.span, format!("parameter `{}` cannot be `void`", p.name)))?;
+            param_types.push(ty);
+        }
+        let result = match f.return_type {
+            Some(t) => lower_annotation(Some(t.as_str())).map_err(|e| cannot_fn(t.span, format!("return {}", e)))?,
+            None => None,
+        };
+        let index = module.declare_function(FuncType { params: param_types, results: result.into_iter().collect() });
+        let params = sigs[name].params.clone();
+        if funcs.insert(name.to_string(), FnInfo { index, params }).is_some() {
+            return Err(cannot(f.name.span, format!("Function `{}` is defined more than once", name)));
+        }
+        module.export_function(name, index);
+        defs.push((f, index, result.is_some()));
+    }
+
+    let mut routes: Vec<Route> = Vec::new();
+    let entry = if linker.dispatcher {
+        for (f, index, _) in &defs {
+            let canonical = abi::canonical(f.name.as_str(), f.params);
+            let selector = abi::selector(&canonical);
+            let sig = &sigs[f.name.as_str()];
+            routes.push(Route {
+                selector,
+                index: *index,
+                params: sig.params.iter().cloned().map(concrete).collect(),
+                ret: concrete(sig.ret.clone()),
+                payable: f.attribute("payable").is_some(),
+            });
+        }
+        if let Some(f) = items.iter().find(|f| f.name.as_str() == "call") {
+            return Err(cannot(f.name.span, "Function `call` clashes with the dispatcher's entry point".into()));
+        }
+        let index = module.declare_function(FuncType { params: Vec::new(), results: Vec::new() });
+        module.export_function("call", index);
+        Some(index)
+    } else {
+        None
+    };
+
+    // Pass 2: emit one body per function.
+    for (f, index, returns_value) in defs {
+        let fields = f.contract.map_or(&[][..], |c| c.fields);
+        let slots = f.contract.and_then(|c| storage.get(c.name.as_str()));
+        let env = Env::new(fields, f.params, sigs);
+        let ret = sigs[f.name.as_str()].ret.clone();
+        let mut emitter = FnEmitter::new(env, f.params, &funcs, linker, ret, slots, f.contract);
+        emitter.emit_block(f.body).map_err(|e| cannot(f.name.span, format!("Cannot compile function `{}`: {}", f.name, e)))?;
+        module.set_body(index, emitter.finish(returns_value));
+    }
+    if let Some(index) = entry {
+        let mut emitter = FnEmitter::new(Env::new(&[], &[], sigs), &[], &funcs, linker, Type::Void, None, None);
+        emitter.emit_dispatcher(&routes);
+        module.set_body(index, emitter.finish(false));
+    }
+    Ok(())
+}
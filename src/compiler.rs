@@ -1,273 +1,701 @@
-use std::collections::HashMap;
-
-use crate::ast::{Expr, Program, Statement, Param};
-
-/// Minimal type model just to get basic checks working.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Type {
-    I32,
-    F64,
-    String,
-    Void,
-    Unknown,
-}
-
-fn type_from_name(name: &str) -> Type {
-    match name {
-        "i32" => Type::I32,
-        "f64" => Type::F64,
-        "string" => Type::String,
-        "void" => Type::Void,
-        _ => Type::Unknown,
-    }
-}
-
-fn type_name(ty: &Type) -> &'static str {
-    match ty {
-        Type::I32 => "i32",
-        Type::F64 => "f64",
-        Type::String => "string",
-        Type::Void => "void",
-        Type::Unknown => "unknown",
-    }
-}
-
-/// Infer an expression's type from literals and a simple environment (params, locals).
-fn infer_expr_type(expr: &Expr, env: &HashMap<String, Type>) -> Type {
-    match expr {
-        Expr::Number(n) => {
-            if n.fract() == 0.0 { Type::I32 } else { Type::F64 }
-        }
-        Expr::StringLiteral(_) => Type::String,
-        Expr::Ident(name) => env.get(name).cloned().unwrap_or(Type::Unknown),
-        Expr::Binary { op, left, right } => {
-            // for now we only model integer add
-            let lt = infer_expr_type(left, env);
-            let rt = infer_expr_type(right, env);
-            match op {
-                BinOp::Add => {
-                    if lt == Type::I32 && rt == Type::I32 { Type::I32 } else { Type::Unknown }
-                }
-            }
-        }
-    }
-}
-
-/// Build a simple symbol table from parameters (uses declared types when present).
-fn build_env(params: &[Param]) -> HashMap<String, Type> {
-    let mut env = HashMap::<String, Type>::new();
-    for p in params {
-        let ty = p.ty.as_deref().map(type_from_name).unwrap_or(Type::Unknown);
-        env.insert(p.name.clone(), ty);
-    }
-    env
-}
-
-/// Type-check the program: ensure `return` expressions match the declared return type (if any).
-pub fn type_check(program: &Program) -> Result<(), String> {
-    for stmt in &program.statements {
-        if let Statement::Function { name, params, return_type, body } = stmt {
-            let env = build_env(params);
-            let expected = return_type
-                .as_ref()
-                .map(|s| type_from_name(s))
-                .unwrap_or(Type::Void);
-
-            for s in body {
-                if let Statement::Return(expr) = s {
-                    let got = infer_expr_type(expr, &env);
-                    if expected != Type::Unknown && expected != Type::Void &&
-                       got != Type::Unknown && got != expected {
-                        return Err(format!(
-                            "Type error in function `{}`: expected `{}` but found `{}`",
-                            name, type_name(&expected), type_name(&got)
-                        ));
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
-/// Pretty-print the AST to a developer-friendly string (great for debugging).
-pub fn pretty(program: &Program) -> String {
-    let mut out = String::new();
-    for stmt in &program.statements {
-        match stmt {
-            Statement::Function { name, params, return_type, body } => {
-                out.push_str(&format!("fn {}(", name));
-                for (i, p) in params.iter().enumerate() {
-                    if i > 0 { out.push_str(", "); }
-                    if let Some(t) = &p.ty { out.push_str(&format!("{}: {}", p.name, t)); }
-                    else { out.push_str(&p.name); }
-                }
-                out.push(')');
-                if let Some(ret) = return_type {
-                    out.push_str(&format!(" -> {}", ret));
-                }
-                out.push_str(" {\n");
-                for inner in body {
-                    match inner {
-                        Statement::Return(expr) => {
-                            out.push_str("  return ");
-                            match expr {
-                                Expr::Number(n) => out.push_str(&format!("{}", n)),
-                                Expr::StringLiteral(s) => out.push_str(&format!("\"{}\"", s)),
-                                Expr::Ident(id) => out.push_str(id),
-                            }
-                            out.push_str(";\n");
-                        }
-                        Statement::Expr(e) => {
-                            out.push_str("  ");
-                            match e {
-                                Expr::Number(n) => out.push_str(&format!("{}", n)),
-                                Expr::StringLiteral(s) => out.push_str(&format!("\"{}\"", s)),
-                                Expr::Ident(id) => out.push_str(id),
-                            }
-                            out.push_str(";\n");
-                        }
-                    }
-                }
-                out.push_str("}\n\n");
-            }
-            _ => {}
-        }
-    }
-    out
-}
-
-// --------------------- WASM helpers ---------------------
-
-fn write_uleb(mut v: u32, out: &mut Vec<u8>) {
-    loop {
-        let mut b = (v & 0x7F) as u8;
-        v >>= 7;
-        if v != 0 { b |= 0x80; }
-        out.push(b);
-        if v == 0 { break; }
-    }
-}
-
-fn section(id: u8, content: Vec<u8>, out: &mut Vec<u8>) {
-    out.push(id);
-    write_uleb(content.len() as u32, out);
-    out.extend_from_slice(&content);
-}
-
-fn emit_expr_i32(e: &Expr, param_names: &[String], body: &mut Vec<u8>) -> Result<(), String> {
-    match e {
-        Expr::Number(n) if n.fract() == 0.0 && *n >= 0.0 => {
-            body.push(0x41); // i32.const
-            write_uleb(*n as u32, body);
-            Ok(())
-        }
-        Expr::Ident(id) => {
-            if let Some(idx) = param_names.iter().position(|p| p == id) {
-                body.push(0x20); // local.get
-                write_uleb(idx as u32, body);
-                Ok(())
-            } else {
-                Err(format!("Unknown identifier `{}`", id))
-            }
-        }
-        Expr::Binary { op: BinOp::Add, left, right } => {
-            emit_expr_i32(left, param_names, body)?;
-            emit_expr_i32(right, param_names, body)?;
-            body.push(0x6A); // i32.add
-            Ok(())
-        }
-        _ => Err("Unsupported expression in codegen".into()),
-    }
-}
-            
-/// Compile a single exported function where:
-///   - return type is `i32`
-///   - params are all `i32` (or unspecified; treated as i32 for now)
-///   - body is exactly `return <paramIdent>` or `return <int literal>`
-/// Exports the function under its Mintora name.
-pub fn compile_to_wasm(program: &Program) -> Result<Vec<u8>, String> {
-    // We'll compile the first function that returns an i32 with a single `return <expr>;`
-
-    let mut export_name: Option<String> = None;
-    let mut param_names: Vec<String> = Vec::new();
-    let mut ret_expr: Option<Expr> = None;
-
-    'search: for stmt in &program.statements {
-        if let Statement::Function { name, params, return_type, body } = stmt {
-            if return_type.as_deref() != Some("i32") { continue; }
-            if body.len() != 1 { continue; }
-
-            // All params must be i32 (or unspecified -> accept as i32 for now)
-            let all_i32 = params.iter().all(|p|
-                p.ty.as_deref().map(|t| t == "i32").unwrap_or(true)
-            );
-            if !all_i32 { continue; }
-
-            // Grab the return expression (clone into our owned AST)
-            if let Statement::Return(expr) = &body[0] {
-                export_name = Some(name.clone());
-                param_names = params.iter().map(|p| p.name.clone()).collect();
-                ret_expr = Some(expr.clone());
-            } else {
-                continue;
-            }
-            break 'search;
-        }
-    }
-
-    let export = export_name.ok_or_else(|| {
-        "No suitable function found. Expected e.g. `fn <name>(a: i32, b: i32) -> i32 { return a + b; }`".to_string()
-    })?;
-    let ret_src = ret_src.unwrap();
-    let param_count = param_names.len();
-
-    // ========= Emit WASM =========
-    let mut out = Vec::new();
-    // header
-    out.extend_from_slice(&[0x00, 0x61, 0x73, 0x6D]); // \0asm
-    out.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]); // version 1
-
-    // -- Type section (id=1): one func type (param_count × i32) -> i32
-    let mut ty = Vec::new();
-    write_uleb(1, &mut ty);      // count
-    ty.push(0x60);               // func type
-    write_uleb(param_count as u32, &mut ty);
-    for _ in 0..param_count { ty.push(0x7F); } // i32 params
-    write_uleb(1, &mut ty);      // results = 1
-    ty.push(0x7F);               // i32
-    section(1, ty, &mut out);
-
-    // -- Function section (id=3): one function that uses type 0
-    let mut func = Vec::new();
-    write_uleb(1, &mut func);    // count
-    write_uleb(0, &mut func);    // type index 0
-    section(3, func, &mut out);
-
-    // -- Export section (id=7): export func 0 with Mintora name
-    let mut exp = Vec::new();
-    write_uleb(1, &mut exp);                         // count
-    let name_bytes = export.as_bytes();
-    write_uleb(name_bytes.len() as u32, &mut exp);   // name len
-    exp.extend_from_slice(name_bytes);
-    exp.push(0x00);                                  // kind = func
-    write_uleb(0, &mut exp);                         // func index
-    section(7, exp, &mut out);
-
-    // -- Code section (id=10): function body
-    let mut body = Vec::new();
-    body.push(0x00);              // local decls = 0
-
-    emit_expr_i32(&ret_expr, &param_bames, &mut body)?;
-    body.push(0x0B);              // end
-
-    let mut code = Vec::new();
-    write_uleb(1, &mut code);     // bodies = 1
-    write_uleb(body.len() as u32, &mut code);
-    code.extend_from_slice(&body);
-    section(10, code, &mut out);
-
-    Ok(out)
-}
-
-
+use std::collections::HashMap;
+
+use crate::abi;
+use crate::ast::{Attribute, BinOp, EventDecl, Expr, ExprKind, Ident, Param, Program, Span, Statement, StmtKind, StorageField, UnaryOp};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::host::{self, Builtin};
+
+/// Minimal type model just to get basic checks working.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    I32,
+    I64,
+    F64,
+    Bool,
+    String,
+    Void,
+    Unknown,
+}
+
+/// The types a program can name; `type_from_name` knows each of them.
+pub const TYPE_NAMES: &[&str] = &["i32", "i64", "f64", "bool", "string", "void"];
+
+pub(crate) fn type_from_name(name: &str) -> Type {
+    match name {
+        "i32" => Type::I32,
+        "i64" => Type::I64,
+        "f64" => Type::F64,
+        "bool" => Type::Bool,
+        "string" => Type::String,
+        "void" => Type::Void,
+        _ => Type::Unknown,
+    }
+}
+
+pub(crate) fn type_name(ty: &Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F64 => "f64",
+        Type::Bool => "bool",
+        Type::String => "string",
+        Type::Void => "void",
+        Type::Unknown => "unknown",
+    }
+}
+
+/// Value of an integer literal, including a negated one such as `-128`.
+pub(crate) fn int_literal(expr: &Expr) -> Option<i128> {
+    match &expr.kind {
+        ExprKind::Int(n) => Some(*n as i128),
+        ExprKind::Unary { op: UnaryOp::Neg, expr } => match expr.kind {
+            ExprKind::Int(n) => Some(-(n as i128)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn literal_fits(v: i128, ty: &Type) -> bool {
+    match ty {
+        Type::I32 => i32::try_from(v).is_ok(),
+        Type::I64 => i64::try_from(v).is_ok(),
+        _ => true,
+    }
+}
+
+type CResult<T> = Result<T, Diagnostic>;
+
+/// A type error located at `span`.
+fn type_error(span: Span, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::error(codes::TYPE, message).with_primary(span, "")
+}
+
+/// Infer an expression's type from literals and a simple environment (params, locals).
+/// `Unknown` operands (e.g. untyped params) are accepted leniently and poison the result.
+/// Integer literals are `i32` unless their value only fits in `i64`.
+pub(crate) fn infer_expr_type(expr: &Expr, env: &Env) -> CResult<Type> {
+    if let Some(v) = int_literal(expr) {
+        return [Type::I32, Type::I64].into_iter().find(|t| literal_fits(v, t))
+            .ok_or_else(|| type_error(expr.span, format!("Integer literal `{}` is out of range for `i64`", v)));
+    }
+    Ok(match &expr.kind {
+        ExprKind::Int(_) => Type::I32,
+        ExprKind::Float(_) => Type::F64,
+        ExprKind::Bool(_) => Type::Bool,
+        ExprKind::StringLiteral(_) => Type::String,
+        ExprKind::Ident(name) => match env.lookup(name) {
+            Some(local) => local.ty.clone(),
+            None => return Err(type_error(expr.span, format!("Unknown identifier `{}`", name))),
+        },
+        ExprKind::Call { callee, args } if callee == "len" && !env.fns.contains_key("len") => {
+            let got = match args.as_slice() {
+                [arg] => infer_expr_type(arg, env)?,
+                _ => return Err(type_error(expr.span, format!("Built-in `len` takes 1 argument but {} were supplied", args.len()))),
+            };
+            if !compatible(&Type::String, &got) {
+                return Err(type_error(args[0].span, format!("Built-in `len` expects `string` but found `{}`", type_name(&got))));
+            }
+            Type::I32
+        }
+        ExprKind::Call { callee, args } if ASSERTIONS.contains(&callee.as_str()) && !env.fns.contains_key(callee.as_str()) => {
+            let cond = assertion_condition(callee, args, expr.span).map_err(|e| type_error(expr.span, e))?;
+            let got = infer_expr_type(&cond, env)?;
+            if !compatible(&Type::Bool, &got) {
+                return Err(type_error(cond.span, format!("Built-in `assert` expects `bool` but found `{}`", type_name(&got))));
+            }
+            Type::Void
+        }
+        ExprKind::Call { callee, args } => {
+            let sig = env.fns.get(callee.as_str())
+                .ok_or_else(|| type_error(callee.span, format!("Unknown function `{}`", callee)))?;
+            if sig.test {
+                return Err(type_error(callee.span, format!("`{}` is a test function and cannot be called", callee))
+                    .with_secondary(sig.span, "defined here"));
+            }
+            if args.len() != sig.params.len() {
+                return Err(type_error(expr.span, format!(
+                    "Function `{}` takes {} argument(s) but {} were supplied",
+                    callee, sig.params.len(), args.len()
+                )).with_secondary(sig.span, "defined here"));
+            }
+            for (i, (arg, expected)) in args.iter().zip(&sig.params).enumerate() {
+                let got = infer_expr_type_as(arg, expected, env)?;
+                if !compatible(expected, &got) {
+                    return Err(type_error(arg.span, format!(
+                        "Argument {} of `{}` expects `{}` but found `{}`",
+                        i + 1, callee, type_name(expected), type_name(&got)
+                    )));
+                }
+            }
+            sig.ret.clone()
+        }
+        ExprKind::Member { object, field } =>
+            type_from_name(resolve_builtin(object, field.as_str(), |n| env.lookup(n).is_some()).map_err(|e| type_error(expr.span, e))?.ty),
+        ExprKind::Unary { op, expr: inner } => {
+            let t = infer_expr_type(inner, env)?;
+            match (op, &t) {
+                (_, Type::Unknown) => Type::Unknown,
+                (UnaryOp::Neg, Type::I32 | Type::I64 | Type::F64) => t,
+                (UnaryOp::Not, Type::Bool) => Type::Bool,
+                _ => return Err(type_error(expr.span, format!("Operator `{}` cannot be applied to `{}`", op.symbol(), type_name(&t)))),
+            }
+        }
+        ExprKind::Binary { op, left, right } => {
+            let lt = infer_expr_type(left, env)?;
+            let rt = infer_expr_type(right, env)?;
+            binary_result_type(*op, &lt, &rt).map_err(|e| type_error(expr.span, e))?
+        }
+        ExprKind::Cast { expr: inner, ty } => {
+            let from = infer_expr_type(inner, env)?;
+            let to = type_from_name(ty.as_str());
+            let numeric = |t: &Type| matches!(t, Type::I32 | Type::I64 | Type::F64);
+            if to == Type::Unknown {
+                return Err(type_error(ty.span, format!("Unknown type `{}` in cast", ty)));
+            }
+            if !(from == to || from == Type::Unknown || numeric(&to) && (numeric(&from) || from == Type::Bool)) {
+                return Err(type_error(expr.span, format!("Cannot cast `{}` to `{}`", type_name(&from), type_name(&to))));
+            }
+            to
+        }
+    })
+}
+
+/// Like `infer_expr_type`, but an integer literal takes on a numeric `expected`
+/// type as long as its value fits, e.g. `let x: i64 = 5000000000;`.
+fn infer_expr_type_as(expr: &Expr, expected: &Type, env: &Env) -> CResult<Type> {
+    match int_literal(expr) {
+        Some(v) if matches!(expected, Type::I32 | Type::I64 | Type::F64) => {
+            if !literal_fits(v, expected) {
+                return Err(type_error(expr.span, format!("Integer literal `{}` is out of range for `{}`", v, type_name(expected))));
+            }
+            Ok(expected.clone())
+        }
+        _ => infer_expr_type(expr, env),
+    }
+}
+
+/// Resolve `ns.field` to a chain built-in such as `msg.sender`, unless `ns` is
+/// shadowed by a local of the same name.
+pub(crate) fn resolve_builtin(object: &Expr, field: &str, is_local: impl Fn(&str) -> bool) -> Result<&'static Builtin, String> {
+    match &object.kind {
+        ExprKind::Ident(ns) if !is_local(ns) && host::is_namespace(ns) =>
+            host::builtin(ns, field).ok_or_else(|| format!("`{}` has no field `{}`", ns, field)),
+        _ => Err(format!("Field access `.{}` is only supported on the built-ins `msg` and `block`", field)),
+    }
+}
+
+/// Whether a `from` value converts implicitly to `to`. Only lossless widenings
+/// are implicit; everything else needs an explicit `as`.
+pub(crate) fn widens(from: &Type, to: &Type) -> bool {
+    matches!((from, to), (Type::I32, Type::I64) | (Type::I32, Type::F64))
+}
+
+/// The type both operands of a binary operator are converted to before it applies.
+pub(crate) fn common_type(lt: &Type, rt: &Type) -> Option<Type> {
+    if lt == rt || widens(rt, lt) { Some(lt.clone()) } else if widens(lt, rt) { Some(rt.clone()) } else { None }
+}
+
+/// Result type of `lt op rt`, or an error if the operator does not apply.
+fn binary_result_type(op: BinOp, lt: &Type, rt: &Type) -> Result<Type, String> {
+    if *lt == Type::Unknown || *rt == Type::Unknown {
+        return Ok(if op.is_comparison() || op.is_logical() { Type::Bool } else { Type::Unknown });
+    }
+    let ty = common_type(lt, rt).filter(|t| if op.is_logical() {
+        *t == Type::Bool
+    } else if matches!(op, BinOp::Eq | BinOp::Ne) {
+        *t != Type::Void
+    } else if op.is_comparison() {
+        matches!(t, Type::I32 | Type::I64 | Type::F64)
+    } else if op.is_bitwise() {
+        matches!(t, Type::I32 | Type::I64)
+    } else if op == BinOp::Add {
+        matches!(t, Type::I32 | Type::I64 | Type::F64 | Type::String) // `+` on strings concatenates
+    } else if op == BinOp::Rem {
+        matches!(t, Type::I32 | Type::I64)
+    } else {
+        matches!(t, Type::I32 | Type::I64 | Type::F64)
+    });
+    match ty {
+        Some(_) if op.is_comparison() => Ok(Type::Bool),
+        Some(ty) => Ok(ty),
+        None => Err(format!(
+            "Operator `{}` cannot be applied to `{}` and `{}`",
+            op.symbol(), type_name(lt), type_name(rt)
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Local {
+    pub(crate) ty: Type,
+    pub(crate) mutable: bool,
+    /// Where the name was declared.
+    pub(crate) span: Span,
+}
+
+/// Parameter and return types of a function, used to check calls.
+#[derive(Debug, Clone)]
+pub(crate) struct Signature {
+    pub(crate) params: Vec<Type>,
+    pub(crate) ret: Type,
+    /// The function's name in its declaration.
+    pub(crate) span: Span,
+    pub(crate) test: bool,
+}
+
+fn annotated(ty: Option<&Ident>, default: Type) -> Type {
+    ty.map(|t| type_from_name(t.as_str())).unwrap_or(default)
+}
+
+impl Signature {
+    pub(crate) fn of(f: &FnItem) -> Self {
+        Signature {
+            params: f.params.iter().map(|p| annotated(p.ty.as_ref(), Type::Unknown)).collect(),
+            ret: annotated(f.return_type.as_ref(), Type::Void),
+            span: f.name.span,
+            test: f.is_test(),
+        }
+    }
+}
+
+/// The declarations of a contract that its functions can refer to.
+#[derive(Clone, Copy)]
+pub(crate) struct ContractItem<'p> {
+    pub(crate) name: &'p Ident,
+    pub(crate) fields: &'p [StorageField],
+    pub(crate) events: &'p [EventDecl],
+    pub(crate) errors: &'p [EventDecl],
+}
+
+impl<'p> ContractItem<'p> {
+    pub(crate) fn of(stmt: &'p Statement) -> Option<Self> {
+        match &stmt.kind {
+            StmtKind::Contract { name, fields, events, errors, .. } => Some(ContractItem { name, fields, events, errors }),
+            _ => None,
+        }
+    }
+
+    /// The event, or with `error` the custom error, declared as `name`.
+    pub(crate) fn event(&self, name: &str, error: bool) -> Option<&'p EventDecl> {
+        if error { self.errors } else { self.events }.iter().find(|e| e.name == name)
+    }
+}
+
+/// A function together with the contract it belongs to (if any).
+pub(crate) struct FnItem<'p> {
+    pub(crate) attributes: &'p [Attribute],
+    pub(crate) name: &'p Ident,
+    pub(crate) params: &'p [Param],
+    pub(crate) return_type: &'p Option<Ident>,
+    pub(crate) body: &'p [Statement],
+    pub(crate) contract: Option<ContractItem<'p>>,
+}
+
+impl FnItem<'_> {
+    pub(crate) fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    /// A `#[test]` function, compiled only into test modules.
+    pub(crate) fn is_test(&self) -> bool { self.attribute("test").is_some() }
+}
+
+/// Flatten top-level functions and contract members into one list. Contract
+/// functions share the module-wide function namespace.
+pub(crate) fn function_items(program: &Program) -> CResult<Vec<FnItem<'_>>> {
+    fn item<'p>(stmt: &'p Statement, contract: Option<ContractItem<'p>>) -> Option<FnItem<'p>> {
+        match &stmt.kind {
+            StmtKind::Function { attributes, name, params, return_type, body } =>
+                Some(FnItem { attributes, name, params, return_type, body, contract }),
+            _ => None,
+        }
+    }
+    let mut items = Vec::new();
+    for stmt in &program.statements {
+        match &stmt.kind {
+            StmtKind::Function { .. } => items.extend(item(stmt, None)),
+            // Already reported by the parser.
+            StmtKind::Error => {}
+            StmtKind::Contract { functions, .. } =>
+                items.extend(functions.iter().filter_map(|f| item(f, ContractItem::of(stmt)))),
+            _ => return Err(type_error(stmt.span, "Only `fn` and `contract` declarations are allowed at the top level")),
+        }
+    }
+    Ok(items)
+}
+
+/// Lexically scoped symbol table: storage fields (inside a contract) live in the
+/// outermost scope, then params, and every block pushes a new one, so inner
+/// `let`s shadow outer names until the block ends.
+pub(crate) struct Env<'a> {
+    scopes: Vec<HashMap<String, Local>>,
+    fns: &'a HashMap<String, Signature>,
+}
+
+impl<'a> Env<'a> {
+    /// Seed the outer scopes from storage fields and parameters (uses declared types when present).
+    pub(crate) fn new(fields: &[StorageField], params: &[Param], fns: &'a HashMap<String, Signature>) -> Self {
+        let mut env = Env { scopes: vec![HashMap::new()], fns };
+        for f in fields {
+            env.declare(f.name.as_str(), Local { ty: type_from_name(f.ty.as_str()), mutable: true, span: f.name.span });
+        }
+        env.push();
+        for p in params {
+            let ty = annotated(p.ty.as_ref(), Type::Unknown);
+            env.declare(p.name.as_str(), Local { ty, mutable: false, span: p.name.span });
+        }
+        env
+    }
+
+    pub(crate) fn push(&mut self) { self.scopes.push(HashMap::new()); }
+    pub(crate) fn pop(&mut self) { self.scopes.pop(); }
+
+    pub(crate) fn declare(&mut self, name: &str, local: Local) {
+        self.scopes.last_mut().expect("at least one scope").insert(name.to_string(), local);
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Option<&Local> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub(crate) fn function(&self, name: &str) -> Option<&Signature> { self.fns.get(name) }
+}
+
+/// `Unknown` is compatible with everything so untyped params don't cascade into errors.
+pub(crate) fn compatible(expected: &Type, got: &Type) -> bool {
+    *expected == Type::Unknown || *got == Type::Unknown || expected == got || widens(got, expected)
+}
+
+/// Whether a statement list returns on every path: a trailing `return` or
+/// `revert`, or an
+/// `if`/`else` whose branches both do. Loops are conservatively assumed to exit.
+fn always_returns(body: &[Statement]) -> bool {
+    match body.last().map(|s| &s.kind) {
+        Some(StmtKind::Return(_) | StmtKind::Revert { .. }) => true,
+        Some(StmtKind::Block(inner)) => always_returns(inner),
+        Some(StmtKind::If { then_body, else_body: Some(else_body), .. }) =>
+            always_returns(then_body) && always_returns(else_body),
+        _ => false,
+    }
+}
+
+/// Collect every function's signature, rejecting duplicates.
+pub(crate) fn collect_signatures(items: &[FnItem]) -> CResult<HashMap<String, Signature>> {
+    let mut fns: HashMap<String, Signature> = HashMap::new();
+    for f in items {
+        if let Some(first) = fns.insert(f.name.to_string(), Signature::of(f)) {
+            return Err(type_error(f.name.span, format!("Function `{}` is defined more than once", f.name))
+                .with_secondary(first.span, "first defined here"));
+        }
+    }
+    Ok(fns)
+}
+
+/// Every function in the ABI needs a selector of its own, see `abi::selector`.
+fn check_selectors(items: &[FnItem]) -> CResult<()> {
+    let mut seen: HashMap<u32, &Ident> = HashMap::new();
+    for f in items.iter().filter(|f| !f.is_test()) {
+        let selector = abi::selector(&abi::canonical(f.name.as_str(), f.params));
+        if let Some(first) = seen.insert(selector, f.name) {
+            return Err(type_error(f.name.span, format!("Function `{}` has the same selector, 0x{:08x}, as `{}`", f.name, selector, first))
+                .with_secondary(first.span, "selector first used here")
+                .with_note("rename one of them"));
+        }
+    }
+    Ok(())
+}
+
+/// Parameter and return annotations must name types, and parameters cannot be `void`.
+fn check_annotations(f: &FnItem) -> CResult<()> {
+    for p in f.params {
+        let Some(ty) = &p.ty else { continue };
+        match type_from_name(ty.as_str()) {
+            Type::Unknown => return Err(type_error(ty.span, format!("Unknown type `{}` for parameter `{}` of function `{}`", ty, p.name, f.name))),
+            Type::Void => return Err(type_error(ty.span, format!("Parameter `{}` of function `{}` cannot be `void`", p.name, f.name))),
+            _ => {}
+        }
+    }
+    match f.return_type {
+        Some(ty) if type_from_name(ty.as_str()) == Type::Unknown =>
+            Err(type_error(ty.span, format!("Unknown return type `{}` of function `{}`", ty, f.name))),
+        _ => Ok(()),
+    }
+}
+
+fn check_storage_fields(contract: &str, fields: &[StorageField]) -> CResult<()> {
+    let mut seen: HashMap<&str, Span> = HashMap::new();
+    for f in fields {
+        if type_from_name(f.ty.as_str()) == Type::Unknown {
+            return Err(type_error(f.ty.span, format!("Unknown type `{}` for storage field `{}` in contract `{}`", f.ty, f.name, contract)));
+        }
+        if let Some(first) = seen.insert(f.name.as_str(), f.name.span) {
+            return Err(type_error(f.name.span, format!("Storage field `{}` is declared more than once in contract `{}`", f.name, contract))
+                .with_secondary(first, "first declared here"));
+        }
+    }
+    Ok(())
+}
+
+/// Event and error fields are logged as 64-bit words, so only scalar types are allowed.
+fn check_events(contract: &str, what: &str, decls: &[EventDecl]) -> CResult<()> {
+    let mut seen: HashMap<&str, Span> = HashMap::new();
+    for e in decls {
+        if let Some(first) = seen.insert(e.name.as_str(), e.name.span) {
+            return Err(type_error(e.name.span, format!("The {} `{}` is declared more than once in contract `{}`", what, e.name, contract))
+                .with_secondary(first, "first declared here"));
+        }
+        let mut fields: HashMap<&str, Span> = HashMap::new();
+        for f in &e.fields {
+            let Some(ty) = &f.ty else {
+                return Err(type_error(f.span, format!("Field `{}` of {} `{}` needs a type", f.name, what, e.name)));
+            };
+            if !matches!(type_from_name(ty.as_str()), Type::I32 | Type::I64 | Type::F64 | Type::Bool) {
+                return Err(type_error(ty.span, format!("Field `{}` of {} `{}` cannot be `{}`", f.name, what, e.name, ty))
+                    .with_note("event and error fields must be `i32`, `i64`, `f64` or `bool`"));
+            }
+            if let Some(first) = fields.insert(f.name.as_str(), f.name.span) {
+                return Err(type_error(f.name.span, format!("Field `{}` is declared more than once in {} `{}`", f.name, what, e.name))
+                    .with_secondary(first, "first declared here"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A contract cannot declare an error of the same name as one `call` reverts with,
+/// or callers could not tell the two apart.
+fn check_reserved_errors(contract: &str, errors: &[EventDecl]) -> CResult<()> {
+    for e in errors {
+        if let Some(reserved) = abi::RESERVED_ERRORS.iter().find(|r| r.split('(').next() == Some(e.name.as_str())) {
+            return Err(type_error(e.name.span, format!("The error `{}` in contract `{}` is reserved", e.name, contract))
+                .with_note(format!("calls revert with `{}` themselves; choose another name", reserved)));
+        }
+    }
+    Ok(())
+}
+
+/// Attributes a function may carry.
+pub const ATTRIBUTES: &[&str] = &["test", "should_revert", "payable"];
+
+fn check_attributes(f: &FnItem) -> CResult<()> {
+    let mut seen: HashMap<&str, Span> = HashMap::new();
+    for a in f.attributes {
+        if !ATTRIBUTES.contains(&a.name.as_str()) {
+            return Err(type_error(a.name.span, format!("Unknown attribute `#[{}]` on function `{}`", a.name, f.name))
+                .with_note("the attributes are `#[test]`, `#[should_revert]` and `#[payable]`"));
+        }
+        if let Some(first) = seen.insert(a.name.as_str(), a.span) {
+            return Err(type_error(a.span, format!("Attribute `#[{}]` is repeated on function `{}`", a.name, f.name))
+                .with_secondary(first, "first given here"));
+        }
+    }
+    if let (Some(a), false) = (f.attribute("should_revert"), f.is_test()) {
+        return Err(type_error(a.span, format!("`#[should_revert]` only applies to `#[test]` functions, and `{}` is not one", f.name)));
+    }
+    if let (Some(a), true) = (f.attribute("payable"), f.is_test()) {
+        return Err(type_error(a.span, format!("Test function `{}` cannot be `#[payable]`", f.name)));
+    }
+    if f.is_test() && (!f.params.is_empty() || f.return_type.is_some()) {
+        return Err(type_error(f.name.span, format!("Test function `{}` must take no arguments and return nothing", f.name)));
+    }
+    Ok(())
+}
+
+/// Functions every program can call without declaring them.
+pub const BUILTIN_FUNCTIONS: &[&str] = &["len", "assert", "assert_eq"];
+
+/// Built-in assertions. A failed one traps with `unreachable` rather than
+/// reverting, so it carries no error for a caller to decode and never
+/// satisfies `#[should_revert]`; a guard a test expects to trip must `revert`
+/// a custom error instead. In a test build the trap also reports where the
+/// assertion is.
+pub(crate) const ASSERTIONS: &[&str] = &["assert", "assert_eq"];
+
+/// The condition an `assert(cond)` or `assert_eq(left, right)` call checks.
+pub(crate) fn assertion_condition(callee: &Ident, args: &[Expr], span: Span) -> Result<Expr, String> {
+    match (callee.as_str(), args) {
+        ("assert", [cond]) => Ok(cond.clone()),
+        ("assert_eq", [left, right]) => Ok(Expr {
+            kind: ExprKind::Binary { op: BinOp::Eq, left: Box::new(left.clone()), right: Box::new(right.clone()) },
+            span,
+        }),
+        (name, _) => Err(format!(
+            "Built-in `{}` takes {} argument(s) but {} were supplied",
+            name, if name == "assert" { 1 } else { 2 }, args.len()
+        )),
+    }
+}
+
+/// Type-check the program: declarations, assignments, calls and `return`s must agree with declared types.
+pub fn type_check(program: &Program) -> CResult<()> {
+    for stmt in &program.statements {
+        if let StmtKind::Contract { name, fields, events, errors, .. } = &stmt.kind {
+            check_storage_fields(name.as_str(), fields)?;
+            check_events(name.as_str(), "event", events)?;
+            check_events(name.as_str(), "error", errors)?;
+            check_reserved_errors(name.as_str(), errors)?;
+        }
+    }
+    let items = function_items(program)?;
+    let fns = collect_signatures(&items)?;
+    check_selectors(&items)?;
+    for f in &items {
+        check_attributes(f)?;
+        check_annotations(f)?;
+        let fields = f.contract.map_or(&[][..], |c| c.fields);
+        let mut env = Env::new(fields, f.params, &fns);
+        let expected = annotated(f.return_type.as_ref(), Type::Void);
+        let ctx = FnCtx { name: f.name.as_str(), ret: expected, in_loop: false, contract: f.contract };
+        check_block(f.body, &mut env, &ctx)?;
+        if !matches!(ctx.ret, Type::Void | Type::Unknown) && !always_returns(f.body) {
+            return Err(type_error(f.name.span, format!(
+                "Function `{}` must return a value of type `{}` on every path",
+                f.name, type_name(&ctx.ret)
+            )));
+        }
+    }
+    Ok(())
+}
+
+struct FnCtx<'a> {
+    name: &'a str,
+    ret: Type,
+    in_loop: bool,
+    contract: Option<ContractItem<'a>>,
+}
+
+/// Check `emit name(args)` or, with `error`, `revert name(args)` against the
+/// declaration in the enclosing contract.
+fn check_event_args(name: &Ident, args: &[Expr], error: bool, span: Span, env: &Env, ctx: &FnCtx) -> CResult<()> {
+    let (keyword, what) = if error { ("revert", "error") } else { ("emit", "event") };
+    let contract = ctx.contract.ok_or_else(|| type_error(span, format!("`{}` is only allowed in contract functions, and `{}` is not one", keyword, ctx.name)))?;
+    let decl = contract.event(name.as_str(), error)
+        .ok_or_else(|| type_error(name.span, format!("Unknown {} `{}` in contract `{}`", what, name, contract.name)))?;
+    if args.len() != decl.fields.len() {
+        return Err(type_error(span, format!("The {} `{}` has {} field(s) but {} were supplied", what, name, decl.fields.len(), args.len()))
+            .with_secondary(decl.name.span, "declared here"));
+    }
+    for (arg, field) in args.iter().zip(&decl.fields) {
+        let expected = annotated(field.ty.as_ref(), Type::Unknown);
+        let got = infer_expr_type_as(arg, &expected, env)?;
+        if !compatible(&expected, &got) {
+            return Err(type_error(arg.span, format!(
+                "Field `{}` of {} `{}` expects `{}` but found `{}`",
+                field.name, what, name, type_name(&expected), type_name(&got)
+            )).with_secondary(field.span, "declared here"));
+        }
+    }
+    Ok(())
+}
+
+fn check_condition(cond: &Expr, env: &Env, ctx: &FnCtx, what: &str) -> CResult<()> {
+    let got = infer_expr_type(cond, env)?;
+    if !compatible(&Type::Bool, &got) {
+        return Err(type_error(cond.span, format!(
+            "Type error in function `{}`: `{}` condition must be `bool` but found `{}`",
+            ctx.name, what, type_name(&got)
+        )));
+    }
+    Ok(())
+}
+
+fn check_block(body: &[Statement], env: &mut Env, ctx: &FnCtx) -> CResult<()> {
+    env.push();
+    let res = body.iter().try_for_each(|s| check_stmt(s, env, ctx));
+    env.pop();
+    res
+}
+
+fn check_stmt(stmt: &Statement, env: &mut Env, ctx: &FnCtx) -> CResult<()> {
+    match &stmt.kind {
+        StmtKind::Let { name, mutable, ty, value } => {
+            let declared = ty.as_ref().map(|t| type_from_name(t.as_str()));
+            if let (Some(t), Some(Type::Unknown)) = (ty, &declared) {
+                return Err(type_error(t.span, format!("Unknown type `{}` for `{}` in function `{}`", t, name, ctx.name)));
+            }
+            let got = match &declared {
+                Some(declared) => infer_expr_type_as(value, declared, env)?,
+                None => infer_expr_type(value, env)?,
+            };
+            let ty = match declared {
+                Some(declared) => {
+                    if !compatible(&declared, &got) {
+                        return Err(type_error(value.span, format!(
+                            "Type error in function `{}`: `{}` is declared as `{}` but initialized with `{}`",
+                            ctx.name, name, type_name(&declared), type_name(&got)
+                        )));
+                    }
+                    declared
+                }
+                None if got == Type::Void =>
+                    return Err(type_error(value.span, format!("`{}` cannot be bound to an expression of type `void`", name))),
+                None => got,
+            };
+            env.declare(name.as_str(), Local { ty, mutable: *mutable, span: name.span });
+        }
+        StmtKind::Assign { name, op, value } => {
+            let local = env.lookup(name.as_str()).cloned()
+                .ok_or_else(|| type_error(name.span, format!("Unknown identifier `{}`", name)))?;
+            if !local.mutable {
+                return Err(type_error(name.span, format!("Cannot assign to immutable variable `{}` (declare it with `let mut`)", name))
+                    .with_secondary(local.span, "declared here"));
+            }
+            let got = match op {
+                Some(op) => binary_result_type(*op, &local.ty, &infer_expr_type(value, env)?)
+                    .map_err(|e| type_error(stmt.span, e))?,
+                None => infer_expr_type_as(value, &local.ty, env)?,
+            };
+            if !compatible(&local.ty, &got) {
+                return Err(type_error(value.span, format!(
+                    "Type error in function `{}`: cannot assign `{}` to `{}` of type `{}`",
+                    ctx.name, type_name(&got), name, type_name(&local.ty)
+                )).with_secondary(local.span, "declared here"));
+            }
+        }
+        StmtKind::Block(inner) => check_block(inner, env, ctx)?,
+        StmtKind::If { cond, then_body, else_body } => {
+            check_condition(cond, env, ctx, "if")?;
+            check_block(then_body, env, ctx)?;
+            if let Some(else_body) = else_body { check_block(else_body, env, ctx)?; }
+        }
+        StmtKind::While { cond, body } => {
+            check_condition(cond, env, ctx, "while")?;
+            let inner = FnCtx { name: ctx.name, ret: ctx.ret.clone(), in_loop: true, contract: ctx.contract };
+            check_block(body, env, &inner)?;
+        }
+        StmtKind::Break | StmtKind::Continue if !ctx.in_loop => {
+            let kw = if matches!(stmt.kind, StmtKind::Break) { "break" } else { "continue" };
+            return Err(type_error(stmt.span, format!("`{}` outside of a loop in function `{}`", kw, ctx.name)));
+        }
+        StmtKind::Break | StmtKind::Continue => {}
+        StmtKind::Return(None) => {
+            if !matches!(ctx.ret, Type::Void | Type::Unknown) {
+                return Err(type_error(stmt.span, format!(
+                    "Type error in function `{}`: `return;` without a value, expected `{}`",
+                    ctx.name, type_name(&ctx.ret)
+                )));
+            }
+        }
+        StmtKind::Return(Some(expr)) => {
+            let got = infer_expr_type_as(expr, &ctx.ret, env)?;
+            let expected = &ctx.ret;
+            if *expected == Type::Void && got != Type::Void {
+                return Err(type_error(expr.span, format!("Type error in function `{}`: cannot return a value from a function without a return type", ctx.name)));
+            }
+            if *expected != Type::Void && !compatible(expected, &got) {
+                return Err(type_error(expr.span, format!(
+                    "Type error in function `{}`: expected `{}` but found `{}`",
+                    ctx.name, type_name(expected), type_name(&got)
+                )));
+            }
+        }
+        StmtKind::Emit { event, args } => check_event_args(event, args, false, stmt.span, env, ctx)?,
+        StmtKind::Revert { error, args } => check_event_args(error, args, true, stmt.span, env, ctx)?,
+        StmtKind::Expr(expr) => { infer_expr_type(expr, env)?; }
+        StmtKind::Error => {}
+        StmtKind::Function { name, .. } | StmtKind::Contract { name, .. } =>
+            return Err(type_error(name.span, format!("Nested declaration `{}` is not supported", name))),
+    }
+    Ok(())
+}
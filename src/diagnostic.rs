@@ -0,0 +1,176 @@
+//! Compiler diagnostics and their terminal rendering.
+
+use serde::Serialize;
+use std::fmt::Write;
+
+use crate::span::{line_col, SourceMap, Span};
+
+/// Diagnostic codes, one per class of problem. The first digit names the phase
+/// that reports it.
+pub mod codes {
+    /// A file could not be read or written.
+    pub const IO: &str = "E0001";
+    /// A token that cannot start or continue the construct being parsed.
+    pub const UNEXPECTED_TOKEN: &str = "E0101";
+    /// A literal the parser cannot represent (e.g. a too-large integer).
+    pub const INVALID_LITERAL: &str = "E0102";
+    /// `a < b < c` and friends.
+    pub const CHAINED_COMPARISON: &str = "E0103";
+    /// Source the lexer cannot turn into a token, e.g. an unterminated string.
+    pub const INVALID_TOKEN: &str = "E0104";
+    /// Any semantic error found by the type checker.
+    pub const TYPE: &str = "E0201";
+    /// A checked program the WASM backend cannot lower.
+    pub const CODEGEN: &str = "E0301";
+    /// A `#[test]` function failed: an assertion did not hold, it trapped, or
+    /// it did not revert as `#[should_revert]` expects.
+    pub const TEST_FAILED: &str = "E0401";
+}
+
+/// Every diagnostic is an error for now; warnings will join once there are lints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// A span with an explanation. The primary label marks where the problem is;
+/// secondary labels point at related code.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic { severity: Severity::Error, code, message: message.into(), labels: Vec::new(), notes: Vec::new() }
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Render for a terminal, in the familiar rustc layout:
+    ///
+    /// ```text
+    /// error[E0101]: Expected `;` but found `}`
+    ///  --> examples/counter.mint:3:13
+    ///   |
+    /// 3 |     return x }
+    ///   |             ^ expected `;`
+    /// ```
+    /// Labels in other files than the primary one are shown under their own path.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = format!("{}[{}]: {}\n", self.severity.as_str(), self.code, self.message);
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        // The primary label's file comes first; within a file, labels follow the source order.
+        let main = labels.iter().find(|l| l.primary).map(|l| l.span.file);
+        labels.sort_by_key(|l| (Some(l.span.file) != main, l.span.file.0, l.span.start));
+        let lines: Vec<usize> = labels.iter().map(|l| line_col(sources.source(l.span.file), l.span.start).0).collect();
+        let gutter = lines.iter().max().map_or(0, |n| n.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        let mut file = None;
+        let mut i = 0;
+        while i < labels.len() {
+            let (label, line) = (labels[i], lines[i]);
+            let src = sources.source(label.span.file);
+            if file != Some(label.span.file) {
+                let at = labels.iter().find(|l| l.primary && l.span.file == label.span.file).unwrap_or(&label);
+                let (line, col) = line_col(src, at.span.start);
+                let _ = writeln!(out, "{}--> {}:{}:{}", pad, sources.name(label.span.file), line, col);
+                let _ = writeln!(out, "{} |", pad);
+                file = Some(label.span.file);
+            }
+            // Every label starting on this line is drawn under one copy of it.
+            let same = labels[i..].iter().zip(&lines[i..]).take_while(|(l, &n)| l.span.file == label.span.file && n == line).count();
+            let text = src.lines().nth(line - 1).unwrap_or("");
+            let _ = writeln!(out, "{:>w$} | {}", line, text, w = gutter);
+            let mut marks: Vec<(usize, usize, &Label)> = labels[i..i + same].iter().map(|l| {
+                let start = l.span.start.min(src.len());
+                let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+                // Underline within the first line of the span, at least one column wide.
+                let col = src[line_start..start].chars().count();
+                let end = l.span.end.clamp(start, line_start + text.len());
+                (col, src.get(start..end).map_or(0, |s| s.chars().count()).max(1), *l)
+            }).collect();
+            marks.sort_by_key(|&(col, ..)| col);
+            let underlines: Vec<String> = marks.iter().map(|&(_, width, l)| (if l.primary { "^" } else { "-" }).repeat(width)).collect();
+            let underline = columns(marks.iter().zip(&underlines).map(|(m, u)| (m.0, u.as_str())));
+            // The rightmost label's message follows the underline; the others
+            // hang below it, each joined to its mark by a `|`.
+            let (last, rest) = marks.split_last().expect("at least one label");
+            let _ = writeln!(out, "{} | {}", pad, format!("{} {}", underline, last.2.message).trim_end());
+            let rest: Vec<&(usize, usize, &Label)> = rest.iter().filter(|m| !m.2.message.is_empty()).collect();
+            for (n, &&(col, _, l)) in rest.iter().enumerate().rev() {
+                let bars = rest[..n].iter().map(|m| (m.0, "|"));
+                let _ = writeln!(out, "{} | {}", pad, columns(bars.clone().chain([(col, "|")])));
+                let _ = writeln!(out, "{} | {}", pad, columns(bars.chain([(col, l.message.as_str())])));
+            }
+            i += same;
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "{} = note: {}", pad, note);
+        }
+        out
+    }
+
+    /// The diagnostic as JSON for `--message-format=json`: the serialized
+    /// fields, with each label's file name and 1-based line/column range
+    /// added, and the terminal rendering under `rendered`.
+    pub fn to_json(&self, sources: &SourceMap) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).expect("diagnostics serialize");
+        for (label, json) in self.labels.iter().zip(value["labels"].as_array_mut().expect("labels is a list")) {
+            let src = sources.source(label.span.file);
+            let (line_start, column_start) = line_col(src, label.span.start);
+            let (line_end, column_end) = line_col(src, label.span.end);
+            json["file_name"] = sources.name(label.span.file).into();
+            json["line_start"] = line_start.into();
+            json["column_start"] = column_start.into();
+            json["line_end"] = line_end.into();
+            json["column_end"] = column_end.into();
+        }
+        value["rendered"] = self.render(sources).into();
+        value
+    }
+}
+
+/// Each text placed at its column, left to right, never overlapping the one before.
+fn columns<'a>(texts: impl IntoIterator<Item = (usize, &'a str)>) -> String {
+    let mut row = String::new();
+    for (col, text) in texts {
+        row.push_str(&" ".repeat(col.saturating_sub(row.chars().count())));
+        row.push_str(text);
+    }
+    row
+}
@@ -0,0 +1,131 @@
+//! A small Wadler-style document printer. A `Group` prints on one line when
+//! it fits in the remaining width; otherwise each `Line` in it (but not in
+//! nested groups) becomes a newline.
+
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A line break, or the given text when the enclosing group is flat.
+    Line(&'static str),
+    /// A line break in every mode.
+    HardLine,
+    /// A line break unless the line so far is blank.
+    FreshLine,
+    Indent(Box<Doc>),
+    /// Contents and whether they must break (they contain a hard line).
+    Group(Box<Doc>, bool),
+    Concat(Vec<Doc>),
+    /// Text held back until the end of the line, for trailing line comments.
+    LineSuffix(String),
+    /// Forces the enclosing groups to break.
+    BreakParent,
+}
+
+pub const INDENT: usize = 4;
+
+pub fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+/// A space, or a line break when the group breaks.
+pub fn line() -> Doc {
+    Doc::Line(" ")
+}
+
+/// Nothing, or a line break when the group breaks.
+pub fn softline() -> Doc {
+    Doc::Line("")
+}
+
+pub fn indent(doc: Doc) -> Doc {
+    Doc::Indent(Box::new(doc))
+}
+
+pub fn group(doc: Doc) -> Doc {
+    let hard = doc.has_hard_line();
+    Doc::Group(Box::new(doc), hard)
+}
+
+/// A line comment ending the current line; the enclosing groups must break.
+pub fn line_suffix(s: impl Into<String>) -> Doc {
+    Doc::Concat(vec![Doc::LineSuffix(s.into()), Doc::BreakParent])
+}
+
+impl Doc {
+    fn has_hard_line(&self) -> bool {
+        match self {
+            Doc::HardLine | Doc::FreshLine | Doc::BreakParent => true,
+            Doc::Group(_, hard) => *hard,
+            Doc::Indent(d) => d.has_hard_line(),
+            Doc::Concat(ds) => ds.iter().any(Doc::has_hard_line),
+            Doc::Text(_) | Doc::Line(_) | Doc::LineSuffix(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode { Flat, Break }
+
+/// Lay out `doc` in `width` columns. Trailing whitespace is dropped from every line.
+pub fn print(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut col = 0;
+    let mut suffix = String::new();
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((ind, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                col = match s.rfind('\n') { Some(i) => s[i + 1..].chars().count(), None => col + s.chars().count() };
+            }
+            Doc::Line(flat) if mode == Mode::Flat => { out.push_str(flat); col += flat.len(); }
+            Doc::Line(_) | Doc::HardLine => { newline(&mut out, &mut suffix, ind); col = ind; }
+            Doc::FreshLine if out.trim_end_matches(' ').ends_with('\n') || out.is_empty() => {}
+            Doc::FreshLine => { newline(&mut out, &mut suffix, ind); col = ind; }
+            Doc::Indent(d) => stack.push((ind + INDENT, mode, d)),
+            Doc::Group(d, hard) => {
+                let flat = !hard && fits(width as isize - col as isize, d, &stack);
+                stack.push((ind, if flat { Mode::Flat } else { Mode::Break }, d));
+            }
+            Doc::Concat(ds) => stack.extend(ds.iter().rev().map(|d| (ind, mode, d))),
+            Doc::LineSuffix(s) => suffix.push_str(s),
+            Doc::BreakParent => {}
+        }
+    }
+    out.push_str(&suffix);
+    out.truncate(out.trim_end_matches([' ', '\t']).len());
+    out
+}
+
+fn newline(out: &mut String, suffix: &mut String, ind: usize) {
+    out.push_str(suffix);
+    suffix.clear();
+    out.truncate(out.trim_end_matches([' ', '\t']).len());
+    out.push('\n');
+    out.extend(std::iter::repeat_n(' ', ind));
+}
+
+/// Whether `doc` printed flat, followed by the rest of the line, fits in `rem` columns.
+fn fits(mut rem: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() { Some(&(_, mode, d)) => (mode, d), None => return true },
+        };
+        match doc {
+            Doc::Text(s) => match s.find('\n') {
+                Some(i) => return rem >= s[..i].chars().count() as isize,
+                None => rem -= s.chars().count() as isize,
+            },
+            Doc::Line(flat) if mode == Mode::Flat => rem -= flat.len() as isize,
+            Doc::Line(_) | Doc::HardLine | Doc::FreshLine => return true,
+            Doc::Indent(d) => stack.push((mode, d)),
+            Doc::Group(d, hard) => stack.push((if *hard { Mode::Break } else { mode }, d)),
+            Doc::Concat(ds) => stack.extend(ds.iter().rev().map(|d| (mode, d))),
+            Doc::LineSuffix(_) | Doc::BreakParent => {}
+        }
+        if rem < 0 { return false; }
+    }
+}
@@ -0,0 +1,221 @@
+//! Source formatter. The layout is rebuilt from the concrete syntax tree, so
+//! every spelling of a program formats the same way, while comments, which
+//! the tree keeps as trivia, are carried over to where they were.
+//!
+//! The canonical style: four-space indents, one statement per line, a blank
+//! line around functions and contracts, and at most one blank line kept from
+//! the source elsewhere. Parameter and argument lists and binary expressions
+//! are broken over several lines only when they do not fit in `MAX_WIDTH`.
+
+mod doc;
+
+use crate::ast::BinOp;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{TokenKind, TriviaKind};
+use crate::parser::binop_for;
+use crate::span::FileId;
+use crate::syntax::{self, NodeKind, SyntaxElement, SyntaxNode, SyntaxToken};
+use doc::{group, indent, line, line_suffix, softline, text, Doc};
+
+pub const MAX_WIDTH: usize = 100;
+
+/// Format `src` in the canonical style. Files with syntax errors are left
+/// alone and their diagnostics returned instead.
+pub fn format(src: &str, file: FileId) -> Result<String, Vec<Diagnostic>> {
+    let (tree, diagnostics) = syntax::parse(src, file);
+    if !diagnostics.is_empty() { return Err(diagnostics); }
+    let mut out = doc::print(&node(&tree), MAX_WIDTH);
+    if !out.is_empty() { out.push('\n'); }
+    Ok(out)
+}
+
+fn node(n: &SyntaxNode) -> Doc {
+    match n.kind {
+        NodeKind::SourceFile => {
+            let eof = n.tokens().last().expect("every file ends with `Eof`");
+            Doc::Concat(vec![lines(n, false), inner_comments(eof, n.nodes().next().is_some())])
+        }
+        NodeKind::Contract => {
+            let mut tokens = n.tokens();
+            let (kw, name) = (tokens.next().unwrap(), tokens.next().unwrap());
+            Doc::Concat(vec![tok(kw), text(" "), tok(name), text(" "), braced(n)])
+        }
+        NodeKind::Block => braced(n),
+        NodeKind::ParamList | NodeKind::ArgList => list(n),
+        NodeKind::Binary => binary(n),
+        NodeKind::Error => unreachable!("files with syntax errors are not formatted"),
+        _ => spaced(n),
+    }
+}
+
+fn element(e: &SyntaxElement) -> Doc {
+    match e {
+        SyntaxElement::Node(n) => node(n),
+        SyntaxElement::Token(t) => tok(t),
+    }
+}
+
+/// Children separated by single spaces, except where punctuation hugs its neighbours.
+fn spaced(n: &SyntaxNode) -> Doc {
+    let mut docs = Vec::new();
+    for (i, child) in n.children.iter().enumerate() {
+        // Each attribute of a function goes on its own line.
+        let after_attribute = i > 0 && matches!(&n.children[i - 1], SyntaxElement::Node(a) if a.kind == NodeKind::Attribute);
+        if after_attribute { docs.push(Doc::HardLine); }
+        else if i > 0 && space_before(n.kind, child) { docs.push(text(" ")); }
+        docs.push(element(child));
+    }
+    Doc::Concat(docs)
+}
+
+fn space_before(parent: NodeKind, child: &SyntaxElement) -> bool {
+    let tight = matches!(parent, NodeKind::Unary | NodeKind::Member | NodeKind::Paren | NodeKind::Attribute);
+    !tight && match child {
+        SyntaxElement::Node(n) => !matches!(n.kind, NodeKind::ParamList | NodeKind::ArgList),
+        SyntaxElement::Token(t) => !matches!(t.kind, TokenKind::Semicolon | TokenKind::Symbol(':')),
+    }
+}
+
+/// `a + b + c`, or with every operator of the chain starting a line when
+/// that does not fit. Operators of different precedence break separately.
+fn binary(n: &SyntaxNode) -> Doc {
+    let precedence = |n: &SyntaxNode| n.tokens().find_map(|t| binop_for(&t.kind)).map(BinOp::precedence);
+    let mut rest = Vec::new();
+    let mut first = n;
+    let first = loop {
+        let [left, op, right] = &first.children[..] else { unreachable!("binary expressions have two operands") };
+        rest.push(Doc::Concat(vec![line(), element(op), text(" "), element(right)]));
+        match left {
+            SyntaxElement::Node(l) if l.kind == NodeKind::Binary && precedence(l) == precedence(n) => first = l,
+            _ => break left,
+        }
+    };
+    rest.reverse();
+    group(Doc::Concat(vec![element(first), indent(Doc::Concat(rest))]))
+}
+
+/// `(a, b)`, or one item per line when that does not fit. A trailing comma is dropped.
+fn list(n: &SyntaxNode) -> Doc {
+    let open = n.tokens().next().unwrap();
+    let close = n.tokens().last().unwrap();
+    let items: Vec<&SyntaxNode> = n.nodes().collect();
+    if items.is_empty() { return Doc::Concat(vec![tok(open), tok(close)]); }
+    let commas: Vec<&SyntaxToken> = n.tokens().filter(|t| t.kind == TokenKind::Symbol(',')).collect();
+    let mut body = vec![softline()];
+    for (i, item) in items.iter().enumerate() {
+        body.push(node(item));
+        match commas.get(i) {
+            Some(comma) if i + 1 < items.len() => { body.push(tok(comma)); body.push(line()); }
+            Some(comma) => body.push(token(comma, "")),
+            None => {}
+        }
+    }
+    group(Doc::Concat(vec![tok(open), indent(Doc::Concat(body)), softline(), tok(close)]))
+}
+
+/// A `{ ... }` body (of a block or a contract) with one member per line.
+fn braced(n: &SyntaxNode) -> Doc {
+    let open = n.tokens().find(|t| t.kind == TokenKind::OpenBrace).unwrap();
+    let close = n.tokens().last().unwrap();
+    let empty = n.nodes().next().is_none();
+    let commented = !leading_comments(close).0.is_empty() || open.trailing.iter().any(|t| t.kind == TriviaKind::LineComment);
+    if empty && !commented { return Doc::Concat(vec![tok(open), tok(close)]); }
+    let body = Doc::Concat(vec![lines(n, true), inner_comments(close, !empty)]);
+    Doc::Concat(vec![tok(open), indent(body), Doc::HardLine, text(close.text.as_str()), trailing(close)])
+}
+
+/// The child nodes of `n` one per line, each preceded by a line break if
+/// `first_break` or it is not the first. A blank line from the source is kept,
+/// and one always separates functions and contracts from their neighbours.
+fn lines(n: &SyntaxNode, first_break: bool) -> Doc {
+    let is_item = |n: &SyntaxNode| matches!(n.kind, NodeKind::Function | NodeKind::Contract);
+    let mut docs = Vec::new();
+    let mut prev: Option<&SyntaxNode> = None;
+    for child in n.nodes() {
+        if let Some(prev) = prev {
+            docs.push(Doc::HardLine);
+            if blank_before(child) || is_item(prev) || is_item(child) { docs.push(Doc::HardLine); }
+        } else if first_break {
+            docs.push(Doc::HardLine);
+        }
+        docs.push(node(child));
+        prev = Some(child);
+    }
+    Doc::Concat(docs)
+}
+
+/// Whether a blank line separates `n` from the line above it in the source.
+fn blank_before(n: &SyntaxNode) -> bool {
+    n.descendant_tokens().first().is_some_and(|t| {
+        t.leading.iter().take_while(|p| p.kind == TriviaKind::Whitespace).any(|p| p.text.ends_with('\n'))
+    })
+}
+
+/// The comments in a token's leading trivia, each with the number of line
+/// breaks before it, and the number of line breaks between the last one and
+/// the token. The first comment always starts a line, since the line above
+/// ends in the previous token's trailing trivia.
+fn leading_comments(t: &SyntaxToken) -> (Vec<(&str, usize)>, usize) {
+    let mut comments = Vec::new();
+    let mut breaks = 0;
+    for piece in &t.leading {
+        match piece.kind {
+            TriviaKind::Whitespace => breaks += piece.text.ends_with('\n') as usize,
+            TriviaKind::LineComment | TriviaKind::BlockComment => { comments.push((piece.text.as_str(), breaks)); breaks = 0; }
+        }
+    }
+    (comments, breaks)
+}
+
+/// What separates two things `breaks` line breaks apart: a space when they
+/// share a line, otherwise a newline, keeping one blank line if there was one.
+fn separator(breaks: usize) -> Doc {
+    match breaks {
+        0 => text(" "),
+        1 => Doc::HardLine,
+        _ => Doc::Concat(vec![Doc::HardLine, Doc::HardLine]),
+    }
+}
+
+fn tok(t: &SyntaxToken) -> Doc {
+    token(t, &t.text)
+}
+
+/// `t` printed as `s`, with its comments.
+fn token(t: &SyntaxToken, s: &str) -> Doc {
+    let (comments, after) = leading_comments(t);
+    let mut docs = Vec::new();
+    for (i, &(comment, breaks)) in comments.iter().enumerate() {
+        docs.push(if i == 0 { Doc::FreshLine } else { separator(breaks) });
+        docs.push(text(comment));
+    }
+    if !comments.is_empty() { docs.push(separator(after)); }
+    docs.push(text(s));
+    docs.push(trailing(t));
+    Doc::Concat(docs)
+}
+
+/// Comments after the last member of a body, starting on a line of their
+/// own; the token they lead (a `}` or the end of the file) is printed by the
+/// caller. A blank line after `after_code` is kept.
+fn inner_comments(t: &SyntaxToken, after_code: bool) -> Doc {
+    let mut docs = Vec::new();
+    for (i, &(comment, breaks)) in leading_comments(t).0.iter().enumerate() {
+        docs.push(match i {
+            // The first comment's breaks only count blank lines.
+            0 if after_code => separator(breaks + 1),
+            0 => Doc::FreshLine,
+            _ => separator(breaks),
+        });
+        docs.push(text(comment));
+    }
+    Doc::Concat(docs)
+}
+
+fn trailing(t: &SyntaxToken) -> Doc {
+    Doc::Concat(t.trailing.iter().filter_map(|piece| match piece.kind {
+        TriviaKind::LineComment => Some(line_suffix(format!(" {}", piece.text))),
+        TriviaKind::BlockComment => Some(text(format!(" {}", piece.text))),
+        TriviaKind::Whitespace => None,
+    }).collect())
+}
@@ -0,0 +1,90 @@
+//! The host ABI: functions a compiled Mintora module may import from the chain
+//! environment. Every import lives in the `env` module and chain values
+//! (account ids, amounts, block data, storage cells) are 64-bit integers.
+
+use crate::wasm::{FuncType, ValType};
+
+pub const MODULE: &str = "env";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HostFn {
+    pub name: &'static str,
+    pub params: &'static [ValType],
+    pub results: &'static [ValType],
+}
+
+impl HostFn {
+    pub fn func_type(&self) -> FuncType {
+        FuncType { params: self.params.to_vec(), results: self.results.to_vec() }
+    }
+}
+
+/// `storage_read(key) -> value`; unset keys read as 0.
+pub const STORAGE_READ: HostFn = HostFn { name: "storage_read", params: &[ValType::I64], results: &[ValType::I64] };
+/// `storage_write(key, value)`.
+pub const STORAGE_WRITE: HostFn = HostFn { name: "storage_write", params: &[ValType::I64, ValType::I64], results: &[] };
+/// Account id of the immediate caller.
+pub const CALLER: HostFn = HostFn { name: "caller", params: &[], results: &[ValType::I64] };
+/// Amount of native currency sent with the call.
+pub const CALL_VALUE: HostFn = HostFn { name: "call_value", params: &[], results: &[ValType::I64] };
+pub const BLOCK_NUMBER: HostFn = HostFn { name: "block_number", params: &[], results: &[ValType::I64] };
+/// Seconds since the Unix epoch.
+pub const BLOCK_TIMESTAMP: HostFn = HostFn { name: "block_timestamp", params: &[], results: &[ValType::I64] };
+
+/// `emit_event(topic, data)`: append an entry to the chain's event log. `data`
+/// points at the event's fields in memory, see `words`.
+pub const EMIT_EVENT: HostFn = HostFn { name: "emit_event", params: &[ValType::I64, ValType::I64], results: &[] };
+/// `revert(selector, data)`: abort the call with a custom error, whose fields
+/// `data` points at like `emit_event`'s. It never returns.
+pub const REVERT: HostFn = HostFn { name: "revert", params: &[ValType::I64, ValType::I64], results: &[] };
+
+/// `calldata_size() -> len`: the byte length of the input to `call`, see `abi`.
+pub const CALLDATA_SIZE: HostFn = HostFn { name: "calldata_size", params: &[], results: &[ValType::I64] };
+/// `calldata_copy(dest)`: copy the whole input to `call` into memory at `dest`.
+pub const CALLDATA_COPY: HostFn = HostFn { name: "calldata_copy", params: &[ValType::I64], results: &[] };
+/// `return_data(data)`: the packed `(ptr << 32) | len` bytes are the call's output.
+pub const RETURN_DATA: HostFn = HostFn { name: "return_data", params: &[ValType::I64], results: &[] };
+
+/// `assert_failed(location)`, called by test modules just before a failed
+/// assertion traps. `location` is its source span, `start << 32 | end`.
+pub const ASSERT_FAILED: HostFn = HostFn { name: "assert_failed", params: &[ValType::I64], results: &[] };
+
+/// Every host function, in the order they are imported when used.
+pub const FUNCTIONS: &[&HostFn] = &[
+    &STORAGE_READ, &STORAGE_WRITE, &CALLER, &CALL_VALUE, &BLOCK_NUMBER, &BLOCK_TIMESTAMP, &EMIT_EVENT, &REVERT,
+    &CALLDATA_SIZE, &CALLDATA_COPY, &RETURN_DATA, &ASSERT_FAILED,
+];
+
+/// The fields of an event or error, which a module passes as a packed
+/// `(ptr << 32) | len` of consecutive little-endian 64-bit words: integers
+/// sign-extended, `bool` as 0 or 1, and `f64` as its bits.
+pub fn words(memory: &[u8], packed: i64) -> Option<Vec<i64>> {
+    let (ptr, len) = ((packed as u64 >> 32) as usize, packed as u32 as usize);
+    let bytes = memory.get(ptr..ptr.checked_add(len)?)?;
+    Some(bytes.chunks_exact(8).map(|w| i64::from_le_bytes(w.try_into().unwrap())).collect())
+}
+
+/// A language built-in such as `msg.sender`, read through a host function.
+#[derive(Debug)]
+pub struct Builtin {
+    pub namespace: &'static str,
+    pub field: &'static str,
+    /// Mintora type of the value.
+    pub ty: &'static str,
+    pub host: &'static HostFn,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { namespace: "msg", field: "sender", ty: "i64", host: &CALLER },
+    Builtin { namespace: "msg", field: "value", ty: "i64", host: &CALL_VALUE },
+    Builtin { namespace: "block", field: "number", ty: "i64", host: &BLOCK_NUMBER },
+    Builtin { namespace: "block", field: "timestamp", ty: "i64", host: &BLOCK_TIMESTAMP },
+];
+
+pub fn is_namespace(name: &str) -> bool {
+    BUILTINS.iter().any(|b| b.namespace == name)
+}
+
+pub fn builtin(namespace: &str, field: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.namespace == namespace && b.field == field)
+}
@@ -1,102 +1,334 @@
-use std::iter::Peekable;
-use std::str::Chars;
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum TokenKind {
-    Keyword(String),
-    Ident(String),
-    Number(String),
-    StringLiteral(String),
-    Symbol(char),
-    Arrow,
-    OpenBrace, CloseBrace,
-    OpenParen, CloseParen,
-    Semicolon,
-    Eof,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Token { pub kind: TokenKind, pub span: (usize, usize) }
-
-pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>,
-    idx: usize,
-}
-
-impl<'a> Lexer<'a> {
-    pub fn new(src: &'a str) -> Self {
-        Self { input: src.chars().peekable(), idx: 0 }
-    }
-
-    fn bump(&mut self) -> Option<char> {
-        let c = self.input.next()?;
-        self.idx += c.len_utf8();
-        Some(c)
-    }
-
-    fn peek(&mut self) -> Option<&char> { self.input.peek() }
-
-    pub fn next_token(&mut self) -> Token {
-        while let Some(&c) = self.peek() {
-            if c.is_whitespace() { self.bump(); } else { break; }
-        }
-        let start = self.idx;
-
-        let kind = match self.bump() {
-            Some('/') if self.peek() == Some(&'/') => {
-                while let Some(&c) = self.peek() {
-                    if c == '\n' { break; }
-                    self.bump();
-                }
-                return self.next_token();
-            }
-            Some('"') => {
-                let mut s = String::new();
-                while let Some(&c) = self.peek() {
-                    if c == '"' { self.bump(); break; }
-                    s.push(self.bump().unwrap());
-                }
-                TokenKind::StringLiteral(s)
-            }
-            Some(c) if c.is_ascii_digit() => {
-                let mut num = c.to_string();
-                while let Some(&d) = self.peek() {
-                    if d.is_ascii_digit() || d == '.' { num.push(self.bump().unwrap()); }
-                    else { break; }
-                }
-                TokenKind::Number(num)
-            }
-            Some(c) if c.is_alphabetic() || c == '_' => {
-                let mut ident = c.to_string();
-                while let Some(&d) = self.peek() {
-                    if d.is_alphanumeric() || d == '_' { ident.push(self.bump().unwrap()); }
-                    else { break; }
-                }
-                match ident.as_str() {
-                    "contract" | "fn" | "return" | "let" | "if" | "else" =>
-                        TokenKind::Keyword(ident),
-                    _ => TokenKind::Ident(ident),
-                }
-            }
-            Some('-') if self.peek() == Some(&'>') => { self.bump(); TokenKind::Arrow }
-            Some('{') => TokenKind::OpenBrace,
-            Some('}') => TokenKind::CloseBrace,
-            Some('(') => TokenKind::OpenParen,
-            Some(')') => TokenKind::CloseParen,
-            Some(';') => TokenKind::Semicolon,
-            Some(c)   => TokenKind::Symbol(c),
-            None      => TokenKind::Eof,
-        };
-
-        let end = self.idx;
-        Token { kind, span: (start, end) }
-    }
-}
-
-impl<'a> Iterator for Lexer<'a> {
-    type Item = Token;
-    fn next(&mut self) -> Option<Self::Item> {
-        let t = self.next_token();
-        if t.kind == TokenKind::Eof { None } else { Some(t) }
-    }
-}
+use serde::Serialize;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// What a keyword or operator does, which decides how editors colour it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Control,
+    Declaration,
+    Modifier,
+    Constant,
+    Cast,
+    Arithmetic,
+    Comparison,
+    Logical,
+    Bitwise,
+    Assignment,
+    Arrow,
+    /// Listed as open/close pairs.
+    Bracket,
+    Delimiter,
+}
+
+/// Words that lex as `TokenKind::Keyword` rather than as identifiers.
+pub const KEYWORDS: &[(&str, TokenClass)] = &[
+    ("contract", TokenClass::Declaration), ("storage", TokenClass::Declaration), ("fn", TokenClass::Declaration),
+    ("let", TokenClass::Declaration), ("event", TokenClass::Declaration), ("error", TokenClass::Declaration),
+    ("mut", TokenClass::Modifier),
+    ("if", TokenClass::Control), ("else", TokenClass::Control), ("while", TokenClass::Control),
+    ("break", TokenClass::Control), ("continue", TokenClass::Control), ("return", TokenClass::Control),
+    ("emit", TokenClass::Control), ("revert", TokenClass::Control),
+    ("true", TokenClass::Constant), ("false", TokenClass::Constant), ("as", TokenClass::Cast),
+];
+
+pub fn is_keyword(word: &str) -> bool { KEYWORDS.iter().any(|(k, _)| *k == word) }
+
+/// Every operator and punctuation token, longest first so the lexer can take
+/// the first one the input starts with.
+pub const OPERATORS: &[(&str, TokenKind, TokenClass)] = &[
+    ("<<=", TokenKind::ShlEq, TokenClass::Assignment), (">>=", TokenKind::ShrEq, TokenClass::Assignment),
+    ("->", TokenKind::Arrow, TokenClass::Arrow),
+    ("+=", TokenKind::PlusEq, TokenClass::Assignment), ("-=", TokenKind::MinusEq, TokenClass::Assignment),
+    ("*=", TokenKind::StarEq, TokenClass::Assignment), ("/=", TokenKind::SlashEq, TokenClass::Assignment),
+    ("%=", TokenKind::PercentEq, TokenClass::Assignment), ("&=", TokenKind::AmpEq, TokenClass::Assignment),
+    ("|=", TokenKind::PipeEq, TokenClass::Assignment), ("^=", TokenKind::CaretEq, TokenClass::Assignment),
+    ("&&", TokenKind::AndAnd, TokenClass::Logical), ("||", TokenKind::OrOr, TokenClass::Logical),
+    ("==", TokenKind::EqEq, TokenClass::Comparison), ("!=", TokenKind::NotEq, TokenClass::Comparison),
+    ("<=", TokenKind::LtEq, TokenClass::Comparison), (">=", TokenKind::GtEq, TokenClass::Comparison),
+    ("<<", TokenKind::Shl, TokenClass::Bitwise), (">>", TokenKind::Shr, TokenClass::Bitwise),
+    ("+", TokenKind::Plus, TokenClass::Arithmetic), ("-", TokenKind::Minus, TokenClass::Arithmetic),
+    ("*", TokenKind::Star, TokenClass::Arithmetic), ("/", TokenKind::Slash, TokenClass::Arithmetic),
+    ("%", TokenKind::Percent, TokenClass::Arithmetic),
+    ("&", TokenKind::Amp, TokenClass::Bitwise), ("|", TokenKind::Pipe, TokenClass::Bitwise), ("^", TokenKind::Caret, TokenClass::Bitwise),
+    ("!", TokenKind::Bang, TokenClass::Logical),
+    ("<", TokenKind::Lt, TokenClass::Comparison), (">", TokenKind::Gt, TokenClass::Comparison),
+    ("=", TokenKind::Assign, TokenClass::Assignment),
+    ("{", TokenKind::OpenBrace, TokenClass::Bracket), ("}", TokenKind::CloseBrace, TokenClass::Bracket),
+    ("[", TokenKind::OpenBracket, TokenClass::Bracket), ("]", TokenKind::CloseBracket, TokenClass::Bracket),
+    ("(", TokenKind::OpenParen, TokenClass::Bracket), (")", TokenKind::CloseParen, TokenClass::Bracket),
+    (";", TokenKind::Semicolon, TokenClass::Delimiter), (":", TokenKind::Symbol(':'), TokenClass::Delimiter),
+    (",", TokenKind::Symbol(','), TokenClass::Delimiter), (".", TokenKind::Symbol('.'), TokenClass::Delimiter),
+    ("#", TokenKind::Hash, TokenClass::Delimiter),
+];
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum TokenKind {
+    Keyword(String),
+    Ident(String),
+    Number(String),
+    StringLiteral(String),
+    Symbol(char),
+    Arrow,
+    Plus, Minus, Star, Slash, Percent,
+    Amp, Pipe, Caret, Shl, Shr,
+    AndAnd, OrOr, Bang,
+    EqEq, NotEq, Lt, LtEq, Gt, GtEq,
+    Assign,
+    PlusEq, MinusEq, StarEq, SlashEq, PercentEq,
+    AmpEq, PipeEq, CaretEq, ShlEq, ShrEq,
+    OpenBrace, CloseBrace,
+    OpenParen, CloseParen,
+    OpenBracket, CloseBracket,
+    /// `#`, which starts an attribute such as `#[test]`.
+    Hash,
+    Semicolon,
+    /// Malformed input, e.g. an unterminated string; the message says what is wrong.
+    Error(String),
+    Eof,
+}
+
+impl std::fmt::Display for TokenKind {
+    /// How a token is named in diagnostics, e.g. "`;`" or "identifier `x`".
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sym = match self {
+            TokenKind::Keyword(k) => return write!(f, "keyword `{}`", k),
+            TokenKind::Ident(id) => return write!(f, "identifier `{}`", id),
+            TokenKind::Number(n) => return write!(f, "number `{}`", n),
+            TokenKind::StringLiteral(_) => return write!(f, "a string literal"),
+            TokenKind::Error(_) => return write!(f, "an invalid token"),
+            TokenKind::Eof => return write!(f, "end of input"),
+            _ => OPERATORS.iter().find(|(_, kind, _)| kind == self).map(|(text, ..)| *text).expect("every other token is in `OPERATORS`"),
+        };
+        write!(f, "`{}`", sym)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Token { pub kind: TokenKind, pub span: (usize, usize) }
+
+/// Source between tokens that the parser ignores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TriviaKind { Whitespace, LineComment, BlockComment }
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trivia { pub kind: TriviaKind, pub span: (usize, usize) }
+
+pub struct Lexer<'a> {
+    input: Peekable<Chars<'a>>,
+    idx: usize,
+    /// An unterminated block comment, reported as the next token.
+    pending: Option<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self { input: src.chars().peekable(), idx: 0, pending: None }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.input.next()?;
+        self.idx += c.len_utf8();
+        Some(c)
+    }
+
+    fn peek(&mut self) -> Option<&char> { self.input.peek() }
+
+    /// Consume the next char if it is `c`.
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&c) { self.bump(); true } else { false }
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        while self.trivia().is_some() {}
+        self.token()
+    }
+
+    /// The whitespace or comment at the current position, if any. Whitespace
+    /// comes in runs that end at (and include) a newline.
+    pub fn trivia(&mut self) -> Option<Trivia> {
+        let start = self.idx;
+        let kind = match (self.peek().copied(), self.input.clone().nth(1)) {
+            (Some(c), _) if c.is_whitespace() => {
+                while let Some(&c) = self.peek() {
+                    if !c.is_whitespace() { break; }
+                    self.bump();
+                    if c == '\n' { break; }
+                }
+                TriviaKind::Whitespace
+            }
+            (Some('/'), Some('/')) => {
+                while let Some(&c) = self.peek() {
+                    if c == '\n' { break; }
+                    self.bump();
+                }
+                TriviaKind::LineComment
+            }
+            (Some('/'), Some('*')) => {
+                self.bump();
+                self.bump();
+                if !self.block_comment() {
+                    self.pending = Some(Token { kind: TokenKind::Error("Unterminated block comment".into()), span: (start, self.idx) });
+                    return None;
+                }
+                TriviaKind::BlockComment
+            }
+            _ => return None,
+        };
+        Some(Trivia { kind, span: (start, self.idx) })
+    }
+
+    /// The token at the current position, which must not be trivia.
+    pub fn token(&mut self) -> Token {
+        if let Some(t) = self.pending.take() { return t; }
+        let start = self.idx;
+        let ahead: String = self.input.clone().take(3).collect();
+        if let Some((text, kind, _)) = OPERATORS.iter().find(|(text, ..)| ahead.starts_with(text)) {
+            for _ in 0..text.len() { self.bump(); }
+            return Token { kind: kind.clone(), span: (start, self.idx) };
+        }
+
+        let kind = match self.bump() {
+            Some(q @ ('"' | '\'')) => self.string(q),
+            Some(c) if c.is_ascii_digit() => self.number(c),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&d) = self.peek() {
+                    if d.is_alphanumeric() || d == '_' { ident.push(self.bump().unwrap()); }
+                    else { break; }
+                }
+                if is_keyword(&ident) { TokenKind::Keyword(ident) } else { TokenKind::Ident(ident) }
+            }
+            Some(c)   => TokenKind::Error(format!("Unexpected character `{}`", c.escape_debug())),
+            None      => TokenKind::Eof,
+        };
+
+        let end = self.idx;
+        Token { kind, span: (start, end) }
+    }
+
+    /// Skip the rest of a `/* */` comment, which may nest. False if it never ends.
+    fn block_comment(&mut self) -> bool {
+        let mut depth = 1;
+        while let Some(c) = self.bump() {
+            if c == '/' && self.eat('*') { depth += 1; }
+            else if c == '*' && self.eat('/') {
+                depth -= 1;
+                if depth == 0 { return true; }
+            }
+        }
+        false
+    }
+
+    /// A string literal quoted with `q`, after the opening quote. Escapes are
+    /// decoded; on a bad escape the rest of the literal is still consumed.
+    fn string(&mut self, q: char) -> TokenKind {
+        let mut s = String::new();
+        let mut error = None;
+        loop {
+            match self.bump() {
+                None => return TokenKind::Error("Unterminated string literal".into()),
+                Some(c) if c == q => break,
+                Some('\\') => match self.escape() {
+                    Ok(c) => s.push(c),
+                    Err(e) => { error.get_or_insert(e); }
+                },
+                Some(c) => s.push(c),
+            }
+        }
+        match error {
+            Some(e) => TokenKind::Error(e),
+            None => TokenKind::StringLiteral(s),
+        }
+    }
+
+    /// The character an escape sequence stands for, after the backslash.
+    fn escape(&mut self) -> Result<char, String> {
+        Ok(match self.bump() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            Some('u') => {
+                if !self.eat('{') { return Err("Expected `{` after `\\u` in escape".into()); }
+                let mut hex = String::new();
+                while let Some(&c) = self.peek() {
+                    if c == '}' || c == '"' || c == '\'' || hex.len() > 6 { break; }
+                    hex.push(c);
+                    self.bump();
+                }
+                if !self.eat('}') { return Err("Unterminated `\\u{...}` escape".into()); }
+                u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                    .ok_or_else(|| format!("`\\u{{{}}}` is not a valid Unicode scalar value", hex))?
+            }
+            Some(c) => return Err(format!("Unknown escape `\\{}` in string literal", c.escape_debug())),
+            None => return Err("Unterminated string literal".into()),
+        })
+    }
+
+    /// A number starting with `first`: decimal with optional fraction and
+    /// exponent, or `0x`/`0b` integers. `_` may separate digits. The token keeps
+    /// the source text; the parser computes the value.
+    fn number(&mut self, first: char) -> TokenKind {
+        let mut text = first.to_string();
+        let radix = match (first, self.peek()) {
+            ('0', Some('x' | 'X')) => 16,
+            ('0', Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            text.push(self.bump().unwrap());
+            self.digits(&mut text, radix);
+            if !text[2..].chars().any(|c| c.is_digit(radix)) {
+                return self.malformed(text, "has no digits");
+            }
+        } else {
+            self.digits(&mut text, 10);
+            if self.peek() == Some(&'.') && self.input.clone().nth(1).is_some_and(|c| c.is_ascii_digit()) {
+                text.push(self.bump().unwrap());
+                self.digits(&mut text, 10);
+            }
+            if let Some(&e @ ('e' | 'E')) = self.peek() {
+                text.push(e);
+                self.bump();
+                if let Some(&sign @ ('+' | '-')) = self.peek() { text.push(sign); self.bump(); }
+                let exp_start = text.len();
+                self.digits(&mut text, 10);
+                if !text[exp_start..].chars().any(|c| c.is_ascii_digit()) {
+                    return self.malformed(text, "has an exponent without digits");
+                }
+            }
+        }
+        // Anything glued to the literal (`1.2.3`, `0b12`, `5px`) makes it malformed.
+        let before = text.len();
+        while let Some(&c) = self.peek() {
+            let fraction = c == '.' && self.input.clone().nth(1).is_some_and(|c| c.is_ascii_digit());
+            if !(c.is_alphanumeric() || c == '_' || fraction) { break; }
+            text.push(c);
+            self.bump();
+        }
+        if text.len() > before { return self.malformed(text, "is not a valid number"); }
+        TokenKind::Number(text)
+    }
+
+    fn digits(&mut self, text: &mut String, radix: u32) {
+        while let Some(&c) = self.peek() {
+            if !(c.is_digit(radix) || c == '_') { break; }
+            text.push(c);
+            self.bump();
+        }
+    }
+
+    fn malformed(&self, text: String, why: &str) -> TokenKind {
+        TokenKind::Error(format!("Number literal `{}` {}", text, why))
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token;
+    fn next(&mut self) -> Option<Self::Item> {
+        let t = self.next_token();
+        if t.kind == TokenKind::Eof { None } else { Some(t) }
+    }
+}
@@ -0,0 +1,31 @@
+//! Mintora: the Paradime smart-contract language. Source goes through the
+//! lexer and parser into a concrete syntax tree (`syntax`), is lowered to the
+//! AST (`ast`), type-checked (`compiler`) and compiled to WebAssembly
+//! (`codegen`), with its interface described by `abi`. `Session` runs that
+//! pipeline for embedders, `chain` runs the compiled modules on a simulated
+//! chain, and `lsp` serves editors.
+
+pub mod abi;
+pub mod analysis;
+pub mod ast;
+pub mod chain;
+pub mod codegen;
+pub mod compiler;
+pub mod diagnostic;
+pub mod fmt;
+pub mod host;
+pub mod lexer;
+pub mod lsp;
+pub mod parser;
+pub mod runtime;
+mod session;
+pub mod span;
+pub mod syntax;
+pub mod testing;
+pub mod vscode;
+pub mod wasm;
+
+pub use session::{Artifact, Emit, Outcome, Session};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,243 @@
+//! A Language Server Protocol server over stdio, behind `mintora lsp`. It keeps
+//! the open documents in memory and answers every request from a fresh parse:
+//! diagnostics whenever a document changes, hovers with inferred types,
+//! go-to-definition, the document outline and completion.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::analysis::{self, Analysis, SymbolKind};
+use crate::ast::Span;
+use crate::compiler::type_check;
+use crate::diagnostic::Diagnostic;
+use crate::span::FileId;
+use crate::syntax;
+
+/// JSON-RPC error codes.
+mod errors {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+}
+
+#[derive(Debug, Default)]
+pub struct Server {
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+    shut_down: bool,
+}
+
+/// Serve until the client sends `exit` or closes the input. Returns whether it
+/// asked the server to shut down first, as a clean exit requires.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+    let mut server = Server::new();
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match serde_json::from_slice::<Value>(&body) {
+            Ok(message) if message["method"] == "exit" => return Ok(server.shut_down),
+            Ok(message) => server.handle(&message),
+            Err(e) => vec![error(Value::Null, errors::PARSE_ERROR, &e.to_string())],
+        };
+        for reply in replies { write_message(&mut output, &reply)?; }
+    }
+    Ok(false)
+}
+
+/// The body of the next `Content-Length`-framed message, or `None` at the end of the input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 { return Ok(None); }
+        let line = line.trim_end();
+        if line.is_empty() { break; }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") { length = value.trim().parse().ok(); }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "message without a `Content-Length` header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// The LSP position of a byte offset. Lines are 0-based and columns count
+/// UTF-16 code units, as the protocol requires.
+pub fn position(src: &str, offset: usize) -> Value {
+    let before = &src[..offset.min(src.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({ "line": before.matches('\n').count(), "character": before[line_start..].encode_utf16().count() })
+}
+
+/// The byte offset of an LSP position, clamped to its line.
+pub fn offset(src: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let line_start = match line {
+        0 => 0,
+        _ => src.match_indices('\n').nth(line - 1).map_or(src.len(), |(i, _)| i + 1),
+    };
+    let mut units = 0;
+    for (i, c) in src[line_start..].char_indices() {
+        if units >= character || c == '\n' { return line_start + i; }
+        units += c.len_utf16();
+    }
+    src.len()
+}
+
+fn range(src: &str, span: Span) -> Value {
+    json!({ "start": position(src, span.start), "end": position(src, span.end) })
+}
+
+/// A diagnostic at its primary label. Notes follow the message and secondary
+/// labels become related information.
+fn lsp_diagnostic(src: &str, uri: &str, d: &Diagnostic) -> Value {
+    let primary = d.labels.iter().find(|l| l.primary).map_or(Span::default(), |l| l.span);
+    let message = d.notes.iter().fold(d.message.clone(), |m, note| format!("{}\nnote: {}", m, note));
+    let related: Vec<Value> = d.labels.iter().filter(|l| !l.primary).map(|l| json!({
+        "location": { "uri": uri, "range": range(src, l.span) },
+        "message": l.message,
+    })).collect();
+    json!({ "range": range(src, primary), "severity": 1, "code": d.code, "source": "mintora", "message": message, "relatedInformation": related })
+}
+
+/// Syntax errors, or if there are none the first type error.
+pub fn diagnostics(src: &str) -> Vec<Diagnostic> {
+    let (tree, diagnostics) = syntax::parse(src, FileId(0));
+    if !diagnostics.is_empty() { return diagnostics; }
+    type_check(&syntax::lower(&tree)).err().into_iter().collect()
+}
+
+fn analyze(src: &str) -> Analysis {
+    analysis::analyze(&syntax::lower(&syntax::parse(src, FileId(0)).0))
+}
+
+/// `SymbolKind` and `CompletionItemKind` numbers from the protocol.
+fn symbol_kind(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Contract => 5,
+        SymbolKind::Function => 12,
+        SymbolKind::Storage => 8,
+        SymbolKind::Event => 24,
+        SymbolKind::Param | SymbolKind::Local | SymbolKind::Builtin | SymbolKind::Keyword => 13,
+    }
+}
+
+fn completion_kind(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Contract => 7,
+        SymbolKind::Function => 3,
+        SymbolKind::Storage => 5,
+        SymbolKind::Event => 23,
+        SymbolKind::Param | SymbolKind::Local => 6,
+        SymbolKind::Builtin => 10,
+        SymbolKind::Keyword => 14,
+    }
+}
+
+fn document_symbol(src: &str, symbol: &analysis::Symbol) -> Value {
+    json!({
+        "name": symbol.name,
+        "detail": symbol.detail,
+        "kind": symbol_kind(symbol.kind),
+        "range": range(src, symbol.span),
+        "selectionRange": range(src, symbol.name_span),
+        "children": symbol.children.iter().map(|c| document_symbol(src, c)).collect::<Vec<_>>(),
+    })
+}
+
+impl Server {
+    pub fn new() -> Self { Self::default() }
+
+    /// Handle one request or notification other than `exit`, returning the
+    /// messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let id = message.get("id").cloned();
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+        if let (Some(id), true) = (&id, self.shut_down) {
+            return vec![error(id.clone(), errors::INVALID_REQUEST, "the server is shutting down")];
+        }
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                },
+                "serverInfo": { "name": "mintora", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+                return vec![self.publish(&uri)];
+            }
+            "textDocument/didChange" => {
+                // Documents are synced in full, so the last change is the whole text.
+                if let Some(text) = params["contentChanges"].as_array().and_then(|c| c.last()).and_then(|c| c["text"].as_str()) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                return vec![self.publish(&uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))];
+            }
+            "textDocument/hover" | "textDocument/definition" | "textDocument/documentSymbol" | "textDocument/completion" => {
+                let src = self.documents.get(&uri).map_or("", String::as_str);
+                let analysis = analyze(src);
+                let at = offset(src, &params["position"]);
+                match method {
+                    "textDocument/hover" => analysis.hover(at).map_or(Value::Null, |(span, text)| json!({
+                        "contents": { "kind": "markdown", "value": format!("```mintora\n{}\n```", text) },
+                        "range": range(src, span),
+                    })),
+                    "textDocument/definition" => analysis.definition(at)
+                        .map_or(Value::Null, |span| json!({ "uri": uri, "range": range(src, span) })),
+                    "textDocument/documentSymbol" => analysis.symbols.iter().map(|s| document_symbol(src, s)).collect(),
+                    _ => analysis.completions(src, at).into_iter()
+                        .map(|c| json!({ "label": c.label, "kind": completion_kind(c.kind), "detail": c.detail }))
+                        .collect(),
+                }
+            }
+            _ => match id {
+                Some(id) => return vec![error(id, errors::METHOD_NOT_FOUND, &format!("unsupported method `{}`", method))],
+                // Notifications the server has no use for, such as `initialized`.
+                None => return Vec::new(),
+            },
+        };
+        id.map(|id| response(id, result)).into_iter().collect()
+    }
+
+    fn publish(&self, uri: &str) -> Value {
+        let src = self.documents.get(uri).map_or("", String::as_str);
+        let diagnostics: Vec<Value> = diagnostics(src).iter().map(|d| lsp_diagnostic(src, uri, d)).collect();
+        notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))
+    }
+}
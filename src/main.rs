@@ -1,37 +1,330 @@
-mod ast;
-mod lexer;
-mod parser;
-mod compiler;
-
+use mintora::diagnostic::{codes, Diagnostic};
+use mintora::span::{line_col, FileId};
+use mintora::ast::{Param, StmtKind};
+use mintora::chain::{Chain, Message};
+use mintora::wasm::interp::Value;
+use mintora::abi::{self, Token};
+use mintora::{lsp, syntax, Emit, Outcome, Session};
+use serde_json::json;
 use std::env;
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+/// Exit statuses, one per class of failure.
+mod status {
+    /// The source has errors, a test failed, or `fmt --check` found unformatted files.
+    pub const ERRORS: i32 = 1;
+    pub const USAGE: i32 = 2;
+    /// A file could not be read or written.
+    pub const IO: i32 = 3;
+    /// `run` could not execute the function, or it trapped.
+    pub const RUN: i32 = 4;
+}
+
+const USAGE: &str = "\
+Usage: mintora <command> [options] <source>.mint...
+
+Commands:
+  build    Compile to WASM (the default when the first argument is a file)
+  check    Report errors without writing anything
+  fmt      Format files in place
+  ast      Print the syntax tree
+  tokens   Print the tokens
+  run      Compile and call a function: mintora run <source>.mint <function> [args...]
+  test     Run the tests in the given files
+  lsp      Serve the Language Server Protocol on stdin and stdout
+
+Options:
+  -o <file>         Where `build` writes the WASM module (default: next to the source);
+                    other outputs go next to it
+  --emit=<kinds>    What `build` writes, comma-separated: wasm, wat, ast, abi
+                    (default: wasm)
+  --dispatcher      Also export `call`, which dispatches ABI-encoded calldata to the
+                    functions; `run` then calls through it
+  --check           With `fmt`: only report unformatted files
+  --quiet           Print nothing but errors
+  --message-format=json
+                    Print diagnostics, progress, ASTs and tokens as JSON objects,
+                    one per line on stdout, each with a `type` field
+
+Exit status: 0 on success, 1 if the source has errors or a test fails,
+2 on bad usage, 3 if a file cannot be read or written, 4 if `run` fails.";
+
+struct Options {
+    command: String,
+    /// Source files, then for `run` the function name and its arguments.
+    inputs: Vec<String>,
+    out: Option<String>,
+    emit: Vec<Emit>,
+    dispatcher: bool,
+    check: bool,
+    quiet: bool,
+    json: bool,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: mintora <source>.mint [out.wasm]");
-        std::process::exit(1);
-    }
-    let path = &args[1];
-    let out_path = if args.len() >= 3 { args[2].clone() } else { "out.wasm".to_string() };
-    let src = fs::read_to_string(path).expect("Failed to read source file");
-
-    let lex = lexer::Lexer::new(&src);
-    let mut p = parser::Parser::new(lex);
-    let program = match p.parse() {
-        Ok(prog) => prog,
-        Err(e) => { eprintln!("[ParseError] {}", e); std::process::exit(1); }
+    let opts = parse_args(env::args().skip(1).collect());
+    let mut session = Session::new().with_dispatcher(opts.dispatcher);
+    match opts.command.as_str() {
+        "build" => build(&opts, &mut session),
+        "check" => check(&opts, &mut session),
+        "fmt" => format_files(&opts, &mut session),
+        "ast" => for path in &opts.inputs {
+            let program = load(path, &mut session).and_then(|file| session.check(file)).unwrap_or_else(|ds| fail(ds, &session, &opts));
+            if opts.json { println!("{}", json!({ "type": "ast", "path": path, "ast": program })); }
+            else { println!("{:#?}", program); }
+        },
+        "tokens" => for path in &opts.inputs {
+            let file = load(path, &mut session).unwrap_or_else(|ds| fail(ds, &session, &opts));
+            let src = session.source(file);
+            let tokens = syntax::tokenize(src, file);
+            if opts.json { println!("{}", json!({ "type": "tokens", "path": path, "tokens": tokens })); continue; }
+            for token in tokens {
+                let (line, col) = line_col(src, token.span.start);
+                println!("{}:{}:{} {:?}", path, line, col, token.kind);
+            }
+        },
+        "run" => run(&opts, &mut session),
+        "test" => test(&opts, &mut session),
+        "lsp" => match lsp::run(io::stdin().lock(), io::stdout().lock()) {
+            Ok(clean) => exit(if clean { 0 } else { status::ERRORS }),
+            Err(e) => {
+                eprintln!("error: the language server stopped: {}", e);
+                exit(status::IO);
+            }
+        },
+        _ => unreachable!("parse_args only accepts known commands"),
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Options {
+    const COMMANDS: &[&str] = &["build", "check", "fmt", "ast", "tokens", "run", "test", "lsp"];
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek() {
+        None => { eprintln!("{}", USAGE); exit(status::USAGE); }
+        Some(a) if COMMANDS.contains(&a.as_str()) => args.next().unwrap(),
+        Some(a) if a.starts_with('-') || a.ends_with(".mint") => "build".to_string(),
+        Some(a) => usage(&format!("unknown command `{}`", a)),
     };
+    let mut opts = Options { command, inputs: Vec::new(), out: None, emit: vec![Emit::Wasm], dispatcher: false, check: false, quiet: false, json: false };
+    while let Some(arg) = args.next() {
+        // Past `run`'s function name everything is an argument, including negative numbers.
+        let positional = opts.command == "run" && opts.inputs.len() >= 2;
+        match arg.as_str() {
+            _ if positional || !arg.starts_with('-') => opts.inputs.push(arg),
+            "-o" => opts.out = Some(args.next().unwrap_or_else(|| usage("`-o` needs a file name"))),
+            "--dispatcher" => opts.dispatcher = true,
+            "--check" => opts.check = true,
+            "--quiet" | "-q" => opts.quiet = true,
+            "-h" | "--help" => { println!("{}", USAGE); exit(0); }
+            "--message-format=json" => opts.json = true,
+            "--message-format=human" => opts.json = false,
+            _ => match arg.strip_prefix("--emit=") {
+                Some(kinds) => opts.emit = kinds.split(',').map(|k| {
+                    Emit::from_name(k).unwrap_or_else(|| usage(&format!("cannot emit `{}`; expected wasm, wat, ast or abi", k)))
+                }).collect(),
+                None if arg.starts_with("--message-format=") => usage("`--message-format` is `human` or `json`"),
+                None => usage(&format!("unknown option `{}`", arg)),
+            },
+        }
+    }
+    if opts.command == "lsp" {
+        if !opts.inputs.is_empty() { usage("`lsp` takes no source files; the editor sends them"); }
+        return opts;
+    }
+    if opts.inputs.is_empty() { usage("no source file given"); }
+    if opts.out.is_some() && (opts.command != "build" || opts.inputs.len() > 1) {
+        usage("`-o` needs `build` with a single source file");
+    }
+    if opts.check && opts.command != "fmt" { usage("`--check` only applies to `fmt`"); }
+    if opts.dispatcher && !["build", "check", "run"].contains(&opts.command.as_str()) {
+        usage("`--dispatcher` only applies to `build`, `check` and `run`");
+    }
+    // The function's own output is not JSON.
+    if opts.json && opts.command == "run" { usage("`run` does not support `--message-format=json`"); }
+    opts
+}
 
-    println!("=== AST ===\n{}", compiler::pretty(&program));
+fn usage(message: &str) -> ! {
+    eprintln!("error: {}\n\nFor usage, run `mintora --help`.", message);
+    exit(status::USAGE);
+}
+
+fn load(path: &str, session: &mut Session) -> Outcome<FileId> {
+    session.load(path).map_err(|d| vec![d])
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), Diagnostic> {
+    fs::write(path, bytes).map_err(|e| Diagnostic::error(codes::IO, format!("Cannot write `{}`: {}", path.display(), e)))
+}
 
-    if let Err(e) = compiler::type_check(&program) {
-        eprintln!("[TypeError] {}", e);
-        std::process::exit(1);
+fn build(opts: &Options, session: &mut Session) {
+    let mut errors = Vec::new();
+    for path in &opts.inputs {
+        let outcome = load(path, session).and_then(|file| session.emit(file, &opts.emit)).and_then(|artifacts| {
+            let wasm_path = opts.out.as_ref().map_or_else(|| Path::new(path).with_extension("wasm"), PathBuf::from);
+            for artifact in artifacts {
+                // `-o` names the module exactly; the other outputs take its stem.
+                let out = if artifact.emit == Emit::Wasm { wasm_path.clone() } else { wasm_path.with_extension(artifact.emit.extension()) };
+                write(&out, &artifact.contents).map_err(|d| vec![d])?;
+                report(opts, format!("[Mintora] Wrote {}", out.display()), json!({ "type": "artifact", "path": out, "emit": artifact.emit }));
+            }
+            Ok(())
+        });
+        errors.extend(outcome.err().unwrap_or_default());
     }
+    if !errors.is_empty() { fail(errors, session, opts); }
+}
+
+/// Report every error `build` would, which takes a full compile: codegen still
+/// rejects programs that type-check, such as a function named `call` under
+/// `--dispatcher` or a `string` storage field.
+fn check(opts: &Options, session: &mut Session) {
+    let mut errors = Vec::new();
+    for path in &opts.inputs {
+        match load(path, session).and_then(|file| session.compile(file)) {
+            Ok(_) => report(opts, format!("[Mintora] {}: no errors", path), json!({ "type": "checked", "path": path })),
+            Err(ds) => errors.extend(ds),
+        }
+    }
+    if !errors.is_empty() { fail(errors, session, opts); }
+}
+
+/// Rewrite each file in the canonical style. With `--check`, only report the
+/// files that are not formatted and exit with status 1 if there are any.
+fn format_files(opts: &Options, session: &mut Session) {
+    let mut unformatted = 0;
+    for path in &opts.inputs {
+        let file = load(path, session).unwrap_or_else(|ds| fail(ds, session, opts));
+        let formatted = session.format(file).unwrap_or_else(|ds| fail(ds, session, opts));
+        let src = session.source(file);
+        if formatted == src { continue; }
+        if opts.check {
+            let line = src.lines().zip(formatted.lines()).take_while(|(a, b)| a == b).count();
+            let (old, new) = (src.lines().nth(line), formatted.lines().nth(line));
+            if opts.json {
+                println!("{}", json!({ "type": "unformatted", "path": path, "line": line + 1, "old": old, "new": new }));
+            } else {
+                println!("Diff in {} at line {}:", path, line + 1);
+                if let Some(old) = old { println!("-{}", old); }
+                if let Some(new) = new { println!("+{}", new); }
+            }
+            unformatted += 1;
+        } else {
+            write(Path::new(path), formatted.as_bytes()).unwrap_or_else(|d| fail(vec![d], session, opts));
+            report(opts, format!("[Mintora] Formatted {}", path), json!({ "type": "formatted", "path": path }));
+        }
+    }
+    if unformatted > 0 { exit(status::ERRORS); }
+}
+
+/// Deploy the file to a fresh simulated chain, call one function and print its result.
+fn run(opts: &Options, session: &mut Session) {
+    let [path, name, args @ ..] = &opts.inputs[..] else { usage("`run` needs a source file and a function name") };
+    let file = load(path, session).unwrap_or_else(|ds| fail(ds, session, opts));
+    let program = session.check(file).unwrap_or_else(|ds| fail(ds, session, opts));
+    let bytes = session.codegen(&program).unwrap_or_else(|ds| fail(ds, session, opts));
+    let Some(StmtKind::Function { params, return_type, .. }) = program.function(name).map(|f| &f.kind) else {
+        usage(&format!("`{}` has no function `{}`", path, name))
+    };
+    let tokens = arguments(name, params, args);
+
+    let mut chain = Chain::new();
+    let sender = chain.create_account(0);
+    let contract = chain.deploy(&bytes).expect("the compiler emits valid modules");
+    let msg = Message { sender, value: 0 };
+    if opts.dispatcher {
+        let calldata = abi::encode_call(abi::selector(&abi::canonical(name, params)), &tokens);
+        let output: Vec<&str> = return_type.iter().map(|t| t.as_str()).filter(|t| *t != "void").collect();
+        match chain.transact(contract, msg, &calldata) {
+            Ok(data) => {
+                let result = abi::decode(&output, &data).expect("`call` encodes results as `abi` describes");
+                if let Some(value) = result.first() { println!("{}", value); }
+            }
+            Err(trap) => {
+                eprintln!("error: `{}` trapped: {}", name, trap);
+                exit(status::RUN);
+            }
+        }
+        return;
+    }
+    let values: Vec<Value> = tokens.into_iter().map(|token| match token {
+        Token::I32(v) => Value::I32(v),
+        Token::I64(v) => Value::I64(v),
+        Token::F64(v) => Value::F64(v),
+        Token::Bool(b) => Value::I32(b as i32),
+        Token::String(_) => usage("`run` can only pass `string` arguments with `--dispatcher`"),
+    }).collect();
+    let result = chain.call(contract, msg, name, &values).and_then(|value| {
+        Ok(match (return_type.as_ref().map(|t| t.as_str()), value) {
+            (_, None) => None,
+            (Some("bool"), Some(Value::I32(b))) => Some((b != 0).to_string()),
+            (Some("string"), Some(Value::I64(s))) => Some(chain.string(contract, s)?),
+            (_, Some(value)) => Some(value.to_string()),
+        })
+    });
+    match result {
+        Ok(output) => if let Some(output) = output { println!("{}", output); },
+        Err(trap) => {
+            eprintln!("error: `{}` trapped: {}", name, trap);
+            exit(status::RUN);
+        }
+    }
+}
+
+/// Parse `run`'s arguments as the parameters' types. Untyped parameters are
+/// `i32`s, as codegen and the ABI treat them.
+fn arguments(name: &str, params: &[Param], args: &[String]) -> Vec<Token> {
+    if params.len() != args.len() {
+        usage(&format!("`{}` takes {} argument(s) but {} were given", name, params.len(), args.len()));
+    }
+    params.iter().zip(args).map(|(p, a)| {
+        let ty = p.ty.as_ref().map_or("i32", |t| t.as_str());
+        Token::parse(ty, a).unwrap_or_else(|| usage(&format!("`{}` is not a valid `{}` argument", a, ty)))
+    }).collect()
+}
+
+/// Run the `#[test]` functions of each file, reporting each one, and exit with
+/// status 1 if any failed.
+fn test(opts: &Options, session: &mut Session) {
+    let (mut passed, mut failed) = (0, 0);
+    for path in &opts.inputs {
+        let results = load(path, session).and_then(|file| session.test(file)).unwrap_or_else(|ds| fail(ds, session, opts));
+        report(opts, format!("\nrunning {} test(s) in {}", results.len(), path), json!({ "type": "test_file", "path": path, "tests": results.len() }));
+        for result in &results {
+            let status = if result.passed() { "ok" } else { "FAILED" };
+            let failure = result.failure.as_ref().map(|d| d.to_json(session.sources()));
+            report(opts, format!("test {} ... {}", result.name, status), json!({ "type": "test", "path": path, "name": result.name, "passed": result.passed(), "failure": failure }));
+        }
+        let failures: Vec<Diagnostic> = results.iter().filter_map(|r| r.failure.clone()).collect();
+        if !opts.json {
+            for failure in &failures { print!("\n{}", session.render(std::slice::from_ref(failure))); }
+        }
+        passed += results.iter().filter(|r| r.passed()).count();
+        failed += failures.len();
+    }
+    let outcome = if failed == 0 { "ok" } else { "FAILED" };
+    report(opts, format!("\ntest result: {}. {} passed; {} failed", outcome, passed, failed), json!({ "type": "test_result", "passed": passed, "failed": failed }));
+    if failed > 0 { exit(status::ERRORS); }
+}
+
+/// Progress output, silenced by `--quiet`.
+fn report(opts: &Options, text: String, json: serde_json::Value) {
+    if opts.quiet { return; }
+    if opts.json { println!("{}", json); } else { println!("{}", text); }
+}
 
-    match compiler::compile_to_wasm(&program) {
-        Ok(bytes) => { fs::write(&out_path, &bytes).expect("Failed to write WASM file"); println!("[Mintora] Wrote {}", out_path); }
-        Err(e) => { eprintln!("[CompileError] {}", e); std::process::exit(1); }
+fn fail(diagnostics: Vec<Diagnostic>, session: &Session, opts: &Options) -> ! {
+    if opts.json {
+        for d in &diagnostics {
+            let mut value = d.to_json(session.sources());
+            value["type"] = "diagnostic".into();
+            println!("{}", value);
+        }
+    } else {
+        eprint!("{}", session.render(&diagnostics));
     }
+    exit(if diagnostics.iter().any(|d| d.code == codes::IO) { status::IO } else { status::ERRORS });
 }
@@ -1,144 +1,609 @@
-use crate::ast::{Expr, Param, Program, Statement, BinOp};
-use crate::lexer::{Lexer, Token, TokenKind};
-
-pub struct Parser {
-    tokens: Vec<Token>,
-    pos: usize,
-}
-
-impl Parser {
-    pub fn new(lexer: Lexer) -> Self {
-        Self { tokens: lexer.collect(), pos: 0 }
-    }
-
-    #[inline] fn at_end(&self) -> bool { self.pos >= self.tokens.len() }
-    #[inline] fn peek(&self) -> &Token { &self.tokens[self.pos] }
-    #[inline] fn bump(&mut self) -> Token {
-        let t = self.tokens[self.pos].clone();
-        self.pos += 1;
-        t
-    }
-
-    pub fn parse(&mut self) -> Result<Program, String> {
-        let mut statements = Vec::new();
-        while !self.at_end() {
-            if let Some(stmt) = self.parse_statement()? {
-                statements.push(stmt);
-            } else { break; }
-        }
-        Ok(Program { statements })
-    }
-
-    fn parse_statement(&mut self) -> Result<Option<Statement>, String> {
-        if self.at_end() { return Ok(None); }
-        match &self.peek().kind {
-            TokenKind::Keyword(k) if k == "fn" => Ok(Some(self.parse_function()?)),
-            TokenKind::Keyword(k) if k == "return" => {
-                self.bump();
-                let expr = self.parse_expression()?;
-                self.expect_semicolon()?;
-                Ok(Some(Statement::Return(expr)))
-            }
-            _ => Ok(None),
-        }
-    }
-
-    fn parse_function(&mut self) -> Result<Statement, String> {
-        self.bump(); // fn
-        let name = self.expect_ident("function name")?;
-        self.expect_symbol('(')?;
-        let params = self.parse_params()?;
-        self.expect_symbol(')')?;
-
-        let return_type = if let TokenKind::Arrow = &self.peek().kind {
-            self.bump();
-            Some(self.expect_ident("return type")?)
-        } else { None };
-
-        self.expect_symbol('{')?;
-        let mut body = Vec::new();
-        while !matches!(&self.peek().kind, TokenKind::CloseBrace) {
-            if let Some(stmt) = self.parse_statement()? { body.push(stmt); }
-            else { return Err(format!("Unexpected token in function body: {:?}", self.peek().kind)); }
-        }
-        self.expect_symbol('}')?;
-
-        Ok(Statement::Function { name, params, return_type, body })
-    }
-
-    fn parse_params(&mut self) -> Result<Vec<Param>, String> {
-        let mut params = Vec::new();
-        if matches!(&self.peek().kind, TokenKind::CloseParen) { return Ok(params); }
-        loop {
-            let name = self.expect_ident("parameter name")?;
-            let mut ty = None;
-            if let TokenKind::Symbol(':') = &self.peek().kind {
-                self.bump();
-                ty = Some(self.expect_ident("parameter type")?);
-            }
-            params.push(Param { name, ty });
-            if let TokenKind::Symbol(',') = &self.peek().kind { self.bump(); } else { break; }
-        }
-        Ok(params)
-    }
-
-    fn expect_ident(&mut self, what: &str) -> Result<String, String> {
-        if self.at_end() { return Err(format!("Expected {} but found <eof>", what)); }
-        match self.bump().kind {
-            TokenKind::Ident(id) => Ok(id),
-            other => Err(format!("Expected {} but found {:?}", what, other)),
-        }
-    }
-
-    fn expect_symbol(&mut self, sym: char) -> Result<(), String> {
-        if self.at_end() { return Err(format!("Expected `{}` but found <eof>", sym)); }
-        match &self.peek().kind {
-            TokenKind::Symbol(c) if *c == sym => { self.bump(); Ok(()) }
-            TokenKind::OpenBrace if sym == '{' => { self.bump(); Ok(()) }
-            TokenKind::CloseBrace if sym == '}' => { self.bump(); Ok(()) }
-            TokenKind::OpenParen if sym == '(' => { self.bump(); Ok(()) }
-            TokenKind::CloseParen if sym == ')' => { self.bump(); Ok(()) }
-            other => Err(format!("Expected symbol `{}` but found {:?}", sym, other)),
-        }
-    }
-
-    fn expect_semicolon(&mut self) -> Result<(), String> {
-        if self.at_end() { return Err("Expected `;` but found <eof>".into()); }
-        match &self.peek().kind {
-            TokenKind::Semicolon => { self.bump(); Ok(()) }
-            other => Err(format!("Expected `;` but found {:?}", other)),
-        }
-    }
-
-    // -------- expressions (support +) --------
-
-    fn parse_expression(&mut self) -> Result<Expr, String> {
-        let left = self.parse_primary()?;
-        self.parse_binop_rhs(left)
-    }
-
-    fn parse_primary(&mut self) -> Result<Expr, String> {
-        if self.at_end() { return Err("Unexpected end of input in expression".into()); }
-        match &self.peek().kind {
-            TokenKind::Number(n) => { let v: f64 = n.parse().map_err(|_| "Invalid number")?; self.bump(); Ok(Expr::Number(v)) }
-            TokenKind::StringLiteral(s) => { let lit = s.clone(); self.bump(); Ok(Expr::StringLiteral(lit)) }
-            TokenKind::Ident(id) => { let name = id.clone(); self.bump(); Ok(Expr::Ident(name)) }
-            other => Err(format!("Unexpected token in expression: {:?}", other)),
-        }
-    }
-
-    fn parse_binop_rhs(&mut self, mut left: Expr) -> Result<Expr, String> {
-        loop {
-            if self.at_end() { break; }
-            match &self.peek().kind {
-                TokenKind::Symbol('+') => {
-                    self.bump();
-                    let right = self.parse_primary()?;
-                    left = Expr::Binary { op: BinOp::Add, left: Box::new(left), right: Box::new(right) };
-                }
-                _ => break,
-            }
-        }
-        Ok(left)
-    }
-}
+use crate::ast::{Attribute, BinOp, EventDecl, Expr, ExprKind, FileId, Ident, Param, Program, Span, Statement, StmtKind, StorageField, UnaryOp};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::lexer::{Lexer, Token, TokenKind};
+use crate::syntax::NodeKind;
+
+type PResult<T> = Result<T, Diagnostic>;
+
+pub struct Parser {
+    /// Always ends with an `Eof` token, so `peek` is valid at the end of input.
+    tokens: Vec<Token>,
+    pos: usize,
+    file: FileId,
+    /// Errors recovered from so far, in source order.
+    diagnostics: Vec<Diagnostic>,
+    /// Every syntax node parsed so far as `(kind, first token, end token)`, in
+    /// the order they were completed; `syntax` builds the concrete tree from them.
+    nodes: Vec<(NodeKind, usize, usize)>,
+}
+
+impl Parser {
+    pub fn new(mut lexer: Lexer) -> Self {
+        let mut tokens = Vec::new();
+        loop {
+            let t = lexer.next_token();
+            let eof = t.kind == TokenKind::Eof;
+            tokens.push(t);
+            if eof { break; }
+        }
+        Self { tokens, pos: 0, file: FileId::default(), diagnostics: Vec::new(), nodes: Vec::new() }
+    }
+
+    /// The syntax nodes recorded by the last parse.
+    pub(crate) fn take_nodes(&mut self) -> Vec<(NodeKind, usize, usize)> {
+        std::mem::take(&mut self.nodes)
+    }
+
+    /// Tag every span with `file` (the default is file 0).
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
+
+    #[inline] fn at_end(&self) -> bool { self.peek().kind == TokenKind::Eof }
+    #[inline] fn peek(&self) -> &Token { &self.tokens[self.pos] }
+    #[inline] fn check(&self, kind: &TokenKind) -> bool { &self.peek().kind == kind }
+    #[inline] fn peek_nth(&self, n: usize) -> Option<&Token> { self.tokens.get(self.pos + n) }
+    #[inline] fn bump(&mut self) -> Token {
+        let t = self.tokens[self.pos].clone();
+        if !self.at_end() { self.pos += 1; }
+        t
+    }
+
+    fn span_of(&self, tok: &Token) -> Span { Span::new(self.file, tok.span.0, tok.span.1) }
+
+    /// Span of the next token; node spans start here.
+    fn here(&self) -> Span { self.span_of(self.peek()) }
+
+    /// Record a syntax node spanning the tokens from index `from` up to here.
+    fn node(&mut self, kind: NodeKind, from: usize) {
+        self.nodes.push((kind, from, self.pos));
+    }
+
+    /// Span from `start` to the end of the last consumed token.
+    fn since(&self, start: Span) -> Span {
+        let end = if self.pos > 0 { self.tokens[self.pos - 1].span.1 } else { start.end };
+        Span { end: end.max(start.start), ..start }
+    }
+
+    /// "Expected X but found Y", pointing at the current token. A malformed
+    /// token reports what is wrong with it instead.
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        if let TokenKind::Error(msg) = &self.peek().kind {
+            return Diagnostic::error(codes::INVALID_TOKEN, msg.clone()).with_primary(self.here(), "");
+        }
+        Diagnostic::error(codes::UNEXPECTED_TOKEN, format!("Expected {} but found {}", expected, self.peek().kind))
+            .with_primary(self.here(), format!("expected {}", expected))
+    }
+
+    /// Parse the whole input, returning every syntax error if there were any.
+    pub fn parse(&mut self) -> Result<Program, Vec<Diagnostic>> {
+        let (program, diagnostics) = self.parse_recovering();
+        if diagnostics.is_empty() { Ok(program) } else { Err(diagnostics) }
+    }
+
+    /// Parse the whole input, recovering from syntax errors. Statements that
+    /// failed to parse appear as `StmtKind::Error` nodes in the returned program.
+    pub fn parse_recovering(&mut self) -> (Program, Vec<Diagnostic>) {
+        let mut statements = Vec::new();
+        while !self.at_end() {
+            statements.push(self.parse_statement_or_recover());
+        }
+        (Program { statements }, std::mem::take(&mut self.diagnostics))
+    }
+
+    /// Parse a statement; on failure record the error, skip to the next
+    /// statement boundary and stand in an error node for what was skipped.
+    fn parse_statement_or_recover(&mut self) -> Statement {
+        let (start, start_pos) = (self.here(), self.pos);
+        self.parse_statement().unwrap_or_else(|d| {
+            self.diagnostics.push(d);
+            self.synchronize(start_pos);
+            self.node(NodeKind::Error, start_pos);
+            Statement { kind: StmtKind::Error, span: self.since(start) }
+        })
+    }
+
+    /// Panic-mode recovery: skip past the next `;` or balanced `{ ... }`, stopping
+    /// before a `}` that closes an enclosing block or a keyword that starts a
+    /// new statement. Always consumes at least one token so parsing progresses.
+    fn synchronize(&mut self, start_pos: usize) {
+        let mut depth = 0usize;
+        let failed_at = self.pos;
+        while !self.at_end() {
+            match &self.peek().kind {
+                TokenKind::Semicolon if depth == 0 => { self.bump(); return; }
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => break,
+                TokenKind::CloseBrace => {
+                    depth -= 1;
+                    if depth == 0 { self.bump(); return; }
+                }
+                TokenKind::Keyword(k) if depth == 0 && self.pos > start_pos && STATEMENT_KEYWORDS.contains(&k.as_str()) => break,
+                // Lexical errors stand on their own, so they are worth reporting even while skipping.
+                TokenKind::Error(_) if self.pos > failed_at => {
+                    let d = self.unexpected("");
+                    self.diagnostics.push(d);
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+        if self.pos == start_pos { self.bump(); }
+    }
+
+    fn parse_statement(&mut self) -> PResult<Statement> {
+        let (start, from) = (self.here(), self.pos);
+        let kind = match &self.peek().kind {
+            TokenKind::Keyword(k) if k == "contract" => return self.parse_contract(),
+            TokenKind::Keyword(k) if k == "fn" => return self.parse_function(),
+            TokenKind::Hash => return self.parse_function(),
+            TokenKind::Keyword(k) if k == "return" => {
+                self.bump();
+                let expr = if self.check(&TokenKind::Semicolon) {
+                    None
+                } else {
+                    Some(self.parse_expression()?)
+                };
+                self.expect_semicolon()?;
+                self.node(NodeKind::Return, from);
+                StmtKind::Return(expr)
+            }
+            TokenKind::Keyword(k) if k == "let" => return self.parse_let(),
+            TokenKind::Keyword(k) if k == "if" => return self.parse_if(),
+            TokenKind::Keyword(k) if k == "while" => {
+                self.bump();
+                let cond = self.parse_expression()?;
+                let body = self.parse_block()?;
+                self.node(NodeKind::While, from);
+                StmtKind::While { cond, body }
+            }
+            TokenKind::Keyword(k) if k == "break" || k == "continue" => {
+                let (kind, node) = if k == "break" { (StmtKind::Break, NodeKind::Break) } else { (StmtKind::Continue, NodeKind::Continue) };
+                self.bump();
+                self.expect_semicolon()?;
+                self.node(node, from);
+                kind
+            }
+            TokenKind::Keyword(k) if k == "emit" || k == "revert" => {
+                let emit = k == "emit";
+                self.bump();
+                let name = self.expect_ident(if emit { "event name" } else { "error name" })?;
+                let args = self.parse_args()?;
+                self.expect_semicolon()?;
+                if emit {
+                    self.node(NodeKind::Emit, from);
+                    StmtKind::Emit { event: name, args }
+                } else {
+                    self.node(NodeKind::Revert, from);
+                    StmtKind::Revert { error: name, args }
+                }
+            }
+            TokenKind::OpenBrace => StmtKind::Block(self.parse_block()?),
+            TokenKind::Ident(_) if self.peek_nth(1).and_then(|t| assign_op_for(&t.kind)).is_some() => {
+                let name = self.expect_ident("assignment target")?;
+                let op = assign_op_for(&self.bump().kind).unwrap();
+                let value = self.parse_expression()?;
+                self.expect_semicolon()?;
+                self.node(NodeKind::Assign, from);
+                StmtKind::Assign { name, op, value }
+            }
+            TokenKind::Number(_) | TokenKind::StringLiteral(_) | TokenKind::Ident(_)
+            | TokenKind::OpenParen | TokenKind::Minus | TokenKind::Bang => return self.parse_expr_statement(),
+            TokenKind::Keyword(k) if k == "true" || k == "false" => return self.parse_expr_statement(),
+            _ => return Err(self.unexpected("a statement")),
+        };
+        Ok(Statement { kind, span: self.since(start) })
+    }
+
+    fn parse_let(&mut self) -> PResult<Statement> {
+        let (start, from) = (self.here(), self.pos);
+        self.bump(); // let
+        let mutable = self.at_keyword("mut");
+        if mutable { self.bump(); }
+        let name = self.expect_ident("variable name")?;
+        let mut ty = None;
+        if let TokenKind::Symbol(':') = &self.peek().kind {
+            self.bump();
+            ty = Some(self.expect_ident("variable type")?);
+        }
+        self.expect_symbol('=')?;
+        let value = self.parse_expression()?;
+        self.expect_semicolon()?;
+        self.node(NodeKind::Let, from);
+        Ok(Statement { kind: StmtKind::Let { name, mutable, ty, value }, span: self.since(start) })
+    }
+
+    fn parse_if(&mut self) -> PResult<Statement> {
+        let (start, from) = (self.here(), self.pos);
+        self.bump(); // if
+        let cond = self.parse_expression()?;
+        let then_body = self.parse_block()?;
+        let else_body = if self.at_keyword("else") {
+            self.bump();
+            if self.at_keyword("if") {
+                Some(vec![self.parse_if()?])
+            } else {
+                Some(self.parse_block()?)
+            }
+        } else { None };
+        self.node(NodeKind::If, from);
+        Ok(Statement { kind: StmtKind::If { cond, then_body, else_body }, span: self.since(start) })
+    }
+
+    fn parse_expr_statement(&mut self) -> PResult<Statement> {
+        let (start, from) = (self.here(), self.pos);
+        let expr = self.parse_expression()?;
+        self.expect_semicolon()?;
+        self.node(NodeKind::ExprStmt, from);
+        Ok(Statement { kind: StmtKind::Expr(expr), span: self.since(start) })
+    }
+
+    fn at_keyword(&self, kw: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Keyword(k) if k == kw)
+    }
+
+    /// Parse `{ stmt* }` and return the statements inside.
+    fn parse_block(&mut self) -> PResult<Vec<Statement>> {
+        let (open, from) = (self.here(), self.pos);
+        self.expect_symbol('{')?;
+        let mut body = Vec::new();
+        while !self.at_end() && !matches!(&self.peek().kind, TokenKind::CloseBrace) {
+            body.push(self.parse_statement_or_recover());
+        }
+        self.expect_symbol('}').map_err(|d| d.with_secondary(open, "block starts here"))?;
+        self.node(NodeKind::Block, from);
+        Ok(body)
+    }
+
+    fn parse_contract(&mut self) -> PResult<Statement> {
+        let (start, from) = (self.here(), self.pos);
+        self.bump(); // contract
+        let name = self.expect_ident("contract name")?;
+        let open = self.here();
+        self.expect_symbol('{')?;
+        let (mut fields, mut events, mut errors) = (Vec::new(), Vec::new(), Vec::new());
+        let mut functions = Vec::new();
+        while !self.check(&TokenKind::CloseBrace) {
+            let (member_start, start_pos) = (self.here(), self.pos);
+            let member = if self.at_keyword("storage") {
+                self.parse_storage_field().map(|f| fields.push(f))
+            } else if self.at_keyword("event") {
+                self.parse_event(NodeKind::EventDecl).map(|e| events.push(e))
+            } else if self.at_keyword("error") {
+                self.parse_event(NodeKind::ErrorDecl).map(|e| errors.push(e))
+            } else if self.at_keyword("fn") || self.check(&TokenKind::Hash) {
+                functions.push(self.parse_statement_or_recover());
+                Ok(())
+            } else if self.at_end() {
+                return Err(self.unexpected("`}`").with_secondary(open, format!("contract `{}` starts here", name)));
+            } else {
+                Err(self.unexpected("`storage`, `event`, `error` or `fn`")
+                    .with_note("contracts contain only storage fields, events, errors and functions"))
+            };
+            if let Err(d) = member {
+                self.diagnostics.push(d);
+                self.synchronize(start_pos);
+                self.node(NodeKind::Error, start_pos);
+                functions.push(Statement { kind: StmtKind::Error, span: self.since(member_start) });
+            }
+        }
+        self.expect_symbol('}')?;
+        self.node(NodeKind::Contract, from);
+        Ok(Statement { kind: StmtKind::Contract { name, fields, events, errors, functions }, span: self.since(start) })
+    }
+
+    /// `event Name(fields);` or, recorded as `ErrorDecl`, `error Name(fields);`.
+    fn parse_event(&mut self, node: NodeKind) -> PResult<EventDecl> {
+        let (start, from) = (self.here(), self.pos);
+        self.bump(); // event | error
+        let name = self.expect_ident(if node == NodeKind::EventDecl { "event name" } else { "error name" })?;
+        let params_from = self.pos;
+        self.expect_symbol('(')?;
+        let fields = self.parse_params()?;
+        self.expect_symbol(')')?;
+        self.node(NodeKind::ParamList, params_from);
+        self.expect_semicolon()?;
+        self.node(node, from);
+        Ok(EventDecl { name, fields, span: self.since(start) })
+    }
+
+    fn parse_storage_field(&mut self) -> PResult<StorageField> {
+        let (start, from) = (self.here(), self.pos);
+        self.bump(); // storage
+        let name = self.expect_ident("storage field name")?;
+        self.expect_symbol(':')?;
+        let ty = self.expect_ident("storage field type")?;
+        self.expect_semicolon()?;
+        self.node(NodeKind::StorageField, from);
+        Ok(StorageField { name, ty, span: self.since(start) })
+    }
+
+    /// A function with any `#[name]` attributes before it.
+    fn parse_function(&mut self) -> PResult<Statement> {
+        let (start, from) = (self.here(), self.pos);
+        let mut attributes = Vec::new();
+        while self.check(&TokenKind::Hash) {
+            let (attr_start, attr_from) = (self.here(), self.pos);
+            self.bump(); // #
+            self.expect_symbol('[')?;
+            let name = self.expect_ident("attribute name")?;
+            self.expect_symbol(']')?;
+            self.node(NodeKind::Attribute, attr_from);
+            attributes.push(Attribute { name, span: self.since(attr_start) });
+        }
+        if !self.at_keyword("fn") { return Err(self.unexpected("`fn`").with_note("attributes apply to functions")); }
+        self.bump(); // fn
+        let name = self.expect_ident("function name")?;
+        let params_from = self.pos;
+        self.expect_symbol('(')?;
+        let params = self.parse_params()?;
+        self.expect_symbol(')')?;
+        self.node(NodeKind::ParamList, params_from);
+
+        let return_type = if let TokenKind::Arrow = &self.peek().kind {
+            self.bump();
+            Some(self.expect_ident("return type")?)
+        } else { None };
+
+        let body = self.parse_block()?;
+        self.node(NodeKind::Function, from);
+
+        Ok(Statement { kind: StmtKind::Function { attributes, name, params, return_type, body }, span: self.since(start) })
+    }
+
+    /// Parse comma-separated parameters up to the closing `)`, which may
+    /// follow a trailing comma as in `parse_args`.
+    fn parse_params(&mut self) -> PResult<Vec<Param>> {
+        let mut params = Vec::new();
+        while !self.check(&TokenKind::CloseParen) {
+            let (start, from) = (self.here(), self.pos);
+            let name = self.expect_ident("parameter name")?;
+            let mut ty = None;
+            if let TokenKind::Symbol(':') = &self.peek().kind {
+                self.bump();
+                ty = Some(self.expect_ident("parameter type")?);
+            }
+            self.node(NodeKind::Param, from);
+            params.push(Param { name, ty, span: self.since(start) });
+            if self.check(&TokenKind::Symbol(',')) { self.bump(); } else { break; }
+        }
+        Ok(params)
+    }
+
+    fn expect_ident(&mut self, what: &str) -> PResult<Ident> {
+        match &self.peek().kind {
+            TokenKind::Ident(id) => {
+                let ident = Ident { name: id.clone(), span: self.here() };
+                self.bump();
+                Ok(ident)
+            }
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    fn expect_symbol(&mut self, sym: char) -> PResult<()> {
+        match &self.peek().kind {
+            TokenKind::Symbol(c) if *c == sym => { self.bump(); Ok(()) }
+            TokenKind::OpenBrace if sym == '{' => { self.bump(); Ok(()) }
+            TokenKind::CloseBrace if sym == '}' => { self.bump(); Ok(()) }
+            TokenKind::OpenParen if sym == '(' => { self.bump(); Ok(()) }
+            TokenKind::CloseParen if sym == ')' => { self.bump(); Ok(()) }
+            TokenKind::OpenBracket if sym == '[' => { self.bump(); Ok(()) }
+            TokenKind::CloseBracket if sym == ']' => { self.bump(); Ok(()) }
+            TokenKind::Assign if sym == '=' => { self.bump(); Ok(()) }
+            _ => Err(self.unexpected(&format!("`{}`", sym))),
+        }
+    }
+
+    fn expect_semicolon(&mut self) -> PResult<()> {
+        match &self.peek().kind {
+            TokenKind::Semicolon => { self.bump(); Ok(()) }
+            TokenKind::Error(_) => Err(self.unexpected("`;`")),
+            // Point just past the previous token: that is where the `;` is missing.
+            _ if self.pos > 0 => {
+                let end = self.tokens[self.pos - 1].span.1;
+                Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, format!("Expected `;` but found {}", self.peek().kind))
+                    .with_primary(Span::new(self.file, end, end), "expected `;`"))
+            }
+            _ => Err(self.unexpected("`;`")),
+        }
+    }
+
+    // -------- expressions (precedence climbing) --------
+
+    fn parse_expression(&mut self) -> PResult<Expr> {
+        self.parse_expr_prec(0)
+    }
+
+    fn parse_expr_prec(&mut self, min_prec: u8) -> PResult<Expr> {
+        let from = self.pos;
+        let mut left = self.parse_cast()?;
+        let mut prev: Option<BinOp> = None;
+        loop {
+            let op = match binop_for(&self.peek().kind) {
+                Some(op) if op.precedence() >= min_prec => op,
+                _ => break,
+            };
+            if op.is_comparison() && prev.is_some_and(BinOp::is_comparison) {
+                return Err(Diagnostic::error(codes::CHAINED_COMPARISON, "Comparison operators cannot be chained")
+                    .with_primary(self.here(), "second comparison")
+                    .with_note(format!("add parentheses, or split it with `&&`: `a < b && b {} c`", op.symbol())));
+            }
+            self.bump();
+            let right = self.parse_expr_prec(op.precedence() + 1)?;
+            self.node(NodeKind::Binary, from);
+            let span = left.span.to(right.span);
+            left = Expr { kind: ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, span };
+            prev = Some(op);
+        }
+        Ok(left)
+    }
+
+    /// `as` binds tighter than every binary operator but looser than unary ones.
+    fn parse_cast(&mut self) -> PResult<Expr> {
+        let from = self.pos;
+        let mut expr = self.parse_unary()?;
+        while self.at_keyword("as") {
+            self.bump();
+            let ty = self.expect_ident("type name")?;
+            self.node(NodeKind::Cast, from);
+            let span = expr.span.to(ty.span);
+            expr = Expr { kind: ExprKind::Cast { expr: Box::new(expr), ty }, span };
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> PResult<Expr> {
+        let (start, from) = (self.here(), self.pos);
+        let op = match &self.peek().kind {
+            TokenKind::Minus => UnaryOp::Neg,
+            TokenKind::Bang => UnaryOp::Not,
+            _ => return self.parse_postfix(),
+        };
+        self.bump();
+        let expr = self.parse_unary()?;
+        self.node(NodeKind::Unary, from);
+        let span = start.to(expr.span);
+        Ok(Expr { kind: ExprKind::Unary { op, expr: Box::new(expr) }, span })
+    }
+
+    fn parse_postfix(&mut self) -> PResult<Expr> {
+        let from = self.pos;
+        let mut expr = self.parse_primary()?;
+        while self.check(&TokenKind::Symbol('.')) {
+            self.bump();
+            let field = self.expect_ident("field name")?;
+            self.node(NodeKind::Member, from);
+            let span = expr.span.to(field.span);
+            expr = Expr { kind: ExprKind::Member { object: Box::new(expr), field }, span };
+        }
+        Ok(expr)
+    }
+
+    /// Parse a parenthesized, comma-separated argument list.
+    fn parse_args(&mut self) -> PResult<Vec<Expr>> {
+        let from = self.pos;
+        self.expect_symbol('(')?;
+        let mut args = Vec::new();
+        while !self.check(&TokenKind::CloseParen) {
+            args.push(self.parse_expression()?);
+            if self.check(&TokenKind::Symbol(',')) { self.bump(); } else { break; }
+        }
+        self.expect_symbol(')')?;
+        self.node(NodeKind::ArgList, from);
+        Ok(args)
+    }
+
+    fn parse_primary(&mut self) -> PResult<Expr> {
+        let (start, from) = (self.here(), self.pos);
+        let (kind, node) = match &self.peek().kind {
+            TokenKind::Ident(_) => {
+                let name = self.expect_ident("identifier")?;
+                if self.check(&TokenKind::OpenParen) {
+                    let args = self.parse_args()?;
+                    (ExprKind::Call { callee: name, args }, NodeKind::Call)
+                } else {
+                    (ExprKind::Ident(name.name), NodeKind::Name)
+                }
+            }
+            TokenKind::OpenParen => {
+                self.bump();
+                let inner = self.parse_expression()?;
+                self.expect_symbol(')')?;
+                self.node(NodeKind::Paren, from);
+                // Keep the parentheses in the span so diagnostics underline them too.
+                return Ok(Expr { span: self.since(start), ..inner });
+            }
+            kind => match literal(kind) {
+                Some(lit) => {
+                    let lit = lit.map_err(|d| d.with_primary(start, ""))?;
+                    self.bump();
+                    (lit, NodeKind::Literal)
+                }
+                None => return Err(self.unexpected("an expression")),
+            },
+        };
+        self.node(node, from);
+        Ok(Expr { kind, span: self.since(start) })
+    }
+}
+
+/// Keywords that begin a statement or declaration; recovery stops in front of them.
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "contract", "fn", "storage", "event", "error", "let", "return", "if", "while", "break", "continue", "emit", "revert",
+];
+
+/// The value of a literal token, or `None` if `kind` is not a literal. The
+/// error for a literal out of range has no label yet.
+pub(crate) fn literal(kind: &TokenKind) -> Option<PResult<ExprKind>> {
+    let invalid = |msg: String| Diagnostic::error(codes::INVALID_LITERAL, msg);
+    Some(Ok(match kind {
+        TokenKind::Number(n) => {
+            let digits = n.replace('_', "");
+            let radix = match digits.get(..2) {
+                Some("0x" | "0X") => 16,
+                Some("0b" | "0B") => 2,
+                _ => 10,
+            };
+            if radix == 10 && digits.contains(['.', 'e', 'E']) {
+                match digits.parse() {
+                    Ok(f) => ExprKind::Float(f),
+                    Err(_) => return Some(Err(invalid(format!("Invalid number `{}`", n)))),
+                }
+            } else {
+                let digits = if radix == 10 { &digits[..] } else { &digits[2..] };
+                match u64::from_str_radix(digits, radix) {
+                    Ok(v) => ExprKind::Int(v),
+                    Err(_) => return Some(Err(invalid(format!("Integer literal `{}` is too large", n))
+                        .with_note("the largest integer literal is 18446744073709551615"))),
+                }
+            }
+        }
+        TokenKind::StringLiteral(s) => ExprKind::StringLiteral(s.clone()),
+        TokenKind::Keyword(k) if k == "true" || k == "false" => ExprKind::Bool(k == "true"),
+        _ => return None,
+    }))
+}
+
+pub(crate) fn binop_for(kind: &TokenKind) -> Option<BinOp> {
+    Some(match kind {
+        TokenKind::Plus => BinOp::Add,
+        TokenKind::Minus => BinOp::Sub,
+        TokenKind::Star => BinOp::Mul,
+        TokenKind::Slash => BinOp::Div,
+        TokenKind::Percent => BinOp::Rem,
+        TokenKind::EqEq => BinOp::Eq,
+        TokenKind::NotEq => BinOp::Ne,
+        TokenKind::Lt => BinOp::Lt,
+        TokenKind::LtEq => BinOp::Le,
+        TokenKind::Gt => BinOp::Gt,
+        TokenKind::GtEq => BinOp::Ge,
+        TokenKind::AndAnd => BinOp::And,
+        TokenKind::OrOr => BinOp::Or,
+        TokenKind::Amp => BinOp::BitAnd,
+        TokenKind::Pipe => BinOp::BitOr,
+        TokenKind::Caret => BinOp::BitXor,
+        TokenKind::Shl => BinOp::Shl,
+        TokenKind::Shr => BinOp::Shr,
+        _ => return None,
+    })
+}
+
+/// Map an assignment token to its compound operator: `Some(None)` for plain `=`.
+pub(crate) fn assign_op_for(kind: &TokenKind) -> Option<Option<BinOp>> {
+    Some(match kind {
+        TokenKind::Assign => None,
+        TokenKind::PlusEq => Some(BinOp::Add),
+        TokenKind::MinusEq => Some(BinOp::Sub),
+        TokenKind::StarEq => Some(BinOp::Mul),
+        TokenKind::SlashEq => Some(BinOp::Div),
+        TokenKind::PercentEq => Some(BinOp::Rem),
+        TokenKind::AmpEq => Some(BinOp::BitAnd),
+        TokenKind::PipeEq => Some(BinOp::BitOr),
+        TokenKind::CaretEq => Some(BinOp::BitXor),
+        TokenKind::ShlEq => Some(BinOp::Shl),
+        TokenKind::ShrEq => Some(BinOp::Shr),
+        _ => return None,
+    })
+}
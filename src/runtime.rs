@@ -0,0 +1,143 @@
+//! Runtime support compiled into modules that use strings: a bump allocator
+//! over linear memory plus string helpers. Bodies are hand-assembled WASM.
+//!
+//! Strings are `(ptr, len)` pairs packed into one `i64`: the pointer in the
+//! high 32 bits and the byte length in the low 32 bits.
+
+use crate::wasm::{write_uleb, FuncType, ValType};
+
+/// Runtime helpers. A module links only the ones its code calls, plus their
+/// `deps`, declared after the host imports and before user functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Helper {
+    /// `__alloc(size: i32) -> i32`: bump-allocate `size` bytes, growing memory as needed.
+    Alloc,
+    /// `__str_concat(a, b) -> string`
+    Concat,
+    /// `__str_eq(a, b) -> i32`: byte-wise equality.
+    StrEq,
+}
+
+/// The order linked helpers take in the function index space.
+pub const HELPERS: &[Helper] = &[Helper::Alloc, Helper::Concat, Helper::StrEq];
+
+/// Start of the data segment holding string literals; address 0 stays unused.
+pub const DATA_BASE: u32 = 16;
+
+impl Helper {
+    /// Helpers this one calls, which must be linked alongside it.
+    pub fn deps(self) -> &'static [Helper] {
+        match self {
+            Helper::Concat => &[Helper::Alloc],
+            _ => &[],
+        }
+    }
+
+    pub fn func_type(self) -> FuncType {
+        match self {
+            Helper::Alloc => FuncType { params: vec![ValType::I32], results: vec![ValType::I32] },
+            Helper::Concat => FuncType { params: vec![ValType::I64, ValType::I64], results: vec![ValType::I64] },
+            Helper::StrEq => FuncType { params: vec![ValType::I64, ValType::I64], results: vec![ValType::I32] },
+        }
+    }
+
+    /// Encoded body. `heap` is the global holding the next free address and
+    /// `alloc` the function index of `__alloc`.
+    pub fn body(self, heap: u32, alloc: u32) -> Vec<u8> {
+        match self {
+            Helper::Alloc => alloc_body(heap),
+            Helper::Concat => concat_body(alloc),
+            Helper::StrEq => str_eq_body(),
+        }
+    }
+}
+
+/// Body prefix declaring `n` i32 locals.
+fn locals_i32(n: u32) -> Vec<u8> {
+    let mut b = vec![0x01];
+    write_uleb(n, &mut b);
+    b.push(0x7F);
+    b
+}
+
+fn global_op(opcode: u8, global: u32, b: &mut Vec<u8>) {
+    b.push(opcode);
+    write_uleb(global, b);
+}
+
+// params: size(0); locals: ptr(1)
+fn alloc_body(heap: u32) -> Vec<u8> {
+    let mut b = locals_i32(1);
+    global_op(0x23, heap, &mut b);                   // global.get $heap
+    b.extend_from_slice(&[0x22, 0x01]);              // local.tee $ptr
+    b.extend_from_slice(&[0x20, 0x00, 0x6A]);        // local.get $size; i32.add
+    global_op(0x24, heap, &mut b);                   // global.set $heap
+    // grow memory if the new heap top lies past the end of memory
+    b.extend_from_slice(&[0x02, 0x40]);              // block
+    global_op(0x23, heap, &mut b);
+    b.extend_from_slice(&[0x3F, 0x00, 0x41, 0x10, 0x74]); // memory.size; i32.const 16; i32.shl
+    b.extend_from_slice(&[0x4D, 0x0D, 0x00]);        // i32.le_u; br_if 0
+    global_op(0x23, heap, &mut b);
+    b.extend_from_slice(&[0x3F, 0x00, 0x41, 0x10, 0x74, 0x6B]); // - memory.size << 16
+    b.extend_from_slice(&[0x41, 0x10, 0x76]);        // i32.const 16; i32.shr_u
+    // (shortfall >> 16) + 1 pages always covers the shortfall
+    b.extend_from_slice(&[0x41, 0x01, 0x6A]);        // i32.const 1; i32.add
+    b.extend_from_slice(&[0x40, 0x00]);              // memory.grow
+    b.extend_from_slice(&[0x41, 0x00, 0x48]);        // i32.const 0; i32.lt_s
+    b.extend_from_slice(&[0x04, 0x40, 0x00, 0x0B]);  // if unreachable end  (out of memory)
+    b.push(0x0B);                                    // end block
+    b.extend_from_slice(&[0x20, 0x01, 0x0B]);        // local.get $ptr; end
+    b
+}
+
+/// Push the pointer half of the packed string in local `idx`.
+fn unpack_ptr(idx: u8, b: &mut Vec<u8>) {
+    b.extend_from_slice(&[0x20, idx, 0x42, 0x20, 0x88, 0xA7]); // local.get; i64.const 32; i64.shr_u; i32.wrap_i64
+}
+
+// params: a(0), b(1); locals: len_a(2), len_b(3), dst(4)
+fn concat_body(alloc: u32) -> Vec<u8> {
+    let mut b = locals_i32(3);
+    b.extend_from_slice(&[0x20, 0x00, 0xA7, 0x21, 0x02]); // len_a = wrap(a)
+    b.extend_from_slice(&[0x20, 0x01, 0xA7, 0x21, 0x03]); // len_b = wrap(b)
+    b.extend_from_slice(&[0x20, 0x02, 0x20, 0x03, 0x6A]); // len_a + len_b
+    b.push(0x10);                                         // call __alloc
+    write_uleb(alloc, &mut b);
+    b.extend_from_slice(&[0x21, 0x04]);                   // local.set $dst
+    // memory.copy(dst, ptr_a, len_a)
+    b.extend_from_slice(&[0x20, 0x04]);
+    unpack_ptr(0, &mut b);
+    b.extend_from_slice(&[0x20, 0x02, 0xFC, 0x0A, 0x00, 0x00]);
+    // memory.copy(dst + len_a, ptr_b, len_b)
+    b.extend_from_slice(&[0x20, 0x04, 0x20, 0x02, 0x6A]);
+    unpack_ptr(1, &mut b);
+    b.extend_from_slice(&[0x20, 0x03, 0xFC, 0x0A, 0x00, 0x00]);
+    // (dst << 32) | (len_a + len_b)
+    b.extend_from_slice(&[0x20, 0x04, 0xAD, 0x42, 0x20, 0x86]);
+    b.extend_from_slice(&[0x20, 0x02, 0x20, 0x03, 0x6A, 0xAD, 0x84]);
+    b.push(0x0B);
+    b
+}
+
+// params: a(0), b(1); locals: pa(2), pb(3), n(4)
+fn str_eq_body() -> Vec<u8> {
+    let mut b = locals_i32(3);
+    b.extend_from_slice(&[0x20, 0x00, 0xA7, 0x22, 0x04]);       // n = len_a (tee)
+    b.extend_from_slice(&[0x20, 0x01, 0xA7, 0x47]);             // len_b; i32.ne
+    b.extend_from_slice(&[0x04, 0x40, 0x41, 0x00, 0x0F, 0x0B]); // if return 0 end
+    unpack_ptr(0, &mut b);
+    b.extend_from_slice(&[0x21, 0x02]);
+    unpack_ptr(1, &mut b);
+    b.extend_from_slice(&[0x21, 0x03]);
+    b.extend_from_slice(&[0x02, 0x40, 0x03, 0x40]);             // block loop
+    b.extend_from_slice(&[0x20, 0x04, 0x45, 0x0D, 0x01]);       // br_if $done (n == 0)
+    b.extend_from_slice(&[0x20, 0x02, 0x2D, 0x00, 0x00]);       // i32.load8_u [pa]
+    b.extend_from_slice(&[0x20, 0x03, 0x2D, 0x00, 0x00, 0x47]); // i32.load8_u [pb]; i32.ne
+    b.extend_from_slice(&[0x04, 0x40, 0x41, 0x00, 0x0F, 0x0B]); // if return 0 end
+    b.extend_from_slice(&[0x20, 0x02, 0x41, 0x01, 0x6A, 0x21, 0x02]); // pa += 1
+    b.extend_from_slice(&[0x20, 0x03, 0x41, 0x01, 0x6A, 0x21, 0x03]); // pb += 1
+    b.extend_from_slice(&[0x20, 0x04, 0x41, 0x01, 0x6B, 0x21, 0x04]); // n -= 1
+    b.extend_from_slice(&[0x0C, 0x00, 0x0B, 0x0B]);             // br $loop; end; end
+    b.extend_from_slice(&[0x41, 0x01, 0x0B]);                   // i32.const 1; end
+    b
+}
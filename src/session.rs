@@ -0,0 +1,154 @@
+//! The embedding API: a `Session` holds the sources of a compilation and runs
+//! the pipeline over them, returning structured results. The `mintora` binary
+//! is a thin command-line wrapper around it.
+
+use serde::Serialize;
+
+use crate::ast::Program;
+use crate::diagnostic::Diagnostic;
+use crate::span::{FileId, SourceMap};
+use crate::syntax::{self, SyntaxNode};
+use crate::testing::{self, TestResult};
+use crate::{abi, codegen, compiler, fmt, wasm};
+
+/// An output `Session::emit` can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Emit {
+    /// The WASM module.
+    Wasm,
+    /// The module in the WebAssembly text format.
+    Wat,
+    /// The checked AST, pretty-printed with `{:#?}`.
+    Ast,
+    /// The contract ABI as JSON, see `abi`.
+    Abi,
+}
+
+impl Emit {
+    pub fn from_name(name: &str) -> Option<Emit> {
+        match name {
+            "wasm" => Some(Emit::Wasm),
+            "wat" => Some(Emit::Wat),
+            "ast" => Some(Emit::Ast),
+            "abi" => Some(Emit::Abi),
+            _ => None,
+        }
+    }
+
+    /// The file extension of this output.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Emit::Wasm => "wasm",
+            Emit::Wat => "wat",
+            Emit::Ast => "ast",
+            Emit::Abi => "abi.json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub emit: Emit,
+    pub contents: Vec<u8>,
+}
+
+/// Every stage reports all the diagnostics it found, or its result.
+pub type Outcome<T> = Result<T, Vec<Diagnostic>>;
+
+#[derive(Debug, Default)]
+pub struct Session {
+    sources: SourceMap,
+    /// Modules export the `call` entry point, see `codegen::compile_with_dispatcher`.
+    dispatcher: bool,
+}
+
+impl Session {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_dispatcher(mut self, dispatcher: bool) -> Self {
+        self.dispatcher = dispatcher;
+        self
+    }
+
+    pub fn add_source(&mut self, name: impl Into<String>, src: impl Into<String>) -> FileId {
+        self.sources.add(name, src)
+    }
+
+    /// Read `path` into the session. Failure is an `IO` diagnostic.
+    pub fn load(&mut self, path: &str) -> Result<FileId, Diagnostic> {
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| Diagnostic::error(crate::diagnostic::codes::IO, format!("Cannot read `{}`: {}", path, e)))?;
+        Ok(self.add_source(path, src))
+    }
+
+    pub fn sources(&self) -> &SourceMap { &self.sources }
+
+    pub fn source(&self, file: FileId) -> &str { self.sources.source(file) }
+
+    /// The lossless syntax tree, which exists even when there are syntax errors.
+    pub fn syntax(&self, file: FileId) -> (SyntaxNode, Vec<Diagnostic>) {
+        syntax::parse(self.source(file), file)
+    }
+
+    pub fn parse(&self, file: FileId) -> Outcome<Program> {
+        let (tree, diagnostics) = self.syntax(file);
+        if !diagnostics.is_empty() { return Err(diagnostics); }
+        Ok(syntax::lower(&tree))
+    }
+
+    /// Parse and type-check.
+    pub fn check(&self, file: FileId) -> Outcome<Program> {
+        let program = self.parse(file)?;
+        compiler::type_check(&program).map_err(|d| vec![d])?;
+        Ok(program)
+    }
+
+    /// Compile to a WASM module.
+    pub fn compile(&self, file: FileId) -> Outcome<Vec<u8>> {
+        let program = self.check(file)?;
+        self.codegen(&program)
+    }
+
+    /// Compile a program `check` returned, for callers that need both.
+    pub fn codegen(&self, program: &Program) -> Outcome<Vec<u8>> {
+        let wasm = if self.dispatcher { codegen::compile_with_dispatcher(program) } else { codegen::compile_to_wasm(program) };
+        wasm.map_err(|d| vec![d])
+    }
+
+    /// Compile once and produce each requested output.
+    pub fn emit(&self, file: FileId, kinds: &[Emit]) -> Outcome<Vec<Artifact>> {
+        let program = self.check(file)?;
+        let wasm = self.codegen(&program)?;
+        Ok(kinds.iter().map(|&emit| {
+            let contents = match emit {
+                Emit::Wasm => wasm.clone(),
+                Emit::Wat => wasm::to_wat(&wasm).expect("the compiler emits valid modules").into_bytes(),
+                Emit::Ast => format!("{:#?}\n", program).into_bytes(),
+                Emit::Abi => abi::abi(&program).json().into_bytes(),
+            };
+            Artifact { emit, contents }
+        }).collect())
+    }
+
+    /// Run the file's `#[test]` functions.
+    pub fn test(&self, file: FileId) -> Outcome<Vec<TestResult>> {
+        let program = self.check(file)?;
+        let wasm = codegen::compile_tests(&program).map_err(|d| vec![d])?;
+        Ok(testing::run(&program, &wasm))
+    }
+
+    /// The file in the canonical style.
+    pub fn format(&self, file: FileId) -> Outcome<String> {
+        fmt::format(self.source(file), file)
+    }
+
+    /// Render diagnostics for a terminal, with the rustc-style summary line
+    /// when there is more than one.
+    pub fn render(&self, diagnostics: &[Diagnostic]) -> String {
+        let rendered: Vec<String> = diagnostics.iter().map(|d| d.render(&self.sources)).collect();
+        let mut out = rendered.join("\n");
+        if diagnostics.len() > 1 { out.push_str(&format!("\nerror: aborting due to {} previous errors\n", diagnostics.len())); }
+        out
+    }
+}
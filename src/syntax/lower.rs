@@ -0,0 +1,138 @@
+//! Lowering from the concrete syntax tree to the AST.
+//!
+//! The parser only records a node once everything in it parsed, so apart from
+//! `Error` nodes every node here has the shape its parser function gives it.
+
+use crate::ast::{Attribute, EventDecl, Expr, ExprKind, Ident, Param, Program, Statement, StmtKind, StorageField, UnaryOp};
+use crate::lexer::TokenKind;
+use crate::parser::{assign_op_for, binop_for, literal};
+
+use super::{NodeKind, SyntaxNode, SyntaxToken};
+
+/// The AST for a tree from `syntax::parse`; equal to what `Parser::parse_recovering` returns.
+pub fn lower(root: &SyntaxNode) -> Program {
+    Program { statements: root.nodes().map(statement).collect() }
+}
+
+fn ident(token: &SyntaxToken) -> Ident {
+    Ident { name: token.text.clone(), span: token.span }
+}
+
+/// The `n`th identifier token directly under `node`.
+fn nth_ident(node: &SyntaxNode, n: usize) -> Option<Ident> {
+    node.tokens().filter(|t| matches!(t.kind, TokenKind::Ident(_))).nth(n).map(ident)
+}
+
+fn has_keyword(node: &SyntaxNode, kw: &str) -> bool {
+    node.tokens().any(|t| matches!(&t.kind, TokenKind::Keyword(k) if k == kw))
+}
+
+fn block(node: &SyntaxNode) -> Vec<Statement> {
+    node.nodes().map(statement).collect()
+}
+
+fn params(list: &SyntaxNode) -> Vec<Param> {
+    list.nodes().map(|p| Param { name: nth_ident(p, 0).expect("parameters have a name"), ty: nth_ident(p, 1), span: p.span() }).collect()
+}
+
+fn args(list: &SyntaxNode) -> Vec<Expr> {
+    list.nodes().map(expr).collect()
+}
+
+fn event(node: &SyntaxNode) -> EventDecl {
+    EventDecl {
+        name: nth_ident(node, 0).expect("events and errors have a name"),
+        fields: params(node.nodes().next().expect("events and errors have fields")),
+        span: node.span(),
+    }
+}
+
+fn statement(node: &SyntaxNode) -> Statement {
+    let name = || nth_ident(node, 0).expect("declarations have a name");
+    let mut nodes = node.nodes();
+    let kind = match node.kind {
+        NodeKind::Contract => StmtKind::Contract {
+            name: name(),
+            fields: node.nodes().filter(|n| n.kind == NodeKind::StorageField).map(|f| StorageField {
+                name: nth_ident(f, 0).expect("storage fields have a name"),
+                ty: nth_ident(f, 1).expect("storage fields have a type"),
+                span: f.span(),
+            }).collect(),
+            events: node.nodes().filter(|n| n.kind == NodeKind::EventDecl).map(event).collect(),
+            errors: node.nodes().filter(|n| n.kind == NodeKind::ErrorDecl).map(event).collect(),
+            functions: node.nodes()
+                .filter(|n| !matches!(n.kind, NodeKind::StorageField | NodeKind::EventDecl | NodeKind::ErrorDecl))
+                .map(statement).collect(),
+        },
+        NodeKind::Function => {
+            let attributes = node.nodes().filter(|n| n.kind == NodeKind::Attribute)
+                .map(|a| Attribute { name: nth_ident(a, 0).expect("attributes have a name"), span: a.span() }).collect();
+            let mut nodes = nodes.skip_while(|n| n.kind == NodeKind::Attribute);
+            let list = nodes.next().expect("functions have a parameter list");
+            StmtKind::Function {
+                attributes,
+                name: name(),
+                params: params(list),
+                return_type: nth_ident(node, 1),
+                body: block(nodes.next().expect("functions have a body")),
+            }
+        }
+        NodeKind::Block => StmtKind::Block(block(node)),
+        NodeKind::Let => StmtKind::Let {
+            name: name(),
+            mutable: has_keyword(node, "mut"),
+            ty: nth_ident(node, 1),
+            value: expr(nodes.next().expect("`let` has a value")),
+        },
+        NodeKind::Assign => StmtKind::Assign {
+            name: name(),
+            op: node.tokens().find_map(|t| assign_op_for(&t.kind)).expect("assignments have an operator"),
+            value: expr(nodes.next().expect("assignments have a value")),
+        },
+        NodeKind::If => StmtKind::If {
+            cond: expr(nodes.next().expect("`if` has a condition")),
+            then_body: block(nodes.next().expect("`if` has a body")),
+            else_body: nodes.next().map(|e| if e.kind == NodeKind::If { vec![statement(e)] } else { block(e) }),
+        },
+        NodeKind::While => StmtKind::While {
+            cond: expr(nodes.next().expect("`while` has a condition")),
+            body: block(nodes.next().expect("`while` has a body")),
+        },
+        NodeKind::Break => StmtKind::Break,
+        NodeKind::Continue => StmtKind::Continue,
+        NodeKind::Return => StmtKind::Return(nodes.next().map(expr)),
+        NodeKind::Emit => StmtKind::Emit { event: name(), args: args(nodes.next().expect("`emit` has arguments")) },
+        NodeKind::Revert => StmtKind::Revert { error: name(), args: args(nodes.next().expect("`revert` has arguments")) },
+        NodeKind::ExprStmt => StmtKind::Expr(expr(nodes.next().expect("expression statements have an expression"))),
+        _ => StmtKind::Error,
+    };
+    Statement { kind, span: node.span() }
+}
+
+fn expr(node: &SyntaxNode) -> Expr {
+    let mut nodes = node.nodes();
+    let mut operand = || Box::new(expr(nodes.next().expect("operators have operands")));
+    let first = || node.tokens().next().expect("the node has a token");
+    let kind = match node.kind {
+        NodeKind::Literal => literal(&first().kind).and_then(Result::ok).expect("recorded literals are valid"),
+        NodeKind::Name => ExprKind::Ident(first().text.clone()),
+        NodeKind::Call => ExprKind::Call {
+            callee: ident(first()),
+            args: node.nodes().flat_map(args).collect(),
+        },
+        NodeKind::Member => ExprKind::Member { object: operand(), field: nth_ident(node, 0).expect("member access names a field") },
+        NodeKind::Unary => ExprKind::Unary {
+            op: if first().kind == TokenKind::Bang { UnaryOp::Not } else { UnaryOp::Neg },
+            expr: operand(),
+        },
+        NodeKind::Binary => ExprKind::Binary {
+            op: node.tokens().find_map(|t| binop_for(&t.kind)).expect("binary expressions have an operator"),
+            left: operand(),
+            right: operand(),
+        },
+        NodeKind::Cast => ExprKind::Cast { expr: operand(), ty: nth_ident(node, 0).expect("casts name a type") },
+        NodeKind::Paren => return Expr { span: node.span(), ..*operand() },
+        kind => unreachable!("{:?} is not an expression", kind),
+    };
+    Expr { kind, span: node.span() }
+}
@@ -0,0 +1,196 @@
+//! Lossless concrete syntax tree. Every byte of the source, including
+//! whitespace and comments, belongs to exactly one token, so a tree prints back
+//! to the text it was parsed from. Tools that rewrite source work on this tree;
+//! the compiler works on the AST lowered from it (see `lower`).
+
+mod lower;
+
+pub use lower::lower;
+
+use serde::Serialize;
+
+use crate::ast::{FileId, Span};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Lexer, Token, TokenKind, TriviaKind};
+use crate::parser::Parser;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TriviaPiece {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+/// A token with the trivia around it. Trivia up to the end of the token's
+/// line trails it; everything else leads the next token, so a comment on its
+/// own line belongs to the code below it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    pub span: Span,
+    pub text: String,
+    pub leading: Vec<TriviaPiece>,
+    pub trailing: Vec<TriviaPiece>,
+}
+
+impl SyntaxToken {
+    /// The token with its trivia, exactly as in the source. Only the round-trip
+    /// tests reprint source so far.
+    #[cfg(test)]
+    pub fn full_text(&self) -> String {
+        let mut out = String::new();
+        out.extend(self.leading.iter().map(|t| t.text.as_str()));
+        out.push_str(&self.text);
+        out.extend(self.trailing.iter().map(|t| t.text.as_str()));
+        out
+    }
+}
+
+/// Split `src` into tokens with trivia attached. The last token is `Eof`,
+/// whose leading trivia is whatever follows the last real token's line.
+pub fn tokenize(src: &str, file: FileId) -> Vec<SyntaxToken> {
+    let mut lexer = Lexer::new(src);
+    let mut tokens: Vec<SyntaxToken> = Vec::new();
+    loop {
+        let mut trivia = Vec::new();
+        while let Some(t) = lexer.trivia() {
+            trivia.push(TriviaPiece { kind: t.kind, text: src[t.span.0..t.span.1].to_string() });
+        }
+        if let Some(prev) = tokens.last_mut() {
+            let eol = trivia.iter().position(|t| t.text.ends_with('\n')).map_or(trivia.len(), |i| i + 1);
+            prev.trailing = trivia.drain(..eol).collect();
+        }
+        let Token { kind, span: (start, end) } = lexer.token();
+        let eof = kind == TokenKind::Eof;
+        tokens.push(SyntaxToken {
+            kind, span: Span::new(file, start, end), text: src[start..end].to_string(), leading: trivia, trailing: Vec::new(),
+        });
+        if eof { return tokens; }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum NodeKind {
+    SourceFile,
+    Contract,
+    StorageField,
+    EventDecl,
+    ErrorDecl,
+    Function,
+    Attribute,
+    ParamList,
+    Param,
+    Block,
+    Let,
+    Assign,
+    If,
+    While,
+    Break,
+    Continue,
+    Return,
+    Emit,
+    Revert,
+    ExprStmt,
+    Literal,
+    Name,
+    Call,
+    ArgList,
+    Member,
+    Unary,
+    Binary,
+    Cast,
+    Paren,
+    /// Tokens skipped while recovering from a syntax error.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    /// Direct child nodes.
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c { SyntaxElement::Node(n) => Some(n), _ => None })
+    }
+
+    /// Direct child tokens.
+    pub fn tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.children.iter().filter_map(|c| match c { SyntaxElement::Token(t) => Some(t), _ => None })
+    }
+
+    /// Every token under this node, in source order.
+    pub fn descendant_tokens(&self) -> Vec<&SyntaxToken> {
+        let mut out = Vec::new();
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(n) => out.extend(n.descendant_tokens()),
+                SyntaxElement::Token(t) => out.push(t),
+            }
+        }
+        out
+    }
+
+    /// From the start of the first token to the end of the last, without trivia.
+    pub fn span(&self) -> Span {
+        let tokens = self.descendant_tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default(),
+        }
+    }
+
+    /// The source text of the node, trivia included.
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        self.descendant_tokens().iter().map(|t| t.full_text()).collect()
+    }
+}
+
+/// Parse `src` into a concrete syntax tree, recovering from errors like
+/// `Parser::parse_recovering` does. The tree always covers the whole input.
+pub fn parse(src: &str, file: FileId) -> (SyntaxNode, Vec<Diagnostic>) {
+    // The parser lexes the same source again without trivia, so its token
+    // indices line up with `tokenize`'s.
+    let mut parser = Parser::new(Lexer::new(src)).with_file(file);
+    let diagnostics = parser.parse().err().unwrap_or_default();
+    (build(tokenize(src, file), parser.take_nodes()), diagnostics)
+}
+
+/// Nest `tokens` into a tree. `nodes` are token ranges in the order the parser
+/// completed them, so a parent comes after any child covering the same tokens.
+fn build(tokens: Vec<SyntaxToken>, nodes: Vec<(NodeKind, usize, usize)>) -> SyntaxNode {
+    let n = tokens.len();
+    let mut order: Vec<(usize, &(NodeKind, usize, usize))> = nodes.iter().enumerate().collect();
+    order.sort_by_key(|&(i, &(_, start, end))| (start, std::cmp::Reverse(end), std::cmp::Reverse(i)));
+
+    let mut stack = vec![(SyntaxNode { kind: NodeKind::SourceFile, children: Vec::new() }, n)];
+    let mut next = order.into_iter().peekable();
+    for (i, token) in tokens.into_iter().enumerate() {
+        loop {
+            close_until(&mut stack, i);
+            match next.next_if(|&(_, &(_, start, _))| start == i) {
+                Some((_, &(kind, _, end))) => stack.push((SyntaxNode { kind, children: Vec::new() }, end)),
+                None => break,
+            }
+        }
+        stack.last_mut().expect("the root is never closed").0.children.push(SyntaxElement::Token(token));
+    }
+    close_until(&mut stack, n);
+    stack.pop().expect("the root is never closed").0
+}
+
+/// Close every open node (except the root) that ends at or before token `i`.
+fn close_until(stack: &mut Vec<(SyntaxNode, usize)>, i: usize) {
+    while stack.len() > 1 && stack.last().is_some_and(|&(_, end)| end <= i) {
+        let (node, _) = stack.pop().unwrap();
+        stack.last_mut().unwrap().0.children.push(SyntaxElement::Node(node));
+    }
+}
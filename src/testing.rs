@@ -0,0 +1,51 @@
+//! The runner behind `mintora test`. Every `#[test]` function is called on a
+//! fresh simulated chain, with the whole program deployed as one test module.
+//! A test passes if the call returns, or with `#[should_revert]` if the
+//! contract reverts with a custom error. Any other trap, including a failed
+//! assertion, fails the test either way.
+
+use serde::Serialize;
+
+use crate::ast::{Program, Span};
+use crate::chain::{Chain, Message};
+use crate::compiler::function_items;
+use crate::diagnostic::{codes, Diagnostic};
+use crate::wasm::interp::Trap;
+
+/// Balance of the account each test is called from.
+pub const SENDER_BALANCE: i64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    /// Why the test failed, pointing at the failed assertion if there was one.
+    pub failure: Option<Diagnostic>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool { self.failure.is_none() }
+}
+
+/// Run the tests of a type-checked program against `wasm`, its module from
+/// `codegen::compile_tests`, in source order.
+pub fn run(program: &Program, wasm: &[u8]) -> Vec<TestResult> {
+    let items = function_items(program).expect("the program was type-checked");
+    items.iter().filter(|f| f.is_test()).map(|f| {
+        let name = f.name.as_str();
+        let mut chain = Chain::new();
+        let sender = chain.create_account(SENDER_BALANCE);
+        let contract = chain.deploy(wasm).expect("the compiler emits valid modules");
+        let failure = match (chain.call(contract, Message { sender, value: 0 }, name, &[]), f.attribute("should_revert")) {
+            (Ok(_), None) | (Err(Trap::Revert { .. }), Some(_)) => None,
+            (Ok(_), Some(attr)) => Some(Diagnostic::error(codes::TEST_FAILED, format!("Test `{}` did not revert", name))
+                .with_primary(attr.span, "the call was expected to revert")),
+            (Err(Trap::AssertionFailed { start, end }), _) => Some(Diagnostic::error(codes::TEST_FAILED, format!("Assertion failed in test `{}`", name))
+                .with_primary(Span::new(f.name.span.file, start, end), "this does not hold")),
+            (Err(trap), Some(attr)) => Some(Diagnostic::error(codes::TEST_FAILED, format!("Test `{}` trapped instead of reverting: {}", name, trap))
+                .with_primary(attr.span, "the call was expected to revert")),
+            (Err(trap), None) => Some(Diagnostic::error(codes::TEST_FAILED, format!("Test `{}` trapped: {}", name, trap))
+                .with_primary(f.name.span, "")),
+        };
+        TestResult { name: name.to_string(), failure }
+    }).collect()
+}
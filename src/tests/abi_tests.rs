@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as Json;
+
+    use crate::abi::{self, Mutability};
+    use crate::chain::{Chain, Event, Message};
+    use crate::wasm::decode::custom_section;
+    use crate::wasm::interp::{Trap, Value};
+    use crate::{Emit, Session};
+
+    const VAULT: &str = "contract Vault {
+        storage total: i64;
+        event Deposited(from: i64, amount: i64, big: bool);
+        event Halved(x: f64);
+        error TooSmall(amount: i64, min: i64);
+
+        #[payable]
+        fn deposit() -> i64 {
+            if msg.value < 10 { revert TooSmall(msg.value, 10); }
+            add(msg.value);
+            emit Deposited(msg.sender, msg.value, msg.value > 100);
+            return total;
+        }
+        fn add(v: i64) { total += v; }
+        fn add_twice(v) { add(v); add(v); }
+        fn get() -> i64 { let mut total = total; total += 1; return total - 1; }
+        fn half(x: f64) -> f64 { emit Halved(x / 2.0); return x / 2.0; }
+    }";
+
+    #[test]
+    fn abi_describes_functions_events_and_errors_and_its_hash_is_embedded() {
+        let mut session = Session::new();
+        let file = session.add_source("vault.mint", VAULT);
+        let artifacts = session.emit(file, &[Emit::Wasm, Emit::Abi]).unwrap();
+        let json: Json = serde_json::from_slice(&artifacts[1].contents).unwrap();
+        let mutability: Vec<(&str, &str)> = json["functions"].as_array().unwrap().iter()
+            .map(|f| (f["name"].as_str().unwrap(), f["mutability"].as_str().unwrap())).collect();
+        assert_eq!(mutability, [("deposit", "payable"), ("add", "nonpayable"), ("add_twice", "nonpayable"), ("get", "view"), ("half", "nonpayable")]);
+        assert_eq!(json["functions"][2]["inputs"], serde_json::json!([{ "name": "v", "type": "i32" }]));
+        assert_eq!(json["functions"][1]["selector"], format!("0x{:08x}", abi::selector("add(i64)")));
+        assert!(json["functions"][1].get("output").is_none());
+        assert_eq!(json["events"][0]["topic"], format!("0x{:016x}", abi::topic("Deposited(i64,i64,bool)")));
+        assert_eq!(json["errors"][0]["fields"][1], serde_json::json!({ "name": "min", "type": "i64" }));
+
+        let program = session.check(file).unwrap();
+        let abi = abi::abi(&program);
+        assert_eq!(abi.functions[0].mutability, Mutability::Payable);
+        assert_eq!(abi.json().as_bytes(), artifacts[1].contents);
+        assert_eq!(custom_section(&artifacts[0].contents, abi::SECTION), Ok(Some(abi.digest().as_slice())));
+    }
+
+    #[test]
+    fn events_are_logged_and_custom_errors_revert() {
+        let mut session = Session::new();
+        let file = session.add_source("vault.mint", VAULT);
+        let mut chain = Chain::new();
+        let vault = chain.deploy(&session.compile(file).unwrap()).unwrap();
+        let alice = chain.create_account(1000);
+        assert_eq!(chain.call(vault, Message { sender: alice, value: 200 }, "deposit", &[]), Ok(Some(Value::I64(200))));
+        assert_eq!(chain.call(vault, Message { sender: alice, value: 0 }, "half", &[Value::F64(5.0)]), Ok(Some(Value::F64(2.5))));
+        assert_eq!(chain.events(), [
+            Event { contract: vault, topic: abi::topic("Deposited(i64,i64,bool)"), data: vec![alice, 200, 1] },
+            Event { contract: vault, topic: abi::topic("Halved(f64)"), data: vec![2.5f64.to_bits() as i64] },
+        ]);
+        let reverted = chain.call(vault, Message { sender: alice, value: 3 }, "deposit", &[]);
+        assert_eq!(reverted, Err(Trap::Revert { selector: abi::selector("TooSmall(i64,i64)"), data: vec![3, 10] }));
+        assert_eq!((chain.events().len(), chain.balance(vault)), (2, 200));
+    }
+
+    #[test]
+    fn events_and_errors_are_checked() {
+        let check = |src: &str| {
+            let mut session = Session::new();
+            let file = session.add_source("test.mint", src);
+            session.check(file).map(|_| ()).map_err(|ds| ds[0].message.clone())
+        };
+        assert!(check("contract C { error Bad(); fn f() -> i64 { revert Bad(); } }").is_ok());
+        let err = |src: &str| check(src).unwrap_err();
+        assert!(err("contract C { event E(a: i64); event E(); }").contains("declared more than once"));
+        assert!(err("contract C { event E(a: string); }").contains("cannot be `string`"));
+        assert!(err("contract C { event E(a); }").contains("needs a type"));
+        assert!(err("contract C { event E(a: i64); fn f() { emit E(true); } }").contains("expects `i64` but found `bool`"));
+        assert!(err("contract C { event E(a: i64); fn f() { emit E(); } }").contains("has 1 field(s) but 0 were supplied"));
+        assert!(err("contract C { event E(); fn f() { revert E(); } }").contains("Unknown error `E`"));
+        assert!(err("fn f() { emit E(); }").contains("only allowed in contract functions"));
+        assert!(err("#[test] #[payable] fn t() {}").contains("cannot be `#[payable]`"));
+        // the dispatcher's own errors cannot be shadowed, whatever their fields
+        assert!(err("contract C { error UnknownSelector(s: i64); }").contains("`UnknownSelector` in contract `C` is reserved"));
+        assert!(err("contract C { error NotPayable(); }").contains("`NotPayable` in contract `C` is reserved"));
+        assert!(err("contract C { error InvalidCalldata(); }").contains("is reserved"));
+        assert!(check("contract C { event NotPayable(); }").is_ok());
+    }
+
+    #[test]
+    fn colliding_selectors_are_reported_on_the_second_function() {
+        // `f13094()` and `f277567()` hash to the same selector.
+        let src = "fn f13094() {}\nfn f277567() {}\n#[test] fn t() {}";
+        assert_eq!(abi::selector("f13094()"), abi::selector("f277567()"));
+        let mut session = Session::new();
+        let file = session.add_source("test.mint", src);
+        let errors = session.emit(file, &[Emit::Abi]).unwrap_err();
+        let span = errors[0].labels.iter().find(|l| l.primary).unwrap().span;
+        assert_eq!(&src[span.start..span.end], "f277567");
+        assert!(errors[0].message.contains("0x50abc31a"), "{}", errors[0].message);
+        assert!(session.with_dispatcher(true).compile(file).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use crate::chain::{Chain, Message};
+    use crate::wasm::interp::{Trap, Value};
+    use crate::Session;
+
+    fn deploy(chain: &mut Chain, src: &str) -> i64 {
+        let mut session = Session::new();
+        let file = session.add_source("test.mint", src);
+        chain.deploy(&session.compile(file).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn contracts_keep_storage_between_calls_and_see_the_call_context() {
+        let mut chain = Chain::new();
+        let src = "contract Vault { storage total: i64; storage last: i64;\n\
+                   fn deposit() -> i64 { total += msg.value; last = msg.sender; return total; }\n\
+                   fn when() -> i64 { return block.number * 1000 + block.timestamp % 1000; } }";
+        let vault = deploy(&mut chain, src);
+        let alice = chain.create_account(100);
+        let bob = chain.create_account(10);
+        assert_eq!(chain.call(vault, Message { sender: alice, value: 40 }, "deposit", &[]), Ok(Some(Value::I64(40))));
+        assert_eq!(chain.call(vault, Message { sender: bob, value: 5 }, "deposit", &[]), Ok(Some(Value::I64(45))));
+        assert_eq!((chain.balance(alice), chain.balance(bob), chain.balance(vault)), (60, 5, 45));
+        assert!(matches!(chain.call(vault, Message { sender: bob, value: 6 }, "deposit", &[]), Err(Trap::Call(_))));
+        chain.advance(2, 7);
+        assert_eq!(chain.call(vault, Message { sender: bob, value: 0 }, "when", &[]), Ok(Some(Value::I64(3007))));
+    }
+
+    #[test]
+    fn a_trapping_call_reverts_its_effects() {
+        let mut chain = Chain::new();
+        let src = "contract C { storage n: i64; fn set(v: i64, d: i64) -> i64 { n = v; return n / d; } fn get() -> i64 { return n; } }";
+        let c = deploy(&mut chain, src);
+        let alice = chain.create_account(100);
+        let msg = Message { sender: alice, value: 10 };
+        assert_eq!(chain.call(c, msg, "set", &[Value::I64(8), Value::I64(2)]), Ok(Some(Value::I64(4))));
+        assert_eq!(chain.call(c, msg, "set", &[Value::I64(9), Value::I64(0)]), Err(Trap::DivisionByZero));
+        assert_eq!(chain.call(c, Message { sender: alice, value: 0 }, "get", &[]), Ok(Some(Value::I64(8))));
+        assert_eq!((chain.balance(alice), chain.balance(c)), (90, 10));
+    }
+
+    #[test]
+    fn a_transfer_cannot_overflow_the_contract_balance() {
+        let mut chain = Chain::new();
+        let c = deploy(&mut chain, "contract C { storage n: i64; fn bump() { n += 1; } fn get() -> i64 { return n; } }");
+        let (whale, minnow) = (chain.create_account(i64::MAX), chain.create_account(1));
+        assert_eq!(chain.call(c, Message { sender: whale, value: i64::MAX }, "bump", &[]), Ok(None));
+        assert!(matches!(chain.call(c, Message { sender: minnow, value: 1 }, "bump", &[]), Err(Trap::Call(m)) if m.contains("cannot receive")));
+        assert_eq!((chain.balance(minnow), chain.balance(c)), (1, i64::MAX));
+        assert_eq!(chain.call(c, Message { sender: minnow, value: 0 }, "get", &[]), Ok(Some(Value::I64(1))));
+    }
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use crate::{codegen::compile_to_wasm, compiler::type_check, diagnostic::Diagnostic, lexer::Lexer, parser::Parser};
+
+    fn check(src: &str) -> Result<(), Diagnostic> {
+        type_check(&Parser::new(Lexer::new(src)).parse().map_err(|mut ds| ds.remove(0))?)
+    }
+
+    #[test]
+    fn type_check_locals_mutability_and_scopes() {
+        assert!(check("fn f(a: i32) -> i32 { let mut x = a; x += 1; return x; }").is_ok());
+        assert!(check("fn f(a: i32) -> i32 { let x = a; x = 2; return x; }").is_err());
+        assert!(check("fn f() -> i32 { { let inner = 1; } return inner; }").is_err());
+        assert!(check("fn f() -> i32 { let s: string = 1; return 0; }").is_err());
+        assert!(check("fn f() -> i32 { let x = 1; }").is_err());
+        let err = check("fn f() -> i32 { let x: foo = 1; return x; }").unwrap_err();
+        assert!(err.message.starts_with("Unknown type `foo`"), "{}", err.message);
+    }
+
+    #[test]
+    fn type_check_control_flow() {
+        assert!(check("fn f(n: i32) -> i32 { while n > 0 { n = n - 1; } if n == 0 { return 1; } else { return 2; } }").is_err());
+        assert!(check("fn f(n: i32) -> i32 { let mut i = n; while i > 0 { i -= 1; } if i == 0 { return 1; } else { return 2; } }").is_ok());
+        assert!(check("fn f(n: i32) -> i32 { if n { return 1; } return 0; }").is_err());
+        assert!(check("fn f() -> i32 { break; return 0; }").is_err());
+        assert!(check("fn f(n: i32) -> i32 { if n > 1 { return 1; } }").is_err());
+    }
+
+    #[test]
+    fn compile_all_functions_with_shared_types() {
+        let src = "fn a(x: i32) -> i32 { return b(x) + 1; } fn b(x: i32) -> i32 { return x; } fn c(x: i32, y: i32) { a(x); }";
+        let prog = Parser::new(Lexer::new(src)).parse().unwrap();
+        type_check(&prog).unwrap();
+        let wasm = compile_to_wasm(&prog).unwrap();
+        // type section: 2 distinct signatures for 3 functions
+        assert_eq!(&wasm[8..11], &[0x01, 0x0B, 0x02]);
+
+        let bad = Parser::new(Lexer::new("fn ok() -> i32 { return 1; } fn f(s: money) -> i32 { return 0; }")).parse().unwrap();
+        let err = compile_to_wasm(&bad).unwrap_err();
+        assert!(err.message.contains("`f`") && err.message.contains("`s`"), "{}", err.message);
+        let err = type_check(&bad).unwrap_err();
+        assert!(err.message.starts_with("Unknown type `money`"), "{}", err.message);
+        assert!(check("fn f() -> money { return 0; }").is_err());
+    }
+
+    #[test]
+    fn contract_storage_is_checked_and_imported() {
+        let src = "contract C { storage total: i32; fn add(x: i32) -> i32 { total += x; return total; } }";
+        let prog = Parser::new(Lexer::new(src)).parse().unwrap();
+        type_check(&prog).unwrap();
+        let wasm = compile_to_wasm(&prog).unwrap();
+        let has = |needle: &[u8]| wasm.windows(needle.len()).any(|w| w == needle);
+        assert!(has(b"storage_read") && has(b"storage_write"));
+
+        assert!(check("contract C { storage total: i32; fn f() -> bool { return total; } }").is_err());
+        assert!(check("contract C { storage total: money; }").is_err());
+        assert!(check("contract C { storage total: i32; } fn f() -> i32 { return total; }").is_err());
+    }
+
+    #[test]
+    fn chain_builtins_import_only_what_is_used() {
+        let compile = |src: &str| {
+            let prog = Parser::new(Lexer::new(src)).parse().unwrap();
+            type_check(&prog).unwrap();
+            compile_to_wasm(&prog).unwrap()
+        };
+        let has = |wasm: &[u8], needle: &str| wasm.windows(needle.len()).any(|w| w == needle.as_bytes());
+
+        let wasm = compile("fn who() -> i64 { return msg.sender; }");
+        assert!(has(&wasm, "caller"));
+        assert!(!has(&wasm, "call_value") && !has(&wasm, "block_number") && !has(&wasm, "storage_read"));
+
+        let wasm = compile("fn f(x: i32) -> i32 { return x; }");
+        assert!(!has(&wasm, "env"));
+
+        assert!(check("fn f() -> i32 { return msg.origin; }").is_err());
+        assert!(check("fn f(a: i32) -> i32 { return a.sender; }").is_err());
+    }
+
+    #[test]
+    fn strings_live_in_memory_with_deduplicated_literals() {
+        let src = "fn f(s: string) -> bool { let t = \"ab\" + s; return len(t) == 3 && t != \"ab\"; }";
+        let prog = Parser::new(Lexer::new(src)).parse().unwrap();
+        type_check(&prog).unwrap();
+        let wasm = compile_to_wasm(&prog).unwrap();
+        let count = |bytes: &[u8], needle: &str| bytes.windows(needle.len()).filter(|w| *w == needle.as_bytes()).count();
+        assert_eq!(count(&wasm, "memory"), 1);
+        let sections = crate::wasm::decode::sections(&wasm).unwrap();
+        let data = sections.iter().find(|(id, _)| *id == 11).unwrap().1;
+        assert_eq!(count(data, "ab"), 1);
+
+        assert!(check("fn f() -> string { return \"a\" + 1; }").is_err());
+        assert!(check("fn f() -> i32 { return len(1); }").is_err());
+        assert!(check("fn f() -> bool { return \"a\" < \"b\"; }").is_err());
+    }
+
+    #[test]
+    fn numeric_types_widen_implicitly_and_narrow_with_casts() {
+        assert!(check("fn f(a: i32, b: i64) -> i64 { let c: f64 = a; return a + b; }").is_ok());
+        assert!(check("fn f(a: f64) -> i32 { return a as i32 + 1; }").is_ok());
+        assert!(check("fn f(b: i64) -> i32 { return b; }").is_err());
+        assert!(check("fn f(b: i64, c: f64) -> f64 { return b + c; }").is_err());
+        assert!(check("fn f(c: f64) -> f64 { return c % 2.0; }").is_err());
+        assert!(check("fn f() -> i32 { return \"1\" as i32; }").is_err());
+
+        let src = "fn f(a: i32, b: i64) -> f64 { return (a + b) as f64 * 0.5; } fn g(x: f64) -> f64 { return f(1, 2) + x; }";
+        let prog = Parser::new(Lexer::new(src)).parse().unwrap();
+        type_check(&prog).unwrap();
+        let wasm = compile_to_wasm(&prog).unwrap();
+        // (i32, i64) -> f64 and (f64) -> f64
+        assert!(wasm.windows(5).any(|w| w == [0x60, 0x02, 0x7F, 0x7E, 0x01]));
+        assert!(wasm.windows(5).any(|w| w == [0x60, 0x01, 0x7C, 0x01, 0x7C]));
+    }
+}
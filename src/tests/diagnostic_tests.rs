@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::{compiler::type_check, diagnostic::codes, lexer::Lexer, parser::Parser, span::{line_col, SourceMap}};
+
+    #[test]
+    fn parse_errors_point_at_the_offending_source() {
+        let src = "fn f() -> i32 {\n    return 1 }\n";
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.mint", src);
+        let d = Parser::new(Lexer::new(src)).with_file(file).parse().unwrap_err().remove(0);
+        assert_eq!(d.code, codes::UNEXPECTED_TOKEN);
+        assert_eq!(line_col(src, d.labels[0].span.start), (2, 13));
+        assert_eq!(
+            d.render(&sources),
+            "error[E0101]: Expected `;` but found `}`\n --> a.mint:2:13\n  |\n2 |     return 1 }\n  |             ^ expected `;`\n"
+        );
+
+        // running out of input names the construct left open
+        let d = Parser::new(Lexer::new("fn f() {\n  let x = 1;\n")).parse().unwrap_err().remove(0);
+        assert!(d.message.ends_with("end of input"), "{}", d.message);
+        assert!(d.labels.iter().any(|l| !l.primary && l.message == "block starts here"));
+
+        // lexical errors are reported as such, including ones skipped during recovery
+        let errors = Parser::new(Lexer::new("fn f() { let = 1; let s = \"a\\q\"; }\nfn g() { \"open")).parse().unwrap_err();
+        let found: Vec<_> = errors.iter().map(|d| d.code).collect();
+        assert_eq!(found, [codes::UNEXPECTED_TOKEN, codes::INVALID_TOKEN, codes::INVALID_TOKEN, codes::UNEXPECTED_TOKEN]);
+        assert!(errors[2].message.starts_with("Unterminated string"), "{}", errors[2].message);
+    }
+
+    #[test]
+    fn type_errors_point_at_the_expression_and_its_declaration() {
+        let src = "fn f() -> i32 {\n    let x = 1;\n    x = true;\n    return x;\n}";
+        let prog = Parser::new(Lexer::new(src)).parse().unwrap();
+        let d = type_check(&prog).unwrap_err();
+        assert_eq!(d.code, codes::TYPE);
+        let at = |primary: bool| d.labels.iter().find(|l| l.primary == primary).map(|l| (line_col(src, l.span.start), &src[l.span.start..l.span.end]));
+        assert_eq!(at(true), Some(((3, 5), "x")));
+        assert_eq!(at(false), Some(((2, 9), "x")));
+
+        let d = type_check(&Parser::new(Lexer::new("fn f() -> i32 { return 1 + true; }")).parse().unwrap()).unwrap_err();
+        assert_eq!(line_col("fn f() -> i32 { return 1 + true; }", d.labels[0].span.start), (1, 24));
+    }
+
+    #[test]
+    fn labels_on_one_line_share_a_single_copy_of_it() {
+        let src = "fn f() -> i32 { return 1; } fn f() -> i32 { return 2; }";
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.mint", src);
+        let d = type_check(&Parser::new(Lexer::new(src)).with_file(file).parse().unwrap()).unwrap_err();
+        assert_eq!(
+            d.render(&sources),
+            "error[E0201]: Function `f` is defined more than once\n --> a.mint:1:32\n  |\n\
+             1 | fn f() -> i32 { return 1; } fn f() -> i32 { return 2; }\n\
+            \x20 |    -                           ^\n\
+            \x20 |    |\n\
+            \x20 |    first defined here\n"
+        );
+    }
+
+    #[test]
+    fn diagnostics_and_the_ast_serialize_to_json() {
+        let src = "fn f() -> i32 {\n    return 1 }\n";
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.mint", src);
+        let d = Parser::new(Lexer::new(src)).with_file(file).parse().unwrap_err().remove(0);
+        let json = d.to_json(&sources);
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["code"], "E0101");
+        let label = &json["labels"][0];
+        assert_eq!((&label["file_name"], &label["line_start"], &label["column_start"]), (&"a.mint".into(), &2.into(), &13.into()));
+        assert_eq!(label["span"], serde_json::json!({ "file": 0, "start": 28, "end": 28 }));
+        assert_eq!(json["rendered"], d.render(&sources));
+
+        let prog = Parser::new(Lexer::new("fn f() -> i32 { return -1; }")).parse().unwrap();
+        let json = serde_json::to_value(&prog).unwrap();
+        let ret = &json["statements"][0]["kind"]["Function"]["body"][0]["kind"]["Return"];
+        assert_eq!(ret["kind"]["Unary"]["op"], "Neg");
+        assert_eq!(ret["span"]["start"], 23);
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use crate::abi::{self, Token};
+    use crate::chain::{Chain, Message};
+    use crate::wasm::interp::Trap;
+    use crate::Session;
+
+    const VAULT: &str = "contract Vault {
+        storage total: i64;
+
+        #[payable]
+        fn deposit(amount: i64) -> i64 { total += amount; return total; }
+        fn greet(name: string, loud: bool) -> string {
+            if loud { return \"HELLO \" + name; }
+            return \"hello \" + name;
+        }
+        fn scale(x: f64, n: i32) -> f64 { return x * n; }
+        fn negate(v) -> i32 { return -v; }
+        fn is_rich(min: i64) -> bool { return total >= min; }
+        fn reset() { total = 0; }
+    }";
+
+    fn deploy(chain: &mut Chain, src: &str) -> i64 {
+        let mut session = Session::new().with_dispatcher(true);
+        let file = session.add_source("vault.mint", src);
+        chain.deploy(&session.compile(file).unwrap()).unwrap()
+    }
+
+    fn calldata(signature: &str, args: &[Token]) -> Vec<u8> {
+        abi::encode_call(abi::selector(signature), args)
+    }
+
+    #[test]
+    fn calls_round_trip_through_the_dispatcher() {
+        let mut chain = Chain::new();
+        let vault = deploy(&mut chain, VAULT);
+        let alice = chain.create_account(100);
+        let msg = Message { sender: alice, value: 0 };
+        let mut call = |signature: &str, args: &[Token], output: &[&str]| {
+            let data = chain.transact(vault, msg, &calldata(signature, args)).unwrap();
+            abi::decode(output, &data).unwrap()
+        };
+        assert_eq!(call("deposit(i64)", &[Token::I64(1 << 40)], &["i64"]), [Token::I64(1 << 40)]);
+        assert_eq!(call("is_rich(i64)", &[Token::I64(1 << 41)], &["bool"]), [Token::Bool(false)]);
+        assert_eq!(call("greet(string,bool)", &[Token::String("wörld".into()), Token::Bool(true)], &["string"]), [Token::String("HELLO wörld".into())]);
+        assert_eq!(call("greet(string,bool)", &[Token::String(String::new()), Token::Bool(false)], &["string"]), [Token::String("hello ".into())]);
+        assert_eq!(call("scale(f64,i32)", &[Token::F64(1.5), Token::I32(-3)], &["f64"]), [Token::F64(-4.5)]);
+        assert_eq!(call("negate(i32)", &[Token::I32(i32::MAX)], &["i32"]), [Token::I32(-i32::MAX)]);
+        assert_eq!(call("reset()", &[], &[]), []);
+        assert_eq!(call("is_rich(i64)", &[Token::I64(1)], &["bool"]), [Token::Bool(false)]);
+    }
+
+    #[test]
+    fn malformed_calls_revert_with_the_reserved_errors() {
+        let mut chain = Chain::new();
+        let vault = deploy(&mut chain, VAULT);
+        let alice = chain.create_account(100);
+        let msg = Message { sender: alice, value: 0 };
+        let invalid = Err(Trap::Revert { selector: abi::selector(abi::INVALID_CALLDATA), data: Vec::new() });
+        assert_eq!(chain.transact(vault, msg, &[1, 2]), invalid);
+        assert_eq!(chain.transact(vault, msg, &calldata("deposit(i64)", &[])), invalid);
+        let mut long = calldata("deposit(i64)", &[Token::I64(1)]);
+        long.push(0);
+        assert_eq!(chain.transact(vault, msg, &long), invalid);
+        // A string longer than the calldata left after its length word.
+        let mut truncated = calldata("greet(string,bool)", &[Token::String("abc".into()), Token::Bool(true)]);
+        truncated[4] = 100;
+        assert_eq!(chain.transact(vault, msg, &truncated), invalid);
+
+        let unknown = calldata("withdraw(i64)", &[Token::I64(1)]);
+        assert_eq!(chain.transact(vault, msg, &unknown), Err(Trap::Revert {
+            selector: abi::selector(abi::UNKNOWN_SELECTOR), data: vec![abi::selector("withdraw(i64)") as i64],
+        }));
+
+        let paid = Message { sender: alice, value: 7 };
+        assert_eq!(chain.transact(vault, paid, &calldata("reset()", &[])), Err(Trap::Revert {
+            selector: abi::selector(abi::NOT_PAYABLE), data: vec![7],
+        }));
+        assert_eq!(chain.transact(vault, paid, &calldata("deposit(i64)", &[Token::I64(7)])), Ok(abi::encode(&[Token::I64(7)])));
+        assert_eq!(chain.balance(alice), 93);
+    }
+
+    #[test]
+    fn tokens_round_trip_through_the_encoding() {
+        let tokens = [Token::I32(-1), Token::String("héllo".into()), Token::Bool(true), Token::F64(0.1), Token::I64(i64::MIN)];
+        let types = ["i32", "string", "bool", "f64", "i64"];
+        let bytes = abi::encode(&tokens);
+        assert_eq!(&bytes[..16], [[0xFF; 8], 6i64.to_le_bytes()].concat());
+        assert_eq!(abi::decode(&types, &bytes).unwrap(), tokens);
+        assert_eq!(abi::decode(&types, &bytes[..bytes.len() - 1]), None);
+        assert_eq!(abi::decode(&types[..4], &bytes), None);
+        assert_eq!(abi::encode_call(0x01020304, &[])[..], [1, 2, 3, 4]);
+        assert_eq!(Token::parse("bool", "true"), Some(Token::Bool(true)));
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use crate::{fmt::format, span::FileId};
+
+    fn fmt(src: &str) -> String {
+        format(src, FileId(0)).unwrap()
+    }
+
+    #[test]
+    fn examples_are_formatted_and_formatting_is_idempotent() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let src = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(fmt(&src), src, "{} is not formatted", path.display());
+            // Squashed onto as few lines as the comments allow, it formats back the same way.
+            let squashed: String = src.lines().map(|l| if l.contains("//") { format!("{}\n", l) } else { format!("{} ", l.trim()) }).collect();
+            assert_eq!(fmt(&squashed), fmt(&fmt(&squashed)), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn canonical_layout_keeps_comments() {
+        let src = "// header\ncontract C { storage n: i64; // count\n  fn get()->i64{return n;}\n}\n\
+                   fn f(a:i32)->i32{\n\n\n  let x=-a ;   /* why */\n\n  // next\n  if x>0{return (x+1)*2;}else{}\n  return x;\n  // tail\n}";
+        let expected = "// header\ncontract C {\n    storage n: i64; // count\n\n    fn get() -> i64 {\n        return n;\n    }\n}\n\n\
+                        fn f(a: i32) -> i32 {\n    let x = -a; /* why */\n\n    // next\n    if x > 0 {\n        return (x + 1) * 2;\n    } else {}\n    return x;\n    // tail\n}\n";
+        assert_eq!(fmt(src), expected);
+        assert_eq!(fmt(expected), expected);
+        assert_eq!(fmt(""), "");
+        assert_eq!(fmt("fn f(a: i32,) { f(a,); }"), "fn f(a: i32) {\n    f(a);\n}\n");
+        assert!(format("fn f( {", FileId(0)).is_err());
+    }
+
+    #[test]
+    fn long_lists_and_expressions_wrap_to_the_line_width() {
+        let params = (0..8).map(|i| format!("parameter_{}: i64", i)).collect::<Vec<_>>().join(", ");
+        let sum = (0..8).map(|i| format!("parameter_{}", i)).collect::<Vec<_>>().join(" + ");
+        let out = fmt(&format!("fn f({}) -> i64 {{ return g({}, {}); }}", params, sum, sum));
+        assert!(out.lines().all(|l| l.len() <= crate::fmt::MAX_WIDTH), "{}", out);
+        assert!(out.starts_with("fn f(\n    parameter_0: i64,\n"), "{}", out);
+        assert!(out.contains("    return g(\n        parameter_0\n            + parameter_1\n"), "{}", out);
+        assert_eq!(fmt(&out), out);
+    }
+}
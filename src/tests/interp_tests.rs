@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use crate::wasm::interp::{Instance, NoHost, Trap, Value};
+    use crate::wasm::{FuncType, ModuleBuilder, ValType};
+    use crate::Session;
+
+    fn instance(src: &str) -> Instance {
+        let mut session = Session::new();
+        let file = session.add_source("test.mint", src);
+        Instance::new(&session.compile(file).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn compiled_functions_run_in_the_interpreter() {
+        let src = "fn fib(n: i64) -> i64 { if n < 2 { return n; } return fib(n - 1) + fib(n - 2); }\n\
+                   fn sum_to(n: i32) -> i32 { let mut i = 0; let mut total = 0; while true { if i > n { break; } total += i; i += 1; } return total; }\n\
+                   fn half(x: i32) -> f64 { return x as f64 / 2.0; }\n\
+                   fn shout(name: bool) -> string { let s = \"hi\" + \", \" + \"there\"; if name && s == \"hi, there\" { return s + \"!\"; } return \"\"; }";
+        let mut vm = instance(src);
+        assert_eq!(vm.invoke("fib", &[Value::I64(20)], &mut NoHost), Ok(Some(Value::I64(6765))));
+        assert_eq!(vm.invoke("sum_to", &[Value::I32(100)], &mut NoHost), Ok(Some(Value::I32(5050))));
+        assert_eq!(vm.invoke("half", &[Value::I32(-5)], &mut NoHost), Ok(Some(Value::F64(-2.5))));
+        let Ok(Some(Value::I64(packed))) = vm.invoke("shout", &[Value::I32(1)], &mut NoHost) else { panic!() };
+        assert_eq!(vm.string(packed).unwrap(), "hi, there!");
+    }
+
+    #[test]
+    fn traps_stop_execution() {
+        let src = "fn div(a: i64, b: i64) -> i64 { return a / b; }\n\
+                   fn forever(n: i32) -> i32 { return forever(n + 1); }\n\
+                   contract C { storage n: i64; fn get() -> i64 { return n; } }";
+        let mut vm = instance(src);
+        assert_eq!(vm.invoke("div", &[Value::I64(1), Value::I64(0)], &mut NoHost), Err(Trap::DivisionByZero));
+        assert_eq!(vm.invoke("div", &[Value::I64(i64::MIN), Value::I64(-1)], &mut NoHost), Err(Trap::IntegerOverflow));
+        assert_eq!(vm.invoke("forever", &[Value::I32(0)], &mut NoHost), Err(Trap::StackOverflow));
+        assert!(matches!(vm.invoke("get", &[], &mut NoHost), Err(Trap::Host(m)) if m.contains("storage_read")));
+        assert!(matches!(vm.invoke("div", &[Value::I32(1)], &mut NoHost), Err(Trap::Call(_))));
+        assert!(matches!(vm.invoke("missing", &[], &mut NoHost), Err(Trap::Call(_))));
+    }
+
+    /// A module exporting `f() -> i32` with the given code and no locals.
+    fn raw(code: &[u8]) -> Result<Instance, String> {
+        let mut module = ModuleBuilder::new();
+        let f = module.declare_function(FuncType { params: Vec::new(), results: vec![ValType::I32] });
+        module.set_body(f, [&[0x00][..], code, &[0x0B]].concat());
+        module.export_function("f", f);
+        Instance::new(&module.finish())
+    }
+
+    #[test]
+    fn modules_the_interpreter_cannot_run_are_rejected() {
+        assert_eq!(raw(&[0x41, 0x07]).unwrap().invoke("f", &[], &mut NoHost), Ok(Some(Value::I32(7))));
+        assert_eq!(raw(&[0x00]).unwrap().invoke("f", &[], &mut NoHost), Err(Trap::Unreachable));
+        // i32.extend8_s, f32.const
+        assert!(raw(&[0x41, 0x07, 0xC0]).is_err());
+        let err = |code: &[u8]| raw(code).err().expect("the module is rejected");
+        assert!(err(&[0x43, 0, 0, 0, 0, 0x1A, 0x41, 0x07]).contains("f32.const"));
+        assert!(err(&[0x6A]).contains("underflows"), "{}", err(&[0x6A])); // i32.add
+        assert!(err(&[]).contains("instead of 1"));
+        assert!(err(&[0x02, 0x40, 0x41, 0x01, 0x0B, 0x41, 0x01]).contains("instead of 0")); // block leaving a value
+        assert!(err(&[0x20, 0x00]).contains("no local"));
+        assert!(err(&[0x0C, 0x01]).contains("no enclosing block"));
+        assert!(err(&[0x41, 0x00, 0x28, 0x02, 0x00]).contains("memory"));
+        assert!(err(&[0x10, 0x05]).contains("no function"));
+
+        let mut module = ModuleBuilder::new();
+        let f = module.declare_function(FuncType { params: Vec::new(), results: Vec::new() });
+        module.set_body(f, vec![0x00, 0x0B]);
+        module.set_memory(u32::MAX, "memory");
+        assert!(Instance::new(&module.finish()).err().expect("the module is rejected").contains("larger than the limit"));
+    }
+}
@@ -1,12 +1,39 @@
-#[cfg(test)]
-mod tests {
-    use crate::lexer::{Lexer, TokenKind};
-
-    #[test]
-    fn lex_basic_sequence() {
-        let src = r#"fn foo(x: i32) -> i32 { return 42; }"#;
-        let kinds: Vec<_> = Lexer::new(src).map(|t| t.kind).collect();
-        assert!(kinds.len() > 0);
-        assert!(matches!(kinds[0], TokenKind::Keyword(ref k) if k == "fn"));
-    }
-}
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{Lexer, TokenKind};
+
+    #[test]
+    fn lex_basic_sequence() {
+        let src = r#"fn foo(x: i32) -> i32 { return 42; }"#;
+        let kinds: Vec<_> = Lexer::new(src).map(|t| t.kind).collect();
+        assert!(!kinds.is_empty());
+        assert!(matches!(kinds[0], TokenKind::Keyword(ref k) if k == "fn"));
+    }
+
+    #[test]
+    fn lex_multi_char_operators() {
+        let kinds: Vec<_> = Lexer::new("a <= b && c != d >> 1 || !e -> f").map(|t| t.kind).collect();
+        let ops: Vec<_> = kinds.into_iter().filter(|k| !matches!(k, TokenKind::Ident(_) | TokenKind::Number(_))).collect();
+        assert_eq!(ops, vec![
+            TokenKind::LtEq, TokenKind::AndAnd, TokenKind::NotEq, TokenKind::Shr,
+            TokenKind::OrOr, TokenKind::Bang, TokenKind::Arrow,
+        ]);
+    }
+
+    #[test]
+    fn lex_comments_strings_and_number_forms() {
+        let kinds = |src: &str| Lexer::new(src).map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(kinds("a /* outer /* inner */ still comment */ b"), kinds("a b"));
+        assert_eq!(kinds(r#""tab\there\n" 'it\'s' "\u{1F600}\"""#), vec![
+            TokenKind::StringLiteral("tab\there\n".into()),
+            TokenKind::StringLiteral("it's".into()),
+            TokenKind::StringLiteral("\u{1F600}\"".into()),
+        ]);
+        let numbers = kinds("0xFF_FF 0b1010 1_000_000 2.5e-3 1E9");
+        assert!(numbers.iter().all(|k| matches!(k, TokenKind::Number(_))), "{:?}", numbers);
+
+        for bad in ["\"open", "'\\q'", "/* open /* */", "1.2.3", "0b102", "0x", "1e+", "5px", "@"] {
+            assert!(matches!(kinds(bad)[..], [TokenKind::Error(_)]), "{}: {:?}", bad, kinds(bad));
+        }
+    }
+}
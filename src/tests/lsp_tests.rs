@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::analysis::{analyze, SymbolKind};
+    use crate::lsp::{self, Server};
+    use crate::span::FileId;
+    use crate::syntax;
+
+    const SRC: &str = "contract C {\n    storage total: i64;\n    fn add(v: i64) -> i64 {\n        let t = total + v;\n        \
+                       if t > 0 { let t = 1.5; total = t as i64; }\n        return t;\n    }\n}\nfn twice() -> i64 { return add(1) * 2; }\n";
+
+    fn at(needle: &str, nth: usize) -> usize {
+        SRC.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    #[test]
+    fn hover_definition_and_completion_follow_scopes() {
+        let analysis = analyze(&syntax::lower(&syntax::parse(SRC, FileId(0)).0));
+        let hover = |offset| analysis.hover(offset).map(|(_, text)| text);
+        assert_eq!(hover(at("total", 1)).as_deref(), Some("storage total: i64"));
+        assert_eq!(hover(at("t = 1.5", 0)).as_deref(), Some("let t: f64"));
+        assert_eq!(hover(at("t;", 0)).as_deref(), Some("let t: i64"));
+        assert_eq!(hover(at("add(1)", 0)).as_deref(), Some("fn add(v: i64) -> i64"));
+        assert_eq!(hover(at("* 2", 0) + 2).as_deref(), Some("i32"));
+        // Each `t` goes to the declaration in scope where it is used.
+        assert_eq!(analysis.definition(at("t as", 0)).map(|s| s.start), Some(at("t = 1.5", 0)));
+        assert_eq!(analysis.definition(at("t;", 0)).map(|s| s.start), Some(at("t = total", 0)));
+        assert_eq!(analysis.definition(at("add(1)", 0)).map(|s| s.start), Some(at("add", 0)));
+        let names = |offset| analysis.completions(SRC, offset).into_iter().map(|c| (c.label, c.kind)).collect::<Vec<_>>();
+        let inside = names(at("return t", 0));
+        for expected in [("t", SymbolKind::Local), ("v", SymbolKind::Param), ("total", SymbolKind::Storage), ("twice", SymbolKind::Function), ("while", SymbolKind::Keyword)] {
+            assert!(inside.contains(&(expected.0.to_string(), expected.1)), "{:?}", expected);
+        }
+        assert!(!names(at("return add", 0)).iter().any(|(n, _)| n == "total" || n == "t"));
+        let members = names(SRC.len()).into_iter().chain(analysis.completions("x = msg.se", 10).into_iter().map(|c| (c.label, c.kind)));
+        assert!(members.map(|(n, _)| n).any(|n| n == "sender"));
+        let outline: Vec<&str> = analysis.symbols[0].children.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!((analysis.symbols.len(), outline), (2, vec!["total", "add"]));
+    }
+
+    fn message(server: &mut Server, method: &str, params: Value) -> Vec<Value> {
+        server.handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+    }
+
+    #[test]
+    fn server_publishes_diagnostics_and_answers_requests() {
+        let mut server = Server::new();
+        assert_eq!(message(&mut server, "initialize", json!({}))[0]["result"]["capabilities"]["hoverProvider"], true);
+        let uri = "file:///a.mint";
+        let open = server.handle(&json!({ "method": "textDocument/didOpen", "params": { "textDocument": { "uri": uri, "text": "fn f() -> i64 {\n  let s = \"é😀\"; return y;\n}" } } }));
+        let diagnostic = &open[0]["params"]["diagnostics"][0];
+        // Columns count UTF-16 code units, so the emoji is two.
+        assert_eq!(diagnostic["range"], json!({ "start": { "line": 1, "character": 24 }, "end": { "line": 1, "character": 25 } }));
+        assert_eq!(diagnostic["message"], "Unknown identifier `y`");
+        let hover = message(&mut server, "textDocument/hover", json!({ "textDocument": { "uri": uri }, "position": { "line": 1, "character": 6 } }));
+        assert_eq!(hover[0]["result"]["contents"]["value"], "```mintora\nlet s: string\n```");
+        let change = json!({ "textDocument": { "uri": uri }, "contentChanges": [{ "text": "fn f() -> i64 { return 1 }" }] });
+        let changed = server.handle(&json!({ "method": "textDocument/didChange", "params": change }));
+        assert_eq!(changed[0]["params"]["diagnostics"][0]["code"], "E0101");
+        assert_eq!(message(&mut server, "textDocument/rename", json!({}))[0]["error"]["code"], -32601);
+        assert_eq!(message(&mut server, "shutdown", Value::Null)[0]["result"], Value::Null);
+        assert_eq!(message(&mut server, "textDocument/hover", json!({}))[0]["error"]["code"], -32600);
+    }
+
+    #[test]
+    fn annotations_naming_no_type_are_diagnosed() {
+        let mut server = Server::new();
+        let open = server.handle(&json!({ "method": "textDocument/didOpen", "params": { "textDocument": { "uri": "file:///a.mint", "text": "fn f(a: foo) {}" } } }));
+        let diagnostics = open[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["range"], json!({ "start": { "line": 0, "character": 8 }, "end": { "line": 0, "character": 11 } }));
+        assert_eq!(diagnostics[0]["code"], "E0201");
+    }
+
+    #[test]
+    fn messages_are_framed_by_content_length() {
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"shutdown"}"#;
+        let input = format!("Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}Content-Length: 17\r\n\r\n{{\"method\":\"exit\"}}", body.len(), body);
+        let mut output = Vec::new();
+        assert!(lsp::run(input.as_bytes(), &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "Content-Length: 38\r\n\r\n{\"id\":7,\"jsonrpc\":\"2.0\",\"result\":null}");
+        assert_eq!(lsp::offset("a\n😀b", &json!({ "line": 1, "character": 2 })), 6);
+    }
+}
@@ -0,0 +1,15 @@
+mod abi_tests;
+mod chain_tests;
+mod compiler_tests;
+mod diagnostic_tests;
+mod dispatch_tests;
+mod fmt_tests;
+mod interp_tests;
+mod lexer_tests;
+mod lsp_tests;
+mod parser_tests;
+mod session_tests;
+mod syntax_tests;
+mod testing_tests;
+mod vscode_tests;
+mod wasm_tests;
@@ -1,25 +1,171 @@
-#[cfg(test)]
-mod tests {
-    use crate::{lexer::Lexer, parser::Parser, ast::{Statement, Param}};
-
-    #[test]
-    fn parse_params_and_return_type() {
-        let src = "fn add(a: i32, b: i32) -> i32 { return a; }";
-        let mut p = Parser::new(Lexer::new(src));
-        let prog = p.parse().expect("Failed to parse function");
-        assert_eq!(prog.statements.len(), 1);
-        match &prog.statements[0] {
-            Statement::Function { name, params, return_type, body } => {
-                assert_eq!(name, "add");
-                assert_eq!(params.len(), 2);
-                assert_eq!(params[0].name, "a");
-                assert_eq!(params[0].ty.as_deref(), Some("i32"));
-                assert_eq!(params[1].name, "b");
-                assert_eq!(params[1].ty.as_deref(), Some("i32"));
-                assert_eq!(return_type.as_deref(), Some("i32"));
-                assert_eq!(body.len(), 1);
-            }
-            _ => panic!("Expected function statement"),
-        }
-    }
-}
+#[cfg(test)]
+mod tests {
+    use crate::{lexer::Lexer, parser::Parser, ast::{BinOp, Expr, ExprKind, FileId, Span, StmtKind}, diagnostic::Diagnostic};
+
+    #[test]
+    fn parse_params_and_return_type() {
+        let src = "fn add(a: i32, b: i32) -> i32 { return a; }";
+        let mut p = Parser::new(Lexer::new(src));
+        let prog = p.parse().expect("Failed to parse function");
+        assert_eq!(prog.statements.len(), 1);
+        match &prog.statements[0].kind {
+            StmtKind::Function { name, params, return_type, body, .. } => {
+                assert_eq!(name, "add");
+                assert_eq!(params.len(), 2);
+                assert_eq!(params[0].name, "a");
+                assert_eq!(params[0].ty.as_ref().map(|t| t.as_str()), Some("i32"));
+                assert_eq!(params[1].name, "b");
+                assert_eq!(params[1].ty.as_ref().map(|t| t.as_str()), Some("i32"));
+                assert_eq!(return_type.as_ref().map(|t| t.as_str()), Some("i32"));
+                assert_eq!(body.len(), 1);
+            }
+            _ => panic!("Expected function statement"),
+        }
+    }
+
+    #[test]
+    fn parameter_and_argument_lists_take_a_trailing_comma() {
+        let prog = Parser::new(Lexer::new("fn add(a: i32, b: i32,) -> i32 { return add(a, b,); }")).parse().unwrap();
+        let StmtKind::Function { params, body, .. } = &prog.statements[0].kind else { panic!("Expected function statement") };
+        assert_eq!(params.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        let StmtKind::Return(Some(Expr { kind: ExprKind::Call { args, .. }, .. })) = &body[0].kind else { panic!("Expected a returned call") };
+        assert_eq!(args.len(), 2);
+        // only after an item, never on its own
+        assert!(Parser::new(Lexer::new("fn f(,) {}")).parse().is_err());
+        assert!(Parser::new(Lexer::new("fn f(a,,) {}")).parse().is_err());
+        assert!(Parser::new(Lexer::new("fn f() { f(,); }")).parse().is_err());
+    }
+
+    fn parse_return_expr(expr: &str) -> Result<Expr, Diagnostic> {
+        let src = format!("fn f(a: i32, b: i32, c: i32) -> i32 {{ return {}; }}", expr);
+        let prog = Parser::new(Lexer::new(&src)).parse().map_err(|mut ds| ds.remove(0))?;
+        match &prog.statements[0].kind {
+            StmtKind::Function { body, .. } => match &body[0].kind {
+                StmtKind::Return(Some(e)) => Ok(e.clone()),
+                _ => panic!("Expected return statement"),
+            },
+            _ => panic!("Expected function statement"),
+        }
+    }
+
+    #[test]
+    fn parse_operator_precedence_and_associativity() {
+        // a - b - c * 2  ==>  (a - b) - (c * 2)
+        match parse_return_expr("a - b - c * 2").unwrap().kind {
+            ExprKind::Binary { op: BinOp::Sub, left, right } => {
+                assert!(matches!(left.kind, ExprKind::Binary { op: BinOp::Sub, .. }));
+                assert!(matches!(right.kind, ExprKind::Binary { op: BinOp::Mul, .. }));
+            }
+            other => panic!("Unexpected shape: {:?}", other),
+        }
+        // parentheses override precedence; comparisons bind looser than arithmetic
+        match parse_return_expr("(a + b) * c < -a || !(b == c)").unwrap().kind {
+            ExprKind::Binary { op: BinOp::Or, left, .. } => match left.kind {
+                ExprKind::Binary { op: BinOp::Lt, left, .. } =>
+                    assert!(matches!(left.kind, ExprKind::Binary { op: BinOp::Mul, .. })),
+                other => panic!("Unexpected shape: {:?}", other),
+            },
+            other => panic!("Unexpected shape: {:?}", other),
+        }
+        assert!(parse_return_expr("a < b < c").is_err());
+    }
+
+    #[test]
+    fn parse_numeric_literals_and_casts() {
+        // `as` binds tighter than `*` but looser than unary minus
+        match parse_return_expr("-a as f64 * 2.5").unwrap().kind {
+            ExprKind::Binary { op: BinOp::Mul, left, right } => {
+                assert!(matches!(left.kind, ExprKind::Cast { ref expr, ref ty } if ty == "f64" && matches!(expr.kind, ExprKind::Unary { .. })));
+                assert!(matches!(right.kind, ExprKind::Float(f) if f == 2.5));
+            }
+            other => panic!("Unexpected shape: {:?}", other),
+        }
+        assert!(matches!(parse_return_expr("18446744073709551615").unwrap().kind, ExprKind::Int(u64::MAX)));
+        assert!(matches!(parse_return_expr("0xFFFF_FFFF_FFFF_FFFF").unwrap().kind, ExprKind::Int(u64::MAX)));
+        assert!(matches!(parse_return_expr("0b1_0000_0000").unwrap().kind, ExprKind::Int(256)));
+        assert!(matches!(parse_return_expr("1_5e-1").unwrap().kind, ExprKind::Float(f) if f == 1.5));
+        assert!(parse_return_expr("0x1_0000_0000_0000_0000").is_err());
+        assert!(parse_return_expr("18446744073709551616").is_err());
+        assert!(parse_return_expr("1.2.3").is_err());
+    }
+
+    #[test]
+    fn parse_let_and_assignments() {
+        let src = "fn f() -> i32 { let mut x: i32 = 1; x = x + 2; x <<= 1; { let y = x; } return x; }";
+        let prog = Parser::new(Lexer::new(src)).parse().expect("Failed to parse");
+        let StmtKind::Function { body, .. } = &prog.statements[0].kind else { panic!("Expected function") };
+        assert!(matches!(&body[0].kind, StmtKind::Let { name, mutable: true, ty: Some(t), .. } if name == "x" && t == "i32"));
+        assert!(matches!(&body[1].kind, StmtKind::Assign { op: None, .. }));
+        assert!(matches!(&body[2].kind, StmtKind::Assign { op: Some(BinOp::Shl), .. }));
+        assert!(matches!(&body[3].kind, StmtKind::Block(inner) if inner.len() == 1));
+    }
+
+    #[test]
+    fn parse_control_flow() {
+        let src = "fn f(n: i32) -> i32 { while n > 0 { if n == 1 { break; } else if n == 2 { continue; } else { n -= 1; } } return n; }";
+        let prog = Parser::new(Lexer::new(src)).parse().expect("Failed to parse");
+        let StmtKind::Function { body, .. } = &prog.statements[0].kind else { panic!("Expected function") };
+        let StmtKind::While { body: loop_body, .. } = &body[0].kind else { panic!("Expected while") };
+        match &loop_body[0].kind {
+            StmtKind::If { then_body, else_body: Some(else_body), .. } => {
+                assert!(matches!(then_body[0].kind, StmtKind::Break));
+                assert!(matches!(&else_body[..], [s] if matches!(s.kind, StmtKind::If { else_body: Some(_), .. })));
+            }
+            other => panic!("Expected if/else chain, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_contract_with_storage() {
+        let src = "contract Counter { storage count: i32; fn bump() -> i32 { count += 1; return count; } }";
+        let prog = Parser::new(Lexer::new(src)).parse().expect("Failed to parse contract");
+        match &prog.statements[0].kind {
+            StmtKind::Contract { name, fields, functions, .. } => {
+                assert_eq!(name, "Counter");
+                assert_eq!(fields.len(), 1);
+                assert_eq!((fields[0].name.as_str(), fields[0].ty.as_str()), ("count", "i32"));
+                assert!(matches!(&functions[0].kind, StmtKind::Function { name, .. } if name == "bump"));
+            }
+            other => panic!("Expected contract, got {:?}", other),
+        }
+        assert!(Parser::new(Lexer::new("contract C { let x = 1; }")).parse().is_err());
+    }
+
+    #[test]
+    fn every_node_carries_its_span() {
+        let src = "fn f(a: i32) -> i32 {\n    let y = a * 2;\n    return y + 1;\n}";
+        let prog = Parser::new(Lexer::new(src)).with_file(FileId(3)).parse().expect("Failed to parse");
+        let text = |span: Span| { assert_eq!(span.file, FileId(3)); &src[span.start..span.end] };
+        let f = &prog.statements[0];
+        assert!(text(f.span).starts_with("fn f(") && text(f.span).ends_with('}'));
+        let StmtKind::Function { name, params, return_type, body, .. } = &f.kind else { panic!("Expected function") };
+        assert_eq!((text(name.span), text(params[0].span)), ("f", "a: i32"));
+        assert_eq!(text(params[0].ty.as_ref().unwrap().span), "i32");
+        assert_eq!(text(return_type.as_ref().unwrap().span), "i32");
+        assert_eq!(text(body[0].span), "let y = a * 2;");
+        let StmtKind::Return(Some(value)) = &body[1].kind else { panic!("Expected return") };
+        assert_eq!(text(value.span), "y + 1");
+        let ExprKind::Binary { left, right, .. } = &value.kind else { panic!("Expected binary") };
+        assert_eq!((text(left.span), text(right.span)), ("y", "1"));
+    }
+
+    #[test]
+    fn recovers_and_reports_every_syntax_error() {
+        let src = "fn f() -> i32 {\n    let x = ;\n    let y = 2\n    return y;\n}\n) fn g() { return 1 +; }\nfn h() {}";
+        let (prog, errors) = Parser::new(Lexer::new(src)).parse_recovering();
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        let names: Vec<_> = prog.statements.iter().filter_map(|s| match &s.kind {
+            StmtKind::Function { name, .. } => Some(name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(names, ["f", "g", "h"]);
+        // the broken `let`s become error nodes, the `return` after them still parses
+        let StmtKind::Function { body, .. } = &prog.statements[0].kind else { panic!("Expected function") };
+        assert!(matches!(body[0].kind, StmtKind::Error) && matches!(body[1].kind, StmtKind::Error));
+        assert!(matches!(body[2].kind, StmtKind::Return(Some(_))));
+        assert!(matches!(prog.statements[1].kind, StmtKind::Error));
+
+        // an unknown token no longer silently ends the program
+        assert!(Parser::new(Lexer::new("fn a() {} ) fn b() {}")).parse().is_err());
+    }
+}
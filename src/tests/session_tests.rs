@@ -0,0 +1,18 @@
+#[cfg(test)]
+mod tests {
+    use crate::{Emit, Session};
+
+    #[test]
+    fn session_runs_the_pipeline_and_reports_every_stage() {
+        let mut session = Session::new();
+        let good = session.add_source("good.mint", "fn add(a: i32, b: i32) -> i32 {return a+b;}");
+        let bad = session.add_source("bad.mint", "fn f() -> i32 { return true; }");
+        let artifacts = session.emit(good, &[Emit::Wasm, Emit::Wat]).unwrap();
+        assert_eq!(artifacts[0].contents, session.compile(good).unwrap());
+        assert!(String::from_utf8_lossy(&artifacts[1].contents).contains("(export \"add\" (func 0))"));
+        assert_eq!(session.format(good).unwrap(), "fn add(a: i32, b: i32) -> i32 {\n    return a + b;\n}\n");
+        let errors = session.compile(bad).unwrap_err();
+        assert!(session.render(&errors).contains("bad.mint:1:"), "{}", session.render(&errors));
+        assert!(session.load("does/not/exist.mint").is_err());
+    }
+}
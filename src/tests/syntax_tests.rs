@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod tests {
+    use crate::{ast::FileId, lexer::{Lexer, TriviaKind}, parser::Parser, syntax::{self, NodeKind}};
+
+    const SOURCES: &[&str] = &[
+        "// counter\ncontract Counter {\n    storage count: i64; // slot\n\n    /* bump */ fn bump(by: i64) -> i64 {\n        count += by;\n        return count;\n    }\n}\n",
+        "fn f(a: i32, b) -> f64 {\n    let mut x = -(a + 1) * 2 as i64;\n    while x > 0 { if !(x == 3) { x -= 1; } else if x < 2 { break; } else { continue; } }\n    return (msg.value as f64) + g(\"s\\n\", 0x_ff, 1.5e3);\n}\n",
+        "fn broken() {\n    let = 1;\n    return 2\n}\n) fn ok() { { 1; } }\ncontract C { storage x; fn g() {} let y = 1; }",
+        "fn tail() {} /* unterminated",
+        "",
+    ];
+
+    #[test]
+    fn trees_reprint_their_source_exactly() {
+        for src in SOURCES {
+            let (tree, _) = syntax::parse(src, FileId(0));
+            assert_eq!(tree.kind, NodeKind::SourceFile);
+            assert_eq!(tree.text(), *src);
+        }
+    }
+
+    #[test]
+    fn comments_attach_to_the_nearest_line() {
+        let tokens = syntax::tokenize(SOURCES[0], FileId(0));
+        let contract = tokens.iter().find(|t| t.text == "contract").unwrap();
+        assert_eq!(contract.leading.iter().map(|t| t.kind).collect::<Vec<_>>(), [TriviaKind::LineComment, TriviaKind::Whitespace]);
+        let semi = tokens.iter().find(|t| t.text == ";").unwrap();
+        assert_eq!(semi.trailing.iter().map(|t| t.text.as_str()).collect::<String>(), " // slot\n");
+        let func = tokens.iter().find(|t| t.text == "fn").unwrap();
+        assert!(func.leading.iter().any(|t| t.kind == TriviaKind::BlockComment && t.text == "/* bump */"));
+    }
+
+    #[test]
+    fn lowering_matches_the_parser() {
+        for src in SOURCES {
+            let (tree, diagnostics) = syntax::parse(src, FileId(2));
+            let (program, expected) = Parser::new(Lexer::new(src)).with_file(FileId(2)).parse_recovering();
+            assert_eq!(diagnostics, expected);
+            assert_eq!(format!("{:?}", syntax::lower(&tree)), format!("{:?}", program), "{}", src);
+        }
+    }
+}
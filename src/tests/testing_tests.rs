@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use crate::chain::{Chain, Message};
+    use crate::testing::TestResult;
+    use crate::wasm::interp::{Trap, Value};
+    use crate::{Outcome, Session};
+
+    fn run(src: &str) -> Outcome<Vec<TestResult>> {
+        let mut session = Session::new();
+        let file = session.add_source("test.mint", src);
+        session.test(file)
+    }
+
+    #[test]
+    fn tests_pass_fail_and_revert() {
+        let src = "contract C { storage n: i64; error NotPositive(v: i64);\n\
+                   fn add(v: i64) -> i64 { if v <= 0 { revert NotPositive(v); } n += v; return n; } }\n\
+                   #[test] fn adds() { assert_eq(add(2), 2); assert_eq(add(3), 5); }\n\
+                   #[test] fn fresh_chain() { assert_eq(add(1), 2); }\n\
+                   #[test] #[should_revert] fn rejects_zero() { add(0); }\n\
+                   #[test] #[should_revert] fn accepts_one() { add(1); }\n\
+                   #[test] fn divides_by_zero() { let z = 0; add(1 / z); }\n\
+                   #[test] #[should_revert] fn traps_instead() { let z = 0; add(1 / z); }\n\
+                   #[test] #[should_revert] fn fails_an_assertion() { assert(false); }";
+        let results = run(src).unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["adds", "fresh_chain", "rejects_zero", "accepts_one", "divides_by_zero", "traps_instead", "fails_an_assertion"]);
+        assert_eq!(results.iter().map(TestResult::passed).collect::<Vec<_>>(), [true, false, true, false, false, false, false]);
+        let failure = results[1].failure.as_ref().unwrap();
+        assert_eq!(failure.code, "E0401");
+        assert_eq!(&src[failure.labels[0].span.start..failure.labels[0].span.end], "assert_eq(add(1), 2)");
+        assert_eq!(results[3].failure.as_ref().unwrap().message, "Test `accepts_one` did not revert");
+        assert!(results[4].failure.as_ref().unwrap().message.contains("trapped"));
+        assert!(results[5].failure.as_ref().unwrap().message.contains("trapped instead of reverting"));
+        assert!(results[6].failure.as_ref().unwrap().message.starts_with("Assertion failed"));
+    }
+
+    #[test]
+    fn failed_assertions_trap_instead_of_reverting() {
+        let src = "contract C { fn pay(v: i64) { assert(v > 0); } }\n\
+                   #[test] #[should_revert] fn guarded() { pay(0); }";
+        let failure = run(src).unwrap().remove(0).failure.expect("an assertion is not a revert");
+        assert_eq!(failure.message, "Assertion failed in test `guarded`");
+        assert_eq!(&src[failure.labels[0].span.start..failure.labels[0].span.end], "assert(v > 0)");
+
+        // outside tests there is no span to report, only the trap
+        let mut session = Session::new();
+        let file = session.add_source("test.mint", src);
+        let mut chain = Chain::new();
+        let contract = chain.deploy(&session.compile(file).unwrap()).unwrap();
+        let sender = chain.create_account(0);
+        assert_eq!(chain.call(contract, Message { sender, value: 0 }, "pay", &[Value::I64(0)]), Err(Trap::Unreachable));
+    }
+
+    #[test]
+    fn attributes_are_checked_and_tests_left_out_of_builds() {
+        for src in ["#[inline] fn f() {}", "#[should_revert] fn f() {}", "#[test] #[test] fn f() {}",
+                    "#[test] fn f(a: i64) {}", "#[test] fn f() -> i64 { return 1; }",
+                    "#[test] fn t() {} fn f() { t(); }", "#[test] let x = 1;"] {
+            assert!(run(src).is_err(), "{}", src);
+        }
+        let mut session = Session::new();
+        let file = session.add_source("test.mint", "fn f() -> i64 { return 1; } #[test] fn t() { assert(f() == 1); }");
+        let wat = String::from_utf8(session.emit(file, &[crate::Emit::Wat]).unwrap().remove(0).contents).unwrap();
+        assert!(wat.contains("\"f\"") && !wat.contains("\"t\"") && !wat.contains("assert_failed"));
+    }
+}
@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{type_from_name, type_name, TYPE_NAMES};
+    use crate::lexer::{Lexer, TokenKind, KEYWORDS, OPERATORS};
+    use crate::vscode::{grammar, language_configuration, CONFIGURATION_PATH, GRAMMAR_PATH};
+
+    #[test]
+    fn extension_files_match_the_generator() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/vscode-exstension/");
+        for (path, generated) in [(GRAMMAR_PATH, grammar()), (CONFIGURATION_PATH, language_configuration())] {
+            let path = format!("{}{}", dir, path);
+            if std::env::var_os("MINTORA_UPDATE_EXTENSION").is_some() {
+                std::fs::write(&path, &generated).unwrap();
+            }
+            let checked_in = std::fs::read_to_string(&path).unwrap();
+            assert!(checked_in == generated, "{} is out of date; rerun the tests with MINTORA_UPDATE_EXTENSION=1", path);
+        }
+    }
+
+    #[test]
+    fn tables_agree_with_the_lexer_and_compiler() {
+        for (keyword, _) in KEYWORDS {
+            assert_eq!(Lexer::new(keyword).next().unwrap().kind, TokenKind::Keyword(keyword.to_string()));
+        }
+        for (text, kind, _) in OPERATORS {
+            let tokens: Vec<TokenKind> = Lexer::new(text).map(|t| t.kind).collect();
+            assert_eq!(tokens, std::slice::from_ref(kind), "{}", text);
+            assert_eq!(kind.to_string(), format!("`{}`", text));
+        }
+        assert!(TYPE_NAMES.iter().all(|name| type_name(&type_from_name(name)) == *name));
+        let grammar: serde_json::Value = serde_json::from_str(&grammar()).unwrap();
+        let rules = grammar["patterns"].to_string();
+        assert!(KEYWORDS.iter().all(|(k, _)| rules.contains(k)) && rules.contains("should_revert") && rules.contains("(?!<=|=|<)"));
+    }
+}
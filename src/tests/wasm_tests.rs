@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codegen::compile_to_wasm,
+        compiler::type_check,
+        diagnostic::Diagnostic,
+        lexer::Lexer,
+        parser::Parser,
+        wasm::{decode::{code_bodies, Reader}, to_wat, write_sleb},
+    };
+
+    fn compile(src: &str) -> Result<Vec<u8>, Diagnostic> {
+        let prog = Parser::new(Lexer::new(src)).parse().map_err(|mut ds| ds.remove(0))?;
+        type_check(&prog)?;
+        compile_to_wasm(&prog)
+    }
+
+    /// The constant returned by `fn f() -> <ty> { return <lit>; }`.
+    fn returned_const(ty: &str, lit: &str) -> (u8, i64) {
+        let wasm = compile(&format!("fn f() -> {} {{ return {}; }}", ty, lit)).unwrap();
+        let bodies = code_bodies(&wasm).unwrap();
+        assert!(bodies[0].locals.is_empty());
+        let mut r = Reader::new(bodies[0].code);
+        let opcode = r.byte().unwrap();
+        let value = r.sleb().unwrap();
+        assert_eq!(r.byte().unwrap(), 0x0F, "`return` must follow the constant");
+        (opcode, value)
+    }
+
+    #[test]
+    fn sleb_round_trips_boundary_values() {
+        let values = [
+            0, 1, -1, 63, 64, -64, -65, 127, 128, -128, 8191, 8192, -8193,
+            i32::MAX as i64, i32::MIN as i64, u32::MAX as i64, i64::MAX, i64::MIN,
+        ];
+        for v in values {
+            let mut buf = Vec::new();
+            write_sleb(v, &mut buf);
+            let mut r = Reader::new(&buf);
+            assert_eq!(r.sleb().unwrap(), v, "{:02x?}", buf);
+            assert!(r.at_end(), "{} used more bytes than it needs: {:02x?}", v, buf);
+        }
+        // ten continuation bytes never terminate within 64 bits
+        assert!(Reader::new(&[0xFF; 10]).sleb().is_err());
+    }
+
+    #[test]
+    fn integer_constants_survive_codegen() {
+        for v in [0, 5, 63, 64, 100, 1000, -1, -64, -65, -1000, i32::MAX as i64, i32::MIN as i64] {
+            assert_eq!(returned_const("i32", &v.to_string()), (0x41, v));
+        }
+        for v in [64, i32::MAX as i64 + 1, -(1 << 40), i64::MAX, i64::MIN] {
+            assert_eq!(returned_const("i64", &v.to_string()), (0x42, v));
+        }
+        // literals are typed by the value they must fit
+        assert_eq!(returned_const("i64", "3").0, 0x42);
+    }
+
+    #[test]
+    fn out_of_range_literals_are_rejected() {
+        assert!(compile("fn f() -> i32 { return 2147483648; }").unwrap_err().message.contains("out of range for `i32`"));
+        assert!(compile("fn f() -> i32 { let x: i32 = -2147483649; return x; }").is_err());
+        assert!(compile("fn f() -> i64 { return 9223372036854775808; }").unwrap_err().message.contains("out of range for `i64`"));
+        assert!(compile("fn f(x: i32) -> i32 { return g(4294967296); } fn g(x: i32) -> i32 { return x; }").is_err());
+        assert!(compile("fn f() -> i64 { let x = 4294967296; return x; }").is_ok());
+    }
+
+    #[test]
+    fn modules_disassemble_to_wat() {
+        let wasm = compile("contract C { storage n: i64; fn add(x: i64) -> i64 { n += x; return n; } } \
+                            fn hi() -> string { return \"hi\\n\"; } fn half(x: f64) -> f64 { return x * 0.5; }").unwrap();
+        let wat = to_wat(&wasm).unwrap();
+        for line in [
+            "(import \"env\" \"storage_read\" (func (;0;) (type 0)))",
+            "(export \"add\" (func 2))",
+            "(memory (;0;) 1)",
+            "(data (;0;) (i32.const 16) \"hi\\0a\")",
+            "    i64.add",
+            "    f64.const 0.5",
+        ] {
+            assert!(wat.contains(line), "missing `{}` in\n{}", line, wat);
+        }
+        assert!(wat.starts_with("(module\n") && wat.ends_with(")\n"));
+        assert!(to_wat(b"\0asm\x02\0\0\0").is_err());
+    }
+}
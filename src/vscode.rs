@@ -0,0 +1,207 @@
+//! The VS Code extension's TextMate grammar and language configuration,
+//! generated from the lexer's keyword and operator tables and the compiler's
+//! lists of types, built-ins and attributes so they cannot drift apart. The
+//! checked-in copies under `vscode-exstension/` are compared against these in
+//! the tests; `MINTORA_UPDATE_EXTENSION=1 cargo test` rewrites them.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::compiler::{ATTRIBUTES, BUILTIN_FUNCTIONS, TYPE_NAMES};
+use crate::host::BUILTINS;
+use crate::lexer::{TokenClass, KEYWORDS, OPERATORS};
+
+/// The grammar file, relative to the extension directory.
+pub const GRAMMAR_PATH: &str = "mintora.tmLanguage.json";
+pub const CONFIGURATION_PATH: &str = "language-configuration.json";
+
+const IDENT: &str = "[A-Za-z_][A-Za-z0-9_]*";
+
+#[derive(Debug, Default, Serialize)]
+struct Rule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
+    matches: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    begin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    captures: BTreeMap<String, Scope>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    patterns: Vec<Rule>,
+}
+
+#[derive(Debug, Serialize)]
+struct Scope {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Grammar {
+    scope_name: &'static str,
+    name: &'static str,
+    file_types: [&'static str; 1],
+    patterns: Vec<Rule>,
+    repository: BTreeMap<&'static str, Rule>,
+}
+
+fn scoped(name: &str, regex: String) -> Rule {
+    Rule { name: Some(format!("{}.mintora", name)), matches: Some(regex), ..Rule::default() }
+}
+
+/// A rule whose numbered groups get the given scopes.
+fn captured(regex: String, scopes: &[&str]) -> Rule {
+    let captures = scopes.iter().enumerate()
+        .map(|(i, name)| ((i + 1).to_string(), Scope { name: format!("{}.mintora", name) }))
+        .collect();
+    Rule { matches: Some(regex), captures, ..Rule::default() }
+}
+
+fn quoted(quote: &str, kind: &str) -> Rule {
+    Rule {
+        name: Some(format!("string.quoted.{}.mintora", kind)),
+        begin: Some(quote.to_string()),
+        end: Some(quote.to_string()),
+        patterns: vec![Rule { include: Some("#escape".into()), ..Rule::default() }],
+        ..Rule::default()
+    }
+}
+
+/// `text` as a regex matching it literally.
+fn escape(text: &str) -> String {
+    text.chars().map(|c| if "\\^$.|?*+()[]{}".contains(c) { format!("\\{}", c) } else { c.to_string() }).collect()
+}
+
+/// `\b(a|b|c)\b` over the given words.
+fn words<'a>(words: impl IntoIterator<Item = &'a str>) -> String {
+    format!("\\b({})\\b", words.into_iter().collect::<Vec<_>>().join("|"))
+}
+
+fn keywords(class: TokenClass) -> String {
+    words(KEYWORDS.iter().filter(|(_, c)| *c == class).map(|(k, _)| *k))
+}
+
+/// The operators of one class. Each refuses to match where a longer operator
+/// begins, so `<` leaves `<<=` alone whatever order the rules are tried in.
+fn operators(class: TokenClass) -> String {
+    let alternatives: Vec<String> = OPERATORS.iter().filter(|(_, _, c)| *c == class).map(|(text, ..)| {
+        let longer: Vec<String> = OPERATORS.iter()
+            .filter(|(other, ..)| other.len() > text.len() && other.starts_with(text))
+            .map(|(other, ..)| escape(&other[text.len()..]))
+            .collect();
+        if longer.is_empty() { escape(text) } else { format!("{}(?!{})", escape(text), longer.join("|")) }
+    }).collect();
+    alternatives.join("|")
+}
+
+fn pretty(value: &impl Serialize) -> String {
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    value.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter)).expect("JSON serializes");
+    String::from_utf8(out).expect("JSON is UTF-8") + "\n"
+}
+
+/// `mintora.tmLanguage.json`.
+pub fn grammar() -> String {
+    let namespaces: Vec<&str> = BUILTINS.iter().map(|b| b.namespace).fold(Vec::new(), |mut all, ns| {
+        if !all.contains(&ns) { all.push(ns); }
+        all
+    });
+    let patterns = vec![
+        scoped("comment.line.double-slash", "//.*$".into()),
+        Rule { name: Some("comment.block.mintora".into()), begin: Some("/\\*".into()), end: Some("\\*/".into()), ..Rule::default() },
+        quoted("\"", "double"),
+        quoted("'", "single"),
+        Rule {
+            name: Some("meta.attribute.mintora".into()),
+            ..captured(format!("(#)(\\[)\\s*{}\\s*(\\])", words(ATTRIBUTES.iter().copied())),
+                       &["punctuation.definition.attribute", "punctuation.section.attribute.begin", "entity.name.function.attribute", "punctuation.section.attribute.end"])
+        },
+        captured(format!("\\b(contract)\\s+({})", IDENT), &["storage.type.contract", "entity.name.type.contract"]),
+        captured(format!("\\b(fn)\\s+({})", IDENT), &["storage.type.function", "entity.name.function"]),
+        captured(format!("\\b(storage)\\s+({})", IDENT), &["storage.type.storage", "variable.other.member"]),
+        scoped("keyword.control", keywords(TokenClass::Control)),
+        scoped("storage.type", keywords(TokenClass::Declaration)),
+        scoped("storage.modifier", keywords(TokenClass::Modifier)),
+        scoped("constant.language.boolean", keywords(TokenClass::Constant)),
+        scoped("keyword.operator.cast", keywords(TokenClass::Cast)),
+        scoped("support.type.primitive", words(TYPE_NAMES.iter().copied())),
+        captured(
+            format!("{}\\s*(\\.)\\s*{}", words(namespaces), words(BUILTINS.iter().map(|b| b.field))),
+            &["support.variable", "punctuation.accessor", "support.variable.property"],
+        ),
+        scoped("support.function.builtin", format!("{}(?=\\s*\\()", words(BUILTIN_FUNCTIONS.iter().copied()))),
+        scoped("entity.name.function.call", format!("\\b{}(?=\\s*\\()", IDENT)),
+        scoped("constant.numeric.hex", "\\b0[xX][0-9a-fA-F_]+\\b".into()),
+        scoped("constant.numeric.binary", "\\b0[bB][01_]+\\b".into()),
+        scoped("constant.numeric.float", "\\b\\d[\\d_]*(?:\\.\\d[\\d_]*(?:[eE][+-]?\\d[\\d_]*)?|[eE][+-]?\\d[\\d_]*)\\b".into()),
+        scoped("constant.numeric.integer", "\\b\\d[\\d_]*\\b".into()),
+        scoped("keyword.operator.arrow", operators(TokenClass::Arrow)),
+        scoped("keyword.operator.assignment", operators(TokenClass::Assignment)),
+        scoped("keyword.operator.comparison", operators(TokenClass::Comparison)),
+        scoped("keyword.operator.logical", operators(TokenClass::Logical)),
+        scoped("keyword.operator.bitwise", operators(TokenClass::Bitwise)),
+        scoped("keyword.operator.arithmetic", operators(TokenClass::Arithmetic)),
+        scoped("punctuation.terminator", ";".into()),
+        scoped("punctuation.separator", "[,:]".into()),
+        scoped("punctuation.accessor", "\\.".into()),
+    ];
+    let escape = scoped("constant.character.escape", "\\\\(?:[ntr0\\\\\"']|u\\{[0-9a-fA-F]+\\})".into());
+    pretty(&Grammar {
+        scope_name: "source.mintora",
+        name: "Mintora",
+        file_types: ["mint"],
+        patterns,
+        repository: BTreeMap::from([("escape", escape)]),
+    })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Configuration {
+    comments: Comments,
+    brackets: Vec<[&'static str; 2]>,
+    auto_closing_pairs: Vec<Pair>,
+    surrounding_pairs: Vec<[&'static str; 2]>,
+    word_pattern: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Comments {
+    line_comment: &'static str,
+    block_comment: [&'static str; 2],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Pair {
+    open: &'static str,
+    close: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    not_in: Vec<&'static str>,
+}
+
+/// `language-configuration.json`.
+pub fn language_configuration() -> String {
+    let brackets: Vec<[&str; 2]> = OPERATORS.iter().filter(|(_, _, c)| *c == TokenClass::Bracket)
+        .map(|(text, ..)| *text).collect::<Vec<_>>().chunks(2).map(|pair| [pair[0], pair[1]]).collect();
+    let quotes = [("\"", vec!["string"]), ("'", vec!["string", "comment"])];
+    let auto_closing_pairs = brackets.iter().map(|[open, close]| Pair { open, close, not_in: Vec::new() })
+        .chain(quotes.iter().map(|(q, not_in)| Pair { open: q, close: q, not_in: not_in.clone() }))
+        .collect();
+    let surrounding_pairs = brackets.iter().copied().chain(quotes.iter().map(|(q, _)| [*q, *q])).collect();
+    pretty(&Configuration {
+        comments: Comments { line_comment: "//", block_comment: ["/*", "*/"] },
+        brackets,
+        auto_closing_pairs,
+        surrounding_pairs,
+        word_pattern: IDENT.to_string(),
+    })
+}
@@ -0,0 +1,338 @@
+//! Reading back the binary format produced by `encode`.
+
+use super::{FuncType, ValType};
+
+/// Cursor over a byte slice with the LEB128 readers the format needs.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self { Reader { bytes, pos: 0 } }
+
+    pub fn at_end(&self) -> bool { self.pos >= self.bytes.len() }
+
+    pub fn byte(&mut self) -> Result<u8, String> {
+        let b = *self.bytes.get(self.pos).ok_or("unexpected end of module")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len()).ok_or("unexpected end of module")?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn name(&mut self) -> Result<String, String> {
+        let len = self.uleb()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| "name is not valid UTF-8".to_string())
+    }
+
+    pub fn val_type(&mut self) -> Result<ValType, String> {
+        let b = self.byte()?;
+        ValType::from_byte(b).ok_or_else(|| format!("unsupported value type 0x{:02X}", b))
+    }
+
+    pub fn uleb(&mut self) -> Result<u32, String> {
+        let mut result: u64 = 0;
+        for shift in (0..35).step_by(7) {
+            let b = self.byte()?;
+            result |= ((b & 0x7F) as u64) << shift;
+            if b & 0x80 == 0 {
+                return u32::try_from(result).map_err(|_| "unsigned LEB128 value overflows u32".to_string());
+            }
+        }
+        Err("unsigned LEB128 value is too long".into())
+    }
+
+    /// Signed LEB128 of up to 64 bits; `i32` immediates are read the same way.
+    pub fn sleb(&mut self) -> Result<i64, String> {
+        let mut result: i64 = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift == 63 && b & 0x7F != 0 && b & 0x7F != 0x7F {
+                return Err("signed LEB128 value overflows i64".into());
+            }
+            result |= ((b & 0x7F) as i64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 { result |= -1 << shift; }
+                return Ok(result);
+            }
+            if shift > 63 { return Err("signed LEB128 value is too long".into()); }
+        }
+    }
+}
+
+/// Split a module into `(section id, contents)` pairs after checking the header.
+pub fn sections(module: &[u8]) -> Result<Vec<(u8, &[u8])>, String> {
+    let mut r = Reader::new(module);
+    if r.bytes(8).ok() != Some(b"\0asm\x01\0\0\0".as_slice()) {
+        return Err("not a WASM module (bad magic or version)".into());
+    }
+    let mut out = Vec::new();
+    while !r.at_end() {
+        let id = r.byte()?;
+        let len = r.uleb()? as usize;
+        out.push((id, r.bytes(len)?));
+    }
+    Ok(out)
+}
+
+/// The contents of the custom section called `name`, if the module has one.
+pub fn custom_section<'a>(module: &'a [u8], name: &str) -> Result<Option<&'a [u8]>, String> {
+    for (id, contents) in sections(module)? {
+        if id != 0 { continue; }
+        let mut r = Reader::new(contents);
+        if r.name()? == name { return Ok(Some(&contents[r.pos..])); }
+    }
+    Ok(None)
+}
+
+/// A function body from the code section.
+#[derive(Debug)]
+pub struct Body<'a> {
+    /// `(count, value type byte)` local declaration groups.
+    pub locals: Vec<(u32, u8)>,
+    /// Instructions, including the final `end`.
+    pub code: &'a [u8],
+}
+
+/// Decode every function body in the module's code section.
+pub fn code_bodies(module: &[u8]) -> Result<Vec<Body<'_>>, String> {
+    let Some(&(_, code)) = sections(module)?.iter().find(|(id, _)| *id == 10) else { return Ok(Vec::new()) };
+    let mut r = Reader::new(code);
+    let mut bodies = Vec::new();
+    for _ in 0..r.uleb()? {
+        let len = r.uleb()? as usize;
+        let mut body = Reader::new(r.bytes(len)?);
+        let mut locals = Vec::new();
+        for _ in 0..body.uleb()? {
+            locals.push((body.uleb()?, body.byte()?));
+        }
+        bodies.push(Body { locals, code: &body.bytes[body.pos..] });
+    }
+    Ok(bodies)
+}
+
+#[derive(Debug)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub type_idx: u32,
+}
+
+#[derive(Debug)]
+pub struct Global {
+    pub ty: ValType,
+    pub mutable: bool,
+    /// The constant initializer expression's single instruction.
+    pub init: Instr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind { Func, Memory, Global }
+
+#[derive(Debug)]
+pub struct Export {
+    pub name: String,
+    pub kind: ExportKind,
+    pub index: u32,
+}
+
+/// A module in the subset `encode` writes: function imports, one memory,
+/// globals, exports, code and active data segments for memory 0.
+#[derive(Debug, Default)]
+pub struct Module<'a> {
+    pub types: Vec<FuncType>,
+    pub imports: Vec<Import>,
+    /// Type index of each defined function.
+    pub functions: Vec<u32>,
+    /// Minimum pages of the linear memory, if there is one.
+    pub memory: Option<u32>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub bodies: Vec<Body<'a>>,
+    /// `(offset, bytes)` of each data segment.
+    pub data: Vec<(u32, &'a [u8])>,
+}
+
+impl Module<'_> {
+    /// The signature of function `idx`, counting imports first.
+    pub fn func_type(&self, idx: u32) -> Option<&FuncType> {
+        let idx = idx as usize;
+        let type_idx = match idx.checked_sub(self.imports.len()) {
+            None => self.imports[idx].type_idx,
+            Some(i) => *self.functions.get(i)?,
+        };
+        self.types.get(type_idx as usize)
+    }
+}
+
+pub fn module(bytes: &[u8]) -> Result<Module<'_>, String> {
+    let mut m = Module::default();
+    for (id, contents) in sections(bytes)? {
+        let mut r = Reader::new(contents);
+        match id {
+            0 => continue, // custom sections carry nothing we need
+            1 => for _ in 0..r.uleb()? {
+                if r.byte()? != 0x60 { return Err("malformed function type".into()); }
+                let params = (0..r.uleb()?).map(|_| r.val_type()).collect::<Result<_, _>>()?;
+                let results = (0..r.uleb()?).map(|_| r.val_type()).collect::<Result<_, _>>()?;
+                m.types.push(FuncType { params, results });
+            },
+            2 => for _ in 0..r.uleb()? {
+                let (module, name) = (r.name()?, r.name()?);
+                if r.byte()? != 0x00 { return Err(format!("import `{}.{}` is not a function", module, name)); }
+                m.imports.push(Import { module, name, type_idx: r.uleb()? });
+            },
+            3 => for _ in 0..r.uleb()? { m.functions.push(r.uleb()?); },
+            5 => {
+                if r.uleb()? != 1 || r.byte()? != 0x00 { return Err("only one memory without a maximum is supported".into()); }
+                m.memory = Some(r.uleb()?);
+            }
+            6 => for _ in 0..r.uleb()? {
+                let (ty, mutable) = (r.val_type()?, r.byte()? == 0x01);
+                let init = instr(&mut r)?;
+                if r.byte()? != 0x0B { return Err("global initializer is not a single constant".into()); }
+                m.globals.push(Global { ty, mutable, init });
+            },
+            7 => for _ in 0..r.uleb()? {
+                let name = r.name()?;
+                let kind = match r.byte()? {
+                    0x00 => ExportKind::Func,
+                    0x02 => ExportKind::Memory,
+                    0x03 => ExportKind::Global,
+                    k => return Err(format!("unsupported export kind 0x{:02X}", k)),
+                };
+                m.exports.push(Export { name, kind, index: r.uleb()? });
+            },
+            10 => m.bodies = code_bodies(bytes)?,
+            11 => for _ in 0..r.uleb()? {
+                if r.byte()? != 0x00 { return Err("only active data segments for memory 0 are supported".into()); }
+                let offset = match instr(&mut r)? {
+                    Instr { opcode: 0x41, imm: Imm::I32(offset) } if r.byte()? == 0x0B => offset as u32,
+                    _ => return Err("data segment offset is not a constant".into()),
+                };
+                let len = r.uleb()? as usize;
+                m.data.push((offset, r.bytes(len)?));
+            },
+            id => return Err(format!("unsupported section id {}", id)),
+        }
+    }
+    if m.functions.len() != m.bodies.len() {
+        return Err("function and code sections disagree on the number of functions".into());
+    }
+    Ok(m)
+}
+
+/// An instruction's immediate operands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Imm {
+    None,
+    /// Result type of `block`, `loop` or `if`.
+    Block(Option<ValType>),
+    /// A function, local, global or label index.
+    Index(u32),
+    Mem { align: u32, offset: u32 },
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    BrTable(Vec<u32>, u32),
+}
+
+/// One instruction. Opcodes behind the `0xFC` prefix are `0xFC00 | sub-opcode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instr {
+    pub opcode: u16,
+    pub imm: Imm,
+}
+
+pub fn instr(r: &mut Reader) -> Result<Instr, String> {
+    let op = r.byte()?;
+    let opcode = if op == 0xFC { 0xFC00 | r.uleb()? as u16 } else { op as u16 };
+    let imm = match opcode {
+        0x02..=0x04 => match r.byte()? {
+            0x40 => Imm::Block(None),
+            b => Imm::Block(Some(ValType::from_byte(b).ok_or_else(|| format!("unsupported block type 0x{:02X}", b))?)),
+        },
+        0x0C | 0x0D | 0x10 | 0x20..=0x24 => Imm::Index(r.uleb()?),
+        0x0E => {
+            let labels = (0..r.uleb()?).map(|_| r.uleb()).collect::<Result<_, _>>()?;
+            Imm::BrTable(labels, r.uleb()?)
+        }
+        0x28..=0x3E => Imm::Mem { align: r.uleb()?, offset: r.uleb()? },
+        0x3F | 0x40 | 0xFC0B => { r.byte()?; Imm::None }
+        0xFC0A => { r.bytes(2)?; Imm::None }
+        0x41 => Imm::I32(r.sleb()? as i32),
+        0x42 => Imm::I64(r.sleb()?),
+        0x43 => Imm::F32(r.f32()?),
+        0x44 => Imm::F64(r.f64()?),
+        _ if name(opcode).is_some() => Imm::None,
+        _ => return Err(format!("unsupported opcode 0x{:02X}", opcode)),
+    };
+    Ok(Instr { opcode, imm })
+}
+
+const NUMERIC: [&str; 0xC0 - 0x45] = [
+    "i32.eqz", "i32.eq", "i32.ne", "i32.lt_s", "i32.lt_u", "i32.gt_s", "i32.gt_u", "i32.le_s", "i32.le_u", "i32.ge_s", "i32.ge_u",
+    "i64.eqz", "i64.eq", "i64.ne", "i64.lt_s", "i64.lt_u", "i64.gt_s", "i64.gt_u", "i64.le_s", "i64.le_u", "i64.ge_s", "i64.ge_u",
+    "f32.eq", "f32.ne", "f32.lt", "f32.gt", "f32.le", "f32.ge",
+    "f64.eq", "f64.ne", "f64.lt", "f64.gt", "f64.le", "f64.ge",
+    "i32.clz", "i32.ctz", "i32.popcnt", "i32.add", "i32.sub", "i32.mul", "i32.div_s", "i32.div_u", "i32.rem_s", "i32.rem_u",
+    "i32.and", "i32.or", "i32.xor", "i32.shl", "i32.shr_s", "i32.shr_u", "i32.rotl", "i32.rotr",
+    "i64.clz", "i64.ctz", "i64.popcnt", "i64.add", "i64.sub", "i64.mul", "i64.div_s", "i64.div_u", "i64.rem_s", "i64.rem_u",
+    "i64.and", "i64.or", "i64.xor", "i64.shl", "i64.shr_s", "i64.shr_u", "i64.rotl", "i64.rotr",
+    "f32.abs", "f32.neg", "f32.ceil", "f32.floor", "f32.trunc", "f32.nearest", "f32.sqrt",
+    "f32.add", "f32.sub", "f32.mul", "f32.div", "f32.min", "f32.max", "f32.copysign",
+    "f64.abs", "f64.neg", "f64.ceil", "f64.floor", "f64.trunc", "f64.nearest", "f64.sqrt",
+    "f64.add", "f64.sub", "f64.mul", "f64.div", "f64.min", "f64.max", "f64.copysign",
+    "i32.wrap_i64", "i32.trunc_f32_s", "i32.trunc_f32_u", "i32.trunc_f64_s", "i32.trunc_f64_u",
+    "i64.extend_i32_s", "i64.extend_i32_u", "i64.trunc_f32_s", "i64.trunc_f32_u", "i64.trunc_f64_s", "i64.trunc_f64_u",
+    "f32.convert_i32_s", "f32.convert_i32_u", "f32.convert_i64_s", "f32.convert_i64_u", "f32.demote_f64",
+    "f64.convert_i32_s", "f64.convert_i32_u", "f64.convert_i64_s", "f64.convert_i64_u", "f64.promote_f32",
+    "i32.reinterpret_f32", "i64.reinterpret_f64", "f32.reinterpret_i32", "f64.reinterpret_i64",
+];
+
+const MEMORY: [&str; 0x3F - 0x28] = [
+    "i32.load", "i64.load", "f32.load", "f64.load",
+    "i32.load8_s", "i32.load8_u", "i32.load16_s", "i32.load16_u",
+    "i64.load8_s", "i64.load8_u", "i64.load16_s", "i64.load16_u", "i64.load32_s", "i64.load32_u",
+    "i32.store", "i64.store", "f32.store", "f64.store",
+    "i32.store8", "i32.store16", "i64.store8", "i64.store16", "i64.store32",
+];
+
+const TRUNC_SAT: [&str; 8] = [
+    "i32.trunc_sat_f32_s", "i32.trunc_sat_f32_u", "i32.trunc_sat_f64_s", "i32.trunc_sat_f64_u",
+    "i64.trunc_sat_f32_s", "i64.trunc_sat_f32_u", "i64.trunc_sat_f64_s", "i64.trunc_sat_f64_u",
+];
+
+/// The text-format name of an opcode, if it is one we decode.
+pub fn name(opcode: u16) -> Option<&'static str> {
+    Some(match opcode {
+        0x00 => "unreachable", 0x01 => "nop", 0x02 => "block", 0x03 => "loop", 0x04 => "if", 0x05 => "else",
+        0x0B => "end", 0x0C => "br", 0x0D => "br_if", 0x0E => "br_table", 0x0F => "return", 0x10 => "call",
+        0x1A => "drop", 0x1B => "select",
+        0x20 => "local.get", 0x21 => "local.set", 0x22 => "local.tee", 0x23 => "global.get", 0x24 => "global.set",
+        0x28..=0x3E => MEMORY[opcode as usize - 0x28],
+        0x3F => "memory.size", 0x40 => "memory.grow",
+        0x41 => "i32.const", 0x42 => "i64.const", 0x43 => "f32.const", 0x44 => "f64.const",
+        0x45..=0xBF => NUMERIC[opcode as usize - 0x45],
+        0xFC00..=0xFC07 => TRUNC_SAT[opcode as usize - 0xFC00],
+        0xFC0A => "memory.copy", 0xFC0B => "memory.fill",
+        _ => return None,
+    })
+}
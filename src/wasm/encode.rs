@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use super::FuncType;
+
+pub fn write_uleb(mut v: u32, out: &mut Vec<u8>) {
+    loop {
+        let mut b = (v & 0x7F) as u8;
+        v >>= 7;
+        if v != 0 { b |= 0x80; }
+        out.push(b);
+        if v == 0 { break; }
+    }
+}
+
+/// Signed LEB128, as used by `i32.const`/`i64.const` immediates (an `i32` is
+/// encoded from its sign-extended value).
+pub fn write_sleb(mut v: i64, out: &mut Vec<u8>) {
+    loop {
+        let b = (v & 0x7F) as u8;
+        v >>= 7;
+        let done = (v == 0 && b & 0x40 == 0) || (v == -1 && b & 0x40 != 0);
+        out.push(if done { b } else { b | 0x80 });
+        if done { break; }
+    }
+}
+
+fn section(id: u8, content: Vec<u8>, out: &mut Vec<u8>) {
+    out.push(id);
+    write_uleb(content.len() as u32, out);
+    out.extend_from_slice(&content);
+}
+
+fn write_name(name: &str, out: &mut Vec<u8>) {
+    write_uleb(name.len() as u32, out);
+    out.extend_from_slice(name.as_bytes());
+}
+
+/// Incrementally assembles a module. Functions are declared up front (so calls
+/// can reference any index) and their bodies filled in afterwards. Imports share
+/// the function index space and come first, so they must be added before any
+/// function is declared.
+#[derive(Default)]
+pub struct ModuleBuilder {
+    types: Vec<FuncType>,
+    type_ids: HashMap<FuncType, u32>,
+    imports: Vec<(String, String, u32)>,
+    funcs: Vec<u32>,
+    bodies: Vec<Option<Vec<u8>>>,
+    exports: Vec<(String, u32)>,
+    /// Minimum pages and export name of the (single) linear memory.
+    memory: Option<(u32, String)>,
+    /// Mutable i32 globals with their initial values.
+    globals: Vec<i32>,
+    data: Vec<(u32, Vec<u8>)>,
+    /// Custom sections by name, written after everything else.
+    custom: Vec<(String, Vec<u8>)>,
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    /// Index of `ty` in the type section, adding it only the first time it is seen.
+    pub fn intern_type(&mut self, ty: FuncType) -> u32 {
+        if let Some(&idx) = self.type_ids.get(&ty) { return idx; }
+        let idx = self.types.len() as u32;
+        self.types.push(ty.clone());
+        self.type_ids.insert(ty, idx);
+        idx
+    }
+
+    /// Import a host function, returning its function index.
+    pub fn import_function(&mut self, module: &str, name: &str, ty: FuncType) -> u32 {
+        assert!(self.funcs.is_empty(), "imports must precede function declarations");
+        let type_idx = self.intern_type(ty);
+        self.imports.push((module.to_string(), name.to_string(), type_idx));
+        (self.imports.len() - 1) as u32
+    }
+
+    /// Reserve a function index with the given signature; its body comes later.
+    pub fn declare_function(&mut self, ty: FuncType) -> u32 {
+        let type_idx = self.intern_type(ty);
+        self.funcs.push(type_idx);
+        self.bodies.push(None);
+        (self.imports.len() + self.funcs.len() - 1) as u32
+    }
+
+    /// `body` is the encoded function body: local declarations, code and the final `end`.
+    pub fn set_body(&mut self, func: u32, body: Vec<u8>) {
+        self.bodies[func as usize - self.imports.len()] = Some(body);
+    }
+
+    pub fn export_function(&mut self, name: &str, func: u32) {
+        self.exports.push((name.to_string(), func));
+    }
+
+    /// Give the module a linear memory of at least `min_pages` 64KiB pages, exported as `export`.
+    pub fn set_memory(&mut self, min_pages: u32, export: &str) {
+        self.memory = Some((min_pages, export.to_string()));
+    }
+
+    /// Add a mutable `i32` global, returning its index.
+    pub fn add_global_i32(&mut self, init: i32) -> u32 {
+        self.globals.push(init);
+        (self.globals.len() - 1) as u32
+    }
+
+    /// Place `bytes` at `offset` in memory 0 when the module is instantiated.
+    pub fn add_data(&mut self, offset: u32, bytes: Vec<u8>) {
+        self.data.push((offset, bytes));
+    }
+
+    /// Add a custom section, which the module's semantics ignore.
+    pub fn add_custom_section(&mut self, name: &str, bytes: Vec<u8>) {
+        self.custom.push((name.to_string(), bytes));
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&[0x00, 0x61, 0x73, 0x6D]); // \0asm
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]); // version 1
+
+        // -- Type section (id=1)
+        let mut ty = Vec::new();
+        write_uleb(self.types.len() as u32, &mut ty);
+        for t in &self.types {
+            ty.push(0x60); // func type
+            write_uleb(t.params.len() as u32, &mut ty);
+            ty.extend(t.params.iter().map(|v| v.byte()));
+            write_uleb(t.results.len() as u32, &mut ty);
+            ty.extend(t.results.iter().map(|v| v.byte()));
+        }
+        section(1, ty, &mut out);
+
+        // -- Import section (id=2)
+        if !self.imports.is_empty() {
+            let mut imp = Vec::new();
+            write_uleb(self.imports.len() as u32, &mut imp);
+            for (module, name, type_idx) in &self.imports {
+                write_name(module, &mut imp);
+                write_name(name, &mut imp);
+                imp.push(0x00); // kind = func
+                write_uleb(*type_idx, &mut imp);
+            }
+            section(2, imp, &mut out);
+        }
+
+        // -- Function section (id=3): type index per defined function
+        let mut func = Vec::new();
+        write_uleb(self.funcs.len() as u32, &mut func);
+        for &t in &self.funcs { write_uleb(t, &mut func); }
+        section(3, func, &mut out);
+
+        // -- Memory section (id=5)
+        if let Some((min_pages, _)) = &self.memory {
+            let mut mem = Vec::new();
+            write_uleb(1, &mut mem);          // count
+            mem.push(0x00);                   // limits: min only
+            write_uleb(*min_pages, &mut mem);
+            section(5, mem, &mut out);
+        }
+
+        // -- Global section (id=6)
+        if !self.globals.is_empty() {
+            let mut glob = Vec::new();
+            write_uleb(self.globals.len() as u32, &mut glob);
+            for &init in &self.globals {
+                glob.extend_from_slice(&[0x7F, 0x01]); // i32, mutable
+                glob.push(0x41);                       // i32.const
+                write_sleb(init as i64, &mut glob);
+                glob.push(0x0B);                       // end
+            }
+            section(6, glob, &mut out);
+        }
+
+        // -- Export section (id=7)
+        let mut exp = Vec::new();
+        write_uleb((self.exports.len() + self.memory.is_some() as usize) as u32, &mut exp);
+        for (name, idx) in &self.exports {
+            write_name(name, &mut exp);
+            exp.push(0x00); // kind = func
+            write_uleb(*idx, &mut exp);
+        }
+        if let Some((_, name)) = &self.memory {
+            write_name(name, &mut exp);
+            exp.push(0x02); // kind = memory
+            write_uleb(0, &mut exp);
+        }
+        section(7, exp, &mut out);
+
+        // -- Code section (id=10): one body per defined function
+        let mut code = Vec::new();
+        write_uleb(self.bodies.len() as u32, &mut code);
+        for body in self.bodies {
+            let body = body.expect("every declared function has a body");
+            write_uleb(body.len() as u32, &mut code);
+            code.extend_from_slice(&body);
+        }
+        section(10, code, &mut out);
+
+        // -- Data section (id=11): active segments for memory 0
+        if !self.data.is_empty() {
+            let mut data = Vec::new();
+            write_uleb(self.data.len() as u32, &mut data);
+            for (offset, bytes) in &self.data {
+                data.push(0x00);                        // active, memory 0
+                data.push(0x41);                        // i32.const
+                write_sleb(*offset as i64, &mut data);
+                data.push(0x0B);                        // end
+                write_uleb(bytes.len() as u32, &mut data);
+                data.extend_from_slice(bytes);
+            }
+            section(11, data, &mut out);
+        }
+
+        // -- Custom sections (id=0): a name, then arbitrary bytes
+        for (name, bytes) in &self.custom {
+            let mut custom = Vec::new();
+            write_name(name, &mut custom);
+            custom.extend_from_slice(bytes);
+            section(0, custom, &mut out);
+        }
+
+        out
+    }
+}
@@ -0,0 +1,688 @@
+//! A small interpreter for the modules `compile_to_wasm` produces, so code can
+//! run without an external engine. It executes the decoded instructions
+//! directly. Instantiation rejects modules that use instructions it does not
+//! implement (including every `f32` one), unbalance the operand stack, name
+//! out-of-range indices or declare more memory than `MAX_PAGES`; value types
+//! are not checked. Operand stack slots are
+//! untyped 64-bit patterns: `i32`s zero-extended, `f64`s as their bits.
+
+use std::fmt;
+use std::rc::Rc;
+
+use super::decode::{self, Imm, Instr, Reader};
+use super::{FuncType, ValType};
+
+/// Pages a module may grow its memory to (64 MiB).
+const MAX_PAGES: u32 = 1024;
+const PAGE_SIZE: usize = 65536;
+/// Nested calls allowed before `StackOverflow`.
+const MAX_DEPTH: usize = 10_000;
+
+/// A typed value passed to or returned from a function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F64(f64),
+}
+
+impl Value {
+    pub fn ty(self) -> ValType {
+        match self {
+            Value::I32(_) => ValType::I32,
+            Value::I64(_) => ValType::I64,
+            Value::F64(_) => ValType::F64,
+        }
+    }
+
+    fn bits(self) -> u64 {
+        match self {
+            Value::I32(v) => v as u32 as u64,
+            Value::I64(v) => v as u64,
+            Value::F64(v) => v.to_bits(),
+        }
+    }
+
+    fn from_bits(ty: ValType, bits: u64) -> Value {
+        match ty {
+            ValType::I32 => Value::I32(bits as i32),
+            ValType::I64 => Value::I64(bits as i64),
+            ValType::F64 => Value::F64(f64::from_bits(bits)),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::F64(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Why execution stopped abnormally.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trap {
+    Unreachable,
+    DivisionByZero,
+    IntegerOverflow,
+    /// A float that is NaN could not be converted to an integer.
+    InvalidConversion,
+    OutOfBounds,
+    StackOverflow,
+    /// A host function failed; the message says why.
+    Host(String),
+    /// An assertion in a test module failed; the source span of the assertion.
+    AssertionFailed { start: usize, end: usize },
+    /// The contract reverted with a custom error: its selector and fields.
+    Revert { selector: u32, data: Vec<i64> },
+    /// The call could not be made: no such export, the wrong arguments, or
+    /// (on a chain) a transfer the sender cannot afford.
+    Call(String),
+    /// The module broke a rule instantiation is meant to enforce.
+    Malformed(&'static str),
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trap::Unreachable => write!(f, "unreachable code was executed"),
+            Trap::DivisionByZero => write!(f, "integer division by zero"),
+            Trap::IntegerOverflow => write!(f, "integer overflow"),
+            Trap::InvalidConversion => write!(f, "invalid conversion to integer"),
+            Trap::OutOfBounds => write!(f, "out of bounds memory access"),
+            Trap::StackOverflow => write!(f, "call stack exhausted"),
+            Trap::AssertionFailed { .. } => write!(f, "assertion failed"),
+            Trap::Revert { selector, data } => write!(f, "reverted with error 0x{:08x} {:?}", selector, data),
+            Trap::Host(message) | Trap::Call(message) => write!(f, "{}", message),
+            Trap::Malformed(why) => write!(f, "malformed module: {}", why),
+        }
+    }
+}
+
+/// The chain environment: implements the functions a module imports. They
+/// may read and write the module's memory.
+pub trait Host {
+    fn call(&mut self, name: &str, args: &[Value], memory: &mut [u8]) -> Result<Option<Value>, Trap>;
+}
+
+/// A host with no chain behind it: every import traps.
+pub struct NoHost;
+
+impl Host for NoHost {
+    fn call(&mut self, name: &str, _: &[Value], _: &mut [u8]) -> Result<Option<Value>, Trap> {
+        Err(Trap::Host(format!("host function `{}` is not available outside a chain", name)))
+    }
+}
+
+enum Func {
+    Import { name: String, ty: FuncType },
+    Code {
+        ty: FuncType,
+        locals: Vec<ValType>,
+        code: Vec<Instr>,
+        /// For `block`, `loop`, `if` and `else`: `(else or end, end)` indices.
+        jumps: Vec<(usize, usize)>,
+    },
+}
+
+impl Func {
+    fn ty(&self) -> &FuncType {
+        match self { Func::Import { ty, .. } | Func::Code { ty, .. } => ty }
+    }
+}
+
+/// An enclosing `block`, `loop` or `if`.
+struct Label {
+    /// Operand stack height when it was entered.
+    height: usize,
+    /// Values a branch to it carries.
+    arity: usize,
+    /// Where a branch to it continues: after its `end`, or the top of a loop.
+    target: usize,
+    is_loop: bool,
+}
+
+/// A call in progress.
+struct Frame {
+    func: usize,
+    pc: usize,
+    locals: Vec<u64>,
+    labels: Vec<Label>,
+    /// Operand stack height below the function's own values.
+    height: usize,
+    results: usize,
+}
+
+/// An instantiated module with its memory and globals. Cloning one is cheap
+/// and gives an independent copy of its state.
+#[derive(Clone)]
+pub struct Instance {
+    /// Shared so a running body can be borrowed while `self` is mutated.
+    funcs: Rc<[Func]>,
+    exports: Vec<(String, u32)>,
+    globals: Vec<u64>,
+    memory: Vec<u8>,
+}
+
+impl Instance {
+    pub fn new(bytes: &[u8]) -> Result<Instance, String> {
+        let m = decode::module(bytes)?;
+        let mut funcs = Vec::new();
+        for import in &m.imports {
+            let ty = m.types.get(import.type_idx as usize).ok_or("import has an unknown type")?.clone();
+            funcs.push(Func::Import { name: import.name.clone(), ty });
+        }
+        for (&type_idx, body) in m.functions.iter().zip(&m.bodies) {
+            let ty = m.types.get(type_idx as usize).ok_or("function has an unknown type")?.clone();
+            let mut locals = ty.params.clone();
+            for &(count, byte) in &body.locals {
+                let local = ValType::from_byte(byte).ok_or_else(|| format!("unsupported local type 0x{:02X}", byte))?;
+                locals.extend(std::iter::repeat_n(local, count as usize));
+            }
+            let mut code = Vec::new();
+            let mut r = Reader::new(body.code);
+            while !r.at_end() {
+                let instr = decode::instr(&mut r)?;
+                let name = decode::name(instr.opcode).unwrap_or("?");
+                if name.contains("f32") { return Err(format!("`{}` is not supported", name)); }
+                code.push(instr);
+            }
+            let jumps = jumps(&code)?;
+            funcs.push(Func::Code { ty, locals, code, jumps });
+        }
+        for (i, f) in funcs.iter().enumerate() {
+            validate(f, &funcs, m.globals.len(), m.memory.is_some()).map_err(|e| format!("function {}: {}", i, e))?;
+        }
+        let globals = m.globals.iter().map(|g| match g.init.imm {
+            Imm::I32(v) => Ok(Value::I32(v).bits()),
+            Imm::I64(v) => Ok(Value::I64(v).bits()),
+            Imm::F64(v) => Ok(Value::F64(v).bits()),
+            _ => Err("global initializer is not a constant".to_string()),
+        }).collect::<Result<_, _>>()?;
+        let pages = m.memory.unwrap_or(0);
+        if pages > MAX_PAGES {
+            return Err(format!("the memory of {} pages is larger than the limit of {}", pages, MAX_PAGES));
+        }
+        let mut memory = vec![0; pages as usize * PAGE_SIZE];
+        for &(offset, data) in &m.data {
+            let dst = memory.get_mut(offset as usize..offset as usize + data.len()).ok_or("data segment does not fit in memory")?;
+            dst.copy_from_slice(data);
+        }
+        let exports: Vec<(String, u32)> = m.exports.iter().filter(|e| e.kind == decode::ExportKind::Func).map(|e| (e.name.clone(), e.index)).collect();
+        if let Some((name, _)) = exports.iter().find(|(_, index)| *index as usize >= funcs.len()) {
+            return Err(format!("export `{}` names no function", name));
+        }
+        Ok(Instance { funcs: funcs.into(), exports, globals, memory })
+    }
+
+    /// The signature of an exported function.
+    pub fn export_type(&self, name: &str) -> Option<&FuncType> {
+        let &(_, index) = self.exports.iter().find(|(n, _)| n == name)?;
+        Some(self.funcs[index as usize].ty())
+    }
+
+    pub fn memory(&self) -> &[u8] { &self.memory }
+
+    /// The contents of a string returned by the module, packed as `(ptr << 32) | len`.
+    pub fn string(&self, packed: i64) -> Result<String, Trap> {
+        let (ptr, len) = ((packed as u64 >> 32) as usize, packed as u32 as usize);
+        let bytes = self.memory.get(ptr..ptr + len).ok_or(Trap::OutOfBounds)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Call an exported function.
+    pub fn invoke(&mut self, name: &str, args: &[Value], host: &mut dyn Host) -> Result<Option<Value>, Trap> {
+        let &(_, index) = self.exports.iter().find(|(n, _)| n == name).ok_or_else(|| Trap::Call(format!("no exported function `{}`", name)))?;
+        let ty = self.funcs[index as usize].ty();
+        if !ty.params.iter().copied().eq(args.iter().map(|a| a.ty())) {
+            let expected: Vec<&str> = ty.params.iter().map(|t| t.name()).collect();
+            return Err(Trap::Call(format!("`{}` takes ({})", name, expected.join(", "))));
+        }
+        let result_ty = ty.results.first().copied();
+        let mut stack: Vec<u64> = args.iter().map(|a| a.bits()).collect();
+        self.run(index, &mut stack, host)?;
+        result_ty.map(|t| Ok(Value::from_bits(t, pop(&mut stack)?))).transpose()
+    }
+
+    /// Call function `index` with its arguments on `stack`, leaving its result
+    /// there. Calls inside the module push frames rather than recursing, so
+    /// deep recursion in a contract cannot overflow the native stack.
+    fn run(&mut self, index: u32, stack: &mut Vec<u64>, host: &mut dyn Host) -> Result<(), Trap> {
+        let funcs = Rc::clone(&self.funcs);
+        let mut frames: Vec<Frame> = Vec::new();
+        enter(&funcs, index, stack, &mut frames, host, &mut self.memory)?;
+        while let Some(frame) = frames.last_mut() {
+            let Func::Code { code, jumps, .. } = &funcs[frame.func] else { return Err(Trap::Malformed("an import has a frame")) };
+            let Some(Instr { opcode, imm }) = code.get(frame.pc) else { leave(stack, &mut frames)?; continue };
+            frame.pc += 1;
+            let pc = frame.pc;
+            match (*opcode, imm) {
+                (0x00, _) => return Err(Trap::Unreachable),
+                (0x01, _) => {}
+                (0x02..=0x04, Imm::Block(ty)) => {
+                    let (else_, end) = jumps[pc - 1];
+                    let is_loop = *opcode == 0x03;
+                    let label = Label { height: stack.len(), arity: if is_loop { 0 } else { ty.is_some() as usize }, target: if is_loop { pc } else { end + 1 }, is_loop };
+                    if *opcode == 0x04 && pop(stack)? as u32 == 0 {
+                        if else_ == end { frame.pc = end + 1; continue; }
+                        frame.pc = else_ + 1;
+                    }
+                    frame.labels.push(label);
+                }
+                // The end of an `if`'s then-branch.
+                (0x05, _) => {
+                    frame.pc = jumps[pc - 1].1 + 1;
+                    frame.labels.pop();
+                }
+                (0x0B, _) => if frame.labels.pop().is_none() { leave(stack, &mut frames)? },
+                (0x0C, Imm::Index(depth)) => branch(*depth, stack, &mut frames)?,
+                (0x0D, Imm::Index(depth)) => if pop(stack)? as u32 != 0 { branch(*depth, stack, &mut frames)? },
+                (0x0E, Imm::BrTable(table, default)) => {
+                    let i = pop(stack)? as u32 as usize;
+                    branch(*table.get(i).unwrap_or(default), stack, &mut frames)?;
+                }
+                (0x0F, _) => leave(stack, &mut frames)?,
+                (0x10, Imm::Index(f)) => enter(&funcs, *f, stack, &mut frames, host, &mut self.memory)?,
+                (0x1A, _) => { pop(stack)?; }
+                (0x1B, _) => {
+                    let (c, b) = (pop(stack)? as u32, pop(stack)?);
+                    if c == 0 { *top(stack)? = b; }
+                }
+                (0x20, Imm::Index(i)) => stack.push(*slot(&mut frame.locals, *i)?),
+                (0x21, Imm::Index(i)) => *slot(&mut frame.locals, *i)? = pop(stack)?,
+                (0x22, Imm::Index(i)) => *slot(&mut frame.locals, *i)? = *top(stack)?,
+                (0x23, Imm::Index(i)) => stack.push(*slot(&mut self.globals, *i)?),
+                (0x24, Imm::Index(i)) => *slot(&mut self.globals, *i)? = pop(stack)?,
+                (0x28..=0x35, Imm::Mem { offset, .. }) => {
+                    let addr = pop(stack)? as u32 as usize + *offset as usize;
+                    stack.push(self.load(*opcode, addr)?);
+                }
+                (0x36..=0x3E, Imm::Mem { offset, .. }) => {
+                    let value = pop(stack)?;
+                    let addr = pop(stack)? as u32 as usize + *offset as usize;
+                    let width = match opcode { 0x36 | 0x3E => 4, 0x37 | 0x39 => 8, 0x3A | 0x3C => 1, _ => 2 };
+                    let dst = self.memory.get_mut(addr..addr + width).ok_or(Trap::OutOfBounds)?;
+                    dst.copy_from_slice(&value.to_le_bytes()[..width]);
+                }
+                (0x3F, _) => stack.push(self.pages() as u64),
+                (0x40, _) => {
+                    let (old, delta) = (self.pages(), pop(stack)? as u32);
+                    if old.checked_add(delta).is_some_and(|new| new <= MAX_PAGES) {
+                        self.memory.resize((old + delta) as usize * PAGE_SIZE, 0);
+                        stack.push(old as u64);
+                    } else {
+                        stack.push(-1i32 as u32 as u64);
+                    }
+                }
+                (0x41, Imm::I32(v)) => stack.push(Value::I32(*v).bits()),
+                (0x42, Imm::I64(v)) => stack.push(*v as u64),
+                (0x44, Imm::F64(v)) => stack.push(v.to_bits()),
+                (0xFC0A, _) => {
+                    let (n, src, dst) = (pop(stack)? as u32 as usize, pop(stack)? as u32 as usize, pop(stack)? as u32 as usize);
+                    if src.max(dst) + n > self.memory.len() { return Err(Trap::OutOfBounds); }
+                    self.memory.copy_within(src..src + n, dst);
+                }
+                (0xFC0B, _) => {
+                    let (n, value, dst) = (pop(stack)? as u32 as usize, pop(stack)? as u8, pop(stack)? as u32 as usize);
+                    self.memory.get_mut(dst..dst + n).ok_or(Trap::OutOfBounds)?.fill(value);
+                }
+                (op, _) => numeric(op, stack)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn pages(&self) -> u32 { (self.memory.len() / PAGE_SIZE) as u32 }
+
+    fn load(&self, opcode: u16, addr: usize) -> Result<u64, Trap> {
+        let width = match opcode { 0x28 | 0x34 | 0x35 => 4, 0x29 | 0x2B => 8, 0x2C | 0x2D | 0x30 | 0x31 => 1, _ => 2 };
+        let src = self.memory.get(addr..addr + width).ok_or(Trap::OutOfBounds)?;
+        let mut bytes = [0; 8];
+        bytes[..width].copy_from_slice(src);
+        let v = u64::from_le_bytes(bytes);
+        Ok(match opcode {
+            0x2C => v as i8 as i32 as u32 as u64,
+            0x2E => v as i16 as i32 as u32 as u64,
+            0x30 => v as i8 as i64 as u64,
+            0x32 => v as i16 as i64 as u64,
+            0x34 => v as i32 as i64 as u64,
+            _ => v,
+        })
+    }
+}
+
+/// Start a call to `index`: a host call completes at once, a defined function gets a frame.
+fn enter(funcs: &[Func], index: u32, stack: &mut Vec<u64>, frames: &mut Vec<Frame>, host: &mut dyn Host, memory: &mut [u8]) -> Result<(), Trap> {
+    match funcs.get(index as usize).ok_or(Trap::Malformed("a call names no function"))? {
+        Func::Import { name, ty } => {
+            let args = take(stack, ty.params.len())?;
+            let values: Vec<Value> = ty.params.iter().zip(args).map(|(&t, b)| Value::from_bits(t, b)).collect();
+            match (host.call(name, &values, memory)?, ty.results.first()) {
+                (Some(v), Some(&t)) if v.ty() == t => stack.push(v.bits()),
+                (None, None) => {}
+                _ => return Err(Trap::Host(format!("host function `{}` returned the wrong type", name))),
+            }
+        }
+        Func::Code { ty, locals, .. } => {
+            if frames.len() == MAX_DEPTH { return Err(Trap::StackOverflow); }
+            let mut values = take(stack, ty.params.len())?;
+            values.resize(locals.len(), 0);
+            frames.push(Frame { func: index as usize, pc: 0, locals: values, labels: Vec::new(), height: stack.len(), results: ty.results.len() });
+        }
+    }
+    Ok(())
+}
+
+/// Return from the innermost frame, keeping only its results on the stack.
+fn leave(stack: &mut Vec<u64>, frames: &mut Vec<Frame>) -> Result<(), Trap> {
+    let frame = frames.pop().ok_or(Trap::Malformed("no frame is running"))?;
+    let results = take(stack, frame.results)?;
+    stack.truncate(frame.height);
+    stack.extend(results);
+    Ok(())
+}
+
+/// Branch `depth` labels out of the innermost frame; past its outermost label is a return.
+fn branch(depth: u32, stack: &mut Vec<u64>, frames: &mut Vec<Frame>) -> Result<(), Trap> {
+    let frame = frames.last_mut().ok_or(Trap::Malformed("no frame is running"))?;
+    let Some(i) = frame.labels.len().checked_sub(depth as usize + 1) else { return leave(stack, frames) };
+    let label = &frame.labels[i];
+    let kept = take(stack, label.arity)?;
+    stack.truncate(label.height);
+    stack.extend(kept);
+    frame.pc = label.target;
+    frame.labels.truncate(if label.is_loop { i + 1 } else { i });
+    Ok(())
+}
+
+const UNDERFLOW: Trap = Trap::Malformed("operand stack underflow");
+
+fn pop(stack: &mut Vec<u64>) -> Result<u64, Trap> {
+    stack.pop().ok_or(UNDERFLOW)
+}
+
+fn top(stack: &mut [u64]) -> Result<&mut u64, Trap> {
+    stack.last_mut().ok_or(UNDERFLOW)
+}
+
+/// The top `n` values, removed from the stack.
+fn take(stack: &mut Vec<u64>, n: usize) -> Result<Vec<u64>, Trap> {
+    let at = stack.len().checked_sub(n).ok_or(UNDERFLOW)?;
+    Ok(stack.split_off(at))
+}
+
+/// A local or global by index.
+fn slot(slots: &mut [u64], index: u32) -> Result<&mut u64, Trap> {
+    slots.get_mut(index as usize).ok_or(Trap::Malformed("a local or global index is out of range"))
+}
+
+/// How many operands a numeric instruction `numeric` implements pops; each pushes one result.
+fn numeric_arity(opcode: u16) -> Option<usize> {
+    match opcode {
+        0x45 | 0x50 | 0x67..=0x69 | 0x79..=0x7B | 0x99..=0x9F | 0xA7 | 0xAA..=0xAD | 0xB0 | 0xB1 | 0xB7..=0xBA | 0xBD | 0xBF
+        | 0xFC02 | 0xFC03 | 0xFC06 | 0xFC07 => Some(1),
+        0x46..=0x4F | 0x51..=0x5A | 0x61..=0x66 | 0x6A..=0x78 | 0x7C..=0x8A | 0xA0..=0xA6 => Some(2),
+        _ => None,
+    }
+}
+
+/// An enclosing block while validating: the operand stack height at its
+/// start, the values it leaves, the values a branch to it carries, and
+/// whether the code since the last branch is unreachable.
+struct Block {
+    height: usize,
+    results: usize,
+    branch: usize,
+    unreachable: bool,
+}
+
+/// Check that a defined function only uses instructions `run` implements,
+/// keeps its operand stack balanced and names only indices that exist.
+fn validate(func: &Func, funcs: &[Func], globals: usize, memory: bool) -> Result<(), String> {
+    let Func::Code { ty, locals, code, .. } = func else { return Ok(()) };
+    let mut blocks = vec![Block { height: 0, results: ty.results.len(), branch: ty.results.len(), unreachable: false }];
+    let mut height = 0;
+    for (i, Instr { opcode, imm }) in code.iter().enumerate() {
+        let name = decode::name(*opcode).unwrap_or("?");
+        let block = blocks.last().ok_or("instructions follow the function's `end`")?;
+        let (start, unreachable) = (block.height, block.unreachable);
+        // After an unconditional branch the stack is polymorphic: popping never underflows.
+        let pop = |n: usize, height: &mut usize| -> Result<(), String> {
+            match height.checked_sub(n).filter(|h| *h >= start) {
+                Some(h) => *height = h,
+                None if unreachable => *height = start,
+                None => return Err(format!("`{}` underflows the operand stack", name)),
+            }
+            Ok(())
+        };
+        let label = |depth: u32| -> Result<usize, String> {
+            blocks.len().checked_sub(depth as usize + 1).map(|i| blocks[i].branch).ok_or_else(|| format!("`{}` targets no enclosing block", name))
+        };
+        let needs_memory = || if memory { Ok(()) } else { Err(format!("`{}` needs a memory", name)) };
+        let (pops, pushes) = match (*opcode, imm) {
+            (0x00, _) | (0x0C, _) | (0x0E, _) | (0x0F, _) => {
+                let carried = match imm {
+                    Imm::Index(depth) => label(*depth)?,
+                    Imm::BrTable(table, default) => {
+                        let arity = label(*default)?;
+                        if table.iter().map(|&d| label(d)).collect::<Result<Vec<_>, _>>()?.iter().any(|&a| a != arity) {
+                            return Err("`br_table` targets blocks of different arities".into());
+                        }
+                        arity
+                    }
+                    _ if *opcode == 0x0F => ty.results.len(),
+                    _ => 0,
+                };
+                pop(carried + (*opcode == 0x0E) as usize, &mut height)?;
+                let block = blocks.last_mut().expect("checked above");
+                height = block.height;
+                block.unreachable = true;
+                continue;
+            }
+            (0x01, _) => (0, 0),
+            (0x02..=0x04, Imm::Block(result)) => {
+                if *opcode == 0x04 { pop(1, &mut height)?; }
+                let results = result.is_some() as usize;
+                blocks.push(Block { height, results, branch: if *opcode == 0x03 { 0 } else { results }, unreachable: false });
+                continue;
+            }
+            (0x05 | 0x0B, _) => {
+                let block = blocks.last_mut().expect("checked above");
+                if height != block.height + block.results && !(block.unreachable && height == block.height) {
+                    return Err(format!("a block ends with {} value(s) instead of {} at instruction {}", height - block.height, block.results, i));
+                }
+                height = block.height;
+                block.unreachable = false;
+                if *opcode == 0x0B {
+                    height += block.results;
+                    blocks.pop();
+                }
+                continue;
+            }
+            (0x0D, Imm::Index(depth)) => {
+                let carried = label(*depth)?;
+                pop(carried + 1, &mut height)?;
+                (0, carried)
+            }
+            (0x10, Imm::Index(f)) => {
+                let callee = funcs.get(*f as usize).ok_or_else(|| format!("`call` names no function {}", f))?.ty();
+                (callee.params.len(), callee.results.len())
+            }
+            (0x1A, _) => (1, 0),
+            (0x1B, _) => (3, 1),
+            (0x20..=0x22, Imm::Index(l)) if *l as usize >= locals.len() => return Err(format!("`{}` names no local {}", name, l)),
+            (0x23 | 0x24, Imm::Index(g)) if *g as usize >= globals => return Err(format!("`{}` names no global {}", name, g)),
+            (0x20 | 0x23, Imm::Index(_)) => (0, 1),
+            (0x21 | 0x24, Imm::Index(_)) => (1, 0),
+            (0x22, Imm::Index(_)) => (1, 1),
+            (0x28..=0x35, Imm::Mem { .. }) | (0x40, _) => { needs_memory()?; (1, 1) }
+            (0x36..=0x3E, Imm::Mem { .. }) => { needs_memory()?; (2, 0) }
+            (0x3F, _) => { needs_memory()?; (0, 1) }
+            (0xFC0A | 0xFC0B, _) => { needs_memory()?; (3, 0) }
+            (0x41 | 0x42 | 0x44, _) => (0, 1),
+            (op, Imm::None) => (numeric_arity(op).ok_or_else(|| format!("`{}` is not supported", name))?, 1),
+            _ => return Err(format!("`{}` has the wrong immediate", name)),
+        };
+        pop(pops, &mut height)?;
+        height += pushes;
+    }
+    if !blocks.is_empty() { return Err("the body has no final `end`".into()); }
+    Ok(())
+}
+
+/// Match every structured instruction to its `else` and `end`.
+fn jumps(code: &[Instr]) -> Result<Vec<(usize, usize)>, String> {
+    let mut jumps = vec![(0, 0); code.len()];
+    let mut open: Vec<(usize, Option<usize>)> = Vec::new();
+    for (i, instr) in code.iter().enumerate() {
+        match instr.opcode {
+            0x02..=0x04 => open.push((i, None)),
+            0x05 => open.last_mut().ok_or("`else` outside `if`")?.1 = Some(i),
+            0x0B => if let Some((start, else_)) = open.pop() {
+                jumps[start] = (else_.unwrap_or(i), i);
+                if let Some(e) = else_ { jumps[e] = (i, i); }
+            },
+            _ => {}
+        }
+    }
+    if !open.is_empty() { return Err("unterminated block".into()); }
+    Ok(jumps)
+}
+
+/// A value's representation in an operand stack slot.
+trait Slot: Copy {
+    fn from_slot(bits: u64) -> Self;
+    fn to_slot(self) -> u64;
+}
+
+impl Slot for i32 { fn from_slot(b: u64) -> Self { b as i32 } fn to_slot(self) -> u64 { self as u32 as u64 } }
+impl Slot for u32 { fn from_slot(b: u64) -> Self { b as u32 } fn to_slot(self) -> u64 { self as u64 } }
+impl Slot for i64 { fn from_slot(b: u64) -> Self { b as i64 } fn to_slot(self) -> u64 { self as u64 } }
+impl Slot for u64 { fn from_slot(b: u64) -> Self { b } fn to_slot(self) -> u64 { self } }
+impl Slot for f64 { fn from_slot(b: u64) -> Self { f64::from_bits(b) } fn to_slot(self) -> u64 { self.to_bits() } }
+impl Slot for bool { fn from_slot(b: u64) -> Self { b != 0 } fn to_slot(self) -> u64 { self as u64 } }
+
+/// Arithmetic, comparison and conversion instructions.
+fn numeric(opcode: u16, stack: &mut Vec<u64>) -> Result<(), Trap> {
+    macro_rules! un {
+        ($t:ty, |$a:ident| $e:expr) => {{ let $a = <$t>::from_slot(pop(stack)?); stack.push(Slot::to_slot($e)); }};
+    }
+    macro_rules! bin {
+        ($t:ty, |$a:ident, $b:ident| $e:expr) => {{
+            let $b = <$t>::from_slot(pop(stack)?);
+            let $a = <$t>::from_slot(pop(stack)?);
+            stack.push(Slot::to_slot($e));
+        }};
+    }
+    match opcode {
+        0x45 => un!(i32, |a| a == 0),
+        0x46 => bin!(i32, |a, b| a == b),
+        0x47 => bin!(i32, |a, b| a != b),
+        0x48 => bin!(i32, |a, b| a < b),
+        0x49 => bin!(u32, |a, b| a < b),
+        0x4A => bin!(i32, |a, b| a > b),
+        0x4B => bin!(u32, |a, b| a > b),
+        0x4C => bin!(i32, |a, b| a <= b),
+        0x4D => bin!(u32, |a, b| a <= b),
+        0x4E => bin!(i32, |a, b| a >= b),
+        0x4F => bin!(u32, |a, b| a >= b),
+        0x50 => un!(i64, |a| a == 0),
+        0x51 => bin!(i64, |a, b| a == b),
+        0x52 => bin!(i64, |a, b| a != b),
+        0x53 => bin!(i64, |a, b| a < b),
+        0x54 => bin!(u64, |a, b| a < b),
+        0x55 => bin!(i64, |a, b| a > b),
+        0x56 => bin!(u64, |a, b| a > b),
+        0x57 => bin!(i64, |a, b| a <= b),
+        0x58 => bin!(u64, |a, b| a <= b),
+        0x59 => bin!(i64, |a, b| a >= b),
+        0x5A => bin!(u64, |a, b| a >= b),
+        0x61 => bin!(f64, |a, b| a == b),
+        0x62 => bin!(f64, |a, b| a != b),
+        0x63 => bin!(f64, |a, b| a < b),
+        0x64 => bin!(f64, |a, b| a > b),
+        0x65 => bin!(f64, |a, b| a <= b),
+        0x66 => bin!(f64, |a, b| a >= b),
+        0x67 => un!(i32, |a| a.leading_zeros()),
+        0x68 => un!(i32, |a| a.trailing_zeros()),
+        0x69 => un!(i32, |a| a.count_ones()),
+        0x6A => bin!(i32, |a, b| a.wrapping_add(b)),
+        0x6B => bin!(i32, |a, b| a.wrapping_sub(b)),
+        0x6C => bin!(i32, |a, b| a.wrapping_mul(b)),
+        0x6D => bin!(i32, |a, b| if b == 0 { return Err(Trap::DivisionByZero) } else { a.checked_div(b).ok_or(Trap::IntegerOverflow)? }),
+        0x6E => bin!(u32, |a, b| a.checked_div(b).ok_or(Trap::DivisionByZero)?),
+        0x6F => bin!(i32, |a, b| if b == 0 { return Err(Trap::DivisionByZero) } else { a.wrapping_rem(b) }),
+        0x70 => bin!(u32, |a, b| a.checked_rem(b).ok_or(Trap::DivisionByZero)?),
+        0x71 => bin!(i32, |a, b| a & b),
+        0x72 => bin!(i32, |a, b| a | b),
+        0x73 => bin!(i32, |a, b| a ^ b),
+        0x74 => bin!(i32, |a, b| a.wrapping_shl(b as u32)),
+        0x75 => bin!(i32, |a, b| a.wrapping_shr(b as u32)),
+        0x76 => bin!(u32, |a, b| a.wrapping_shr(b)),
+        0x77 => bin!(u32, |a, b| a.rotate_left(b % 32)),
+        0x78 => bin!(u32, |a, b| a.rotate_right(b % 32)),
+        0x79 => un!(i64, |a| a.leading_zeros() as i64),
+        0x7A => un!(i64, |a| a.trailing_zeros() as i64),
+        0x7B => un!(i64, |a| a.count_ones() as i64),
+        0x7C => bin!(i64, |a, b| a.wrapping_add(b)),
+        0x7D => bin!(i64, |a, b| a.wrapping_sub(b)),
+        0x7E => bin!(i64, |a, b| a.wrapping_mul(b)),
+        0x7F => bin!(i64, |a, b| if b == 0 { return Err(Trap::DivisionByZero) } else { a.checked_div(b).ok_or(Trap::IntegerOverflow)? }),
+        0x80 => bin!(u64, |a, b| a.checked_div(b).ok_or(Trap::DivisionByZero)?),
+        0x81 => bin!(i64, |a, b| if b == 0 { return Err(Trap::DivisionByZero) } else { a.wrapping_rem(b) }),
+        0x82 => bin!(u64, |a, b| a.checked_rem(b).ok_or(Trap::DivisionByZero)?),
+        0x83 => bin!(i64, |a, b| a & b),
+        0x84 => bin!(i64, |a, b| a | b),
+        0x85 => bin!(i64, |a, b| a ^ b),
+        0x86 => bin!(i64, |a, b| a.wrapping_shl(b as u32)),
+        0x87 => bin!(i64, |a, b| a.wrapping_shr(b as u32)),
+        0x88 => bin!(u64, |a, b| a.wrapping_shr(b as u32)),
+        0x89 => bin!(u64, |a, b| a.rotate_left((b % 64) as u32)),
+        0x8A => bin!(u64, |a, b| a.rotate_right((b % 64) as u32)),
+        0x99 => un!(f64, |a| a.abs()),
+        0x9A => un!(f64, |a| -a),
+        0x9B => un!(f64, |a| a.ceil()),
+        0x9C => un!(f64, |a| a.floor()),
+        0x9D => un!(f64, |a| a.trunc()),
+        0x9E => un!(f64, |a| a.round_ties_even()),
+        0x9F => un!(f64, |a| a.sqrt()),
+        0xA0 => bin!(f64, |a, b| a + b),
+        0xA1 => bin!(f64, |a, b| a - b),
+        0xA2 => bin!(f64, |a, b| a * b),
+        0xA3 => bin!(f64, |a, b| a / b),
+        0xA4 => bin!(f64, |a, b| if a.is_nan() || b.is_nan() { f64::NAN } else if a == b { if a.is_sign_negative() { a } else { b } } else { a.min(b) }),
+        0xA5 => bin!(f64, |a, b| if a.is_nan() || b.is_nan() { f64::NAN } else if a == b { if a.is_sign_positive() { a } else { b } } else { a.max(b) }),
+        0xA6 => bin!(f64, |a, b| a.copysign(b)),
+        0xA7 => un!(i64, |a| a as i32),
+        0xAA => un!(f64, |a| truncate(a, -2147483649.0, 2147483648.0)? as i32),
+        0xAB => un!(f64, |a| truncate(a, -1.0, 4294967296.0)? as u32),
+        0xAC => un!(i32, |a| a as i64),
+        0xAD => un!(u32, |a| a as u64),
+        0xB0 => un!(f64, |a| truncate(a, -9223372036854777856.0, 9223372036854775808.0)? as i64),
+        0xB1 => un!(f64, |a| truncate(a, -1.0, 18446744073709551616.0)? as u64),
+        0xB7 => un!(i32, |a| a as f64),
+        0xB8 => un!(u32, |a| a as f64),
+        0xB9 => un!(i64, |a| a as f64),
+        0xBA => un!(u64, |a| a as f64),
+        // The slot already holds the bits.
+        0xBD | 0xBF => {}
+        // `as` saturates and maps NaN to 0, as `trunc_sat` does.
+        0xFC02 => un!(f64, |a| a as i32),
+        0xFC03 => un!(f64, |a| a as u32),
+        0xFC06 => un!(f64, |a| a as i64),
+        0xFC07 => un!(f64, |a| a as u64),
+        _ => return Err(Trap::Malformed("an instruction is not supported")),
+    }
+    Ok(())
+}
+
+/// `a` truncated toward zero, which must lie strictly between `min` and `max`.
+fn truncate(a: f64, min: f64, max: f64) -> Result<f64, Trap> {
+    if a.is_nan() { return Err(Trap::InvalidConversion); }
+    let t = a.trunc();
+    if t <= min || t >= max { return Err(Trap::IntegerOverflow); }
+    Ok(t)
+}
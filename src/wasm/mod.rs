@@ -0,0 +1,51 @@
+//! WebAssembly binary format support shared by the code generator and tooling.
+
+pub mod decode;
+mod encode;
+pub mod interp;
+mod wat;
+
+pub use encode::{write_sleb, write_uleb, ModuleBuilder};
+pub use wat::to_wat;
+
+/// WASM value types (binary encodings per the MVP spec).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F64,
+}
+
+impl ValType {
+    pub fn byte(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+            ValType::F64 => 0x7C,
+        }
+    }
+
+    pub fn from_byte(b: u8) -> Option<ValType> {
+        match b {
+            0x7F => Some(ValType::I32),
+            0x7E => Some(ValType::I64),
+            0x7C => Some(ValType::F64),
+            _ => None,
+        }
+    }
+
+    /// The text-format name, e.g. `i64`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F64 => "f64",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
@@ -0,0 +1,101 @@
+//! Printing modules in the WebAssembly text format, for `--emit=wat`.
+//! Instructions are printed flat (not folded), indented by block depth.
+
+use std::fmt::Write;
+
+use super::decode::{self, ExportKind, Imm, Instr, Reader};
+use super::FuncType;
+
+/// Disassemble a binary module into WAT.
+pub fn to_wat(bytes: &[u8]) -> Result<String, String> {
+    let m = decode::module(bytes)?;
+    let mut out = String::from("(module\n");
+    for (i, ty) in m.types.iter().enumerate() {
+        writeln!(out, "  (type (;{};) (func{}))", i, signature(ty)).unwrap();
+    }
+    for (i, import) in m.imports.iter().enumerate() {
+        writeln!(out, "  (import {} {} (func (;{};) (type {})))", string(import.module.as_bytes()), string(import.name.as_bytes()), i, import.type_idx).unwrap();
+    }
+    if let Some(pages) = m.memory {
+        writeln!(out, "  (memory (;0;) {})", pages).unwrap();
+    }
+    for (i, global) in m.globals.iter().enumerate() {
+        let ty = if global.mutable { format!("(mut {})", global.ty.name()) } else { global.ty.name().to_string() };
+        writeln!(out, "  (global (;{};) {} ({}))", i, ty, instruction(&global.init)).unwrap();
+    }
+    for export in &m.exports {
+        let kind = match export.kind { ExportKind::Func => "func", ExportKind::Memory => "memory", ExportKind::Global => "global" };
+        writeln!(out, "  (export {} ({} {}))", string(export.name.as_bytes()), kind, export.index).unwrap();
+    }
+    for (i, body) in m.bodies.iter().enumerate() {
+        let idx = m.imports.len() + i;
+        let ty = &m.types[m.functions[i] as usize];
+        writeln!(out, "  (func (;{};) (type {}){}", idx, m.functions[i], signature(ty)).unwrap();
+        for &(count, ty) in &body.locals {
+            let name = super::ValType::from_byte(ty).map_or("?", super::ValType::name);
+            writeln!(out, "    (local{})", format!(" {}", name).repeat(count as usize)).unwrap();
+        }
+        let mut r = Reader::new(body.code);
+        let mut depth = 2;
+        while !r.at_end() {
+            let instr = decode::instr(&mut r)?;
+            if matches!(instr.opcode, 0x05 | 0x0B) { depth -= 1; }
+            if r.at_end() { break; } // the body's own `end` closes the `(func`
+            writeln!(out, "{}{}", "  ".repeat(depth), instruction(&instr)).unwrap();
+            if matches!(instr.opcode, 0x02..=0x05) { depth += 1; }
+        }
+        out.push_str("  )\n");
+    }
+    for (i, (offset, bytes)) in m.data.iter().enumerate() {
+        writeln!(out, "  (data (;{};) (i32.const {}) {})", i, offset, string(bytes)).unwrap();
+    }
+    out.push_str(")\n");
+    Ok(out)
+}
+
+fn signature(ty: &FuncType) -> String {
+    let mut out = String::new();
+    if !ty.params.is_empty() { write!(out, " (param{})", ty.params.iter().map(|t| format!(" {}", t.name())).collect::<String>()).unwrap(); }
+    if !ty.results.is_empty() { write!(out, " (result{})", ty.results.iter().map(|t| format!(" {}", t.name())).collect::<String>()).unwrap(); }
+    out
+}
+
+fn instruction(instr: &Instr) -> String {
+    let name = decode::name(instr.opcode).expect("decoded opcodes have names");
+    match &instr.imm {
+        Imm::None => name.to_string(),
+        Imm::Block(None) => name.to_string(),
+        Imm::Block(Some(t)) => format!("{} (result {})", name, t.name()),
+        Imm::Index(i) => format!("{} {}", name, i),
+        Imm::Mem { align, offset: 0 } => format!("{} align={}", name, 1u32 << align),
+        Imm::Mem { align, offset } => format!("{} offset={} align={}", name, offset, 1u32 << align),
+        Imm::I32(v) => format!("{} {}", name, v),
+        Imm::I64(v) => format!("{} {}", name, v),
+        Imm::F32(v) => format!("{} {}", name, float(*v as f64)),
+        Imm::F64(v) => format!("{} {}", name, float(*v)),
+        Imm::BrTable(labels, default) => format!("{}{} {}", name, labels.iter().map(|l| format!(" {}", l)).collect::<String>(), default),
+    }
+}
+
+fn float(v: f64) -> String {
+    match v {
+        _ if v.is_nan() => "nan".into(),
+        f64::INFINITY => "inf".into(),
+        f64::NEG_INFINITY => "-inf".into(),
+        _ => format!("{:?}", v),
+    }
+}
+
+/// A WAT string literal; bytes outside printable ASCII are `\hh` escapes.
+fn string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => { out.push('\\'); out.push(b as char); }
+            0x20..=0x7E => out.push(b as char),
+            _ => write!(out, "\\{:02x}", b).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
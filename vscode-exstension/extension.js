@@ -0,0 +1,21 @@
+// Starts `mintora lsp` for Mintora files. The server binary is looked up on
+// PATH unless `mintora.server.path` points somewhere else.
+const vscode = require('vscode');
+const { LanguageClient } = require('vscode-languageclient/node');
+
+let client;
+
+function activate(context) {
+    const command = vscode.workspace.getConfiguration('mintora').get('server.path') || 'mintora';
+    const server = { command, args: ['lsp'] };
+    client = new LanguageClient('mintora', 'Mintora Language Server', server, {
+        documentSelector: [{ scheme: 'file', language: 'mintora' }],
+    });
+    context.subscriptions.push(client.start());
+}
+
+function deactivate() {
+    return client ? client.stop() : undefined;
+}
+
+module.exports = { activate, deactivate };